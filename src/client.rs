@@ -1,22 +1,79 @@
-use crate::data::packet::{Packet, PacketDetail};
+mod login;
+mod message;
+mod push;
+mod qrcode;
+mod reconnect;
+mod request;
+mod stat;
+mod sync;
+mod token;
+
+pub use login::{LoginResponse, LoginVerifier};
+pub use push::PushHandler;
+pub use qrcode::{QrCode, QrCodeLoginInfo, QrCodeState};
+pub use reconnect::Backoff;
+pub use request::Request;
+
+use crate::client::login::LoginState;
+use crate::client::message::{SendMessage, Target};
+use crate::client::push::{MessageParts, PushHandlers};
+use crate::client::reconnect::Reconnect;
+use crate::client::sync::SyncState;
+use crate::crypto::ecdh::{Ecdh, ServerPublicKey};
+use crate::crypto::Transport;
+use crate::data::device::DeviceInfo;
+use crate::data::oicq::{OicqEncrypt, OicqPacket, OicqResponse};
+use crate::data::packet::{Encrypt, Packet, PacketDetail, PacketError};
+use crate::data::protocol::ProtocolInfo;
 use crate::error::ClientError;
-use crate::event::ClientEvent;
-use crate::executor::Executor;
-use crate::net::connector::Connector;
+use crate::event::{ClientEvent, EventKind, Lagged};
+use crate::executor::{Executor, JoinHandle, Timeout, Timer};
+use crate::message::{MessageChain, MessageReceipt};
+use crate::net::connector::{Connector, ConnectorFactory};
+use crate::net::framed::FramedConnector;
+use crate::net::server::ServerList;
+use crate::sync::{broadcast, Mutex};
+use bytes::Bytes;
 use dashmap::DashMap;
 use futures::channel::mpsc::{UnboundedReceiver, UnboundedSender};
 use futures::channel::oneshot;
-use futures::StreamExt;
+use futures::future::{self, AbortHandle, BoxFuture, FutureExt, Shared};
+use futures::stream::BoxStream;
+use futures::{Sink, SinkExt, Stream, StreamExt};
+use std::borrow::Cow;
+use std::fmt;
 use std::future::Future;
+use std::io;
 use std::pin::Pin;
-use std::sync::atomic::{AtomicU16, Ordering};
-use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, AtomicU16, Ordering};
+use std::sync::{Arc, Weak};
 use std::task::{Context, Poll};
+use std::time::Duration;
 
 pub struct RequestClient {
     uin: u64,
     seq: AtomicU16,
-    seq_packet_sender: DashMap<u16, oneshot::Sender<Packet>>,
+    /// The seq of the next message sent, kept apart from the packet seq.
+    message_seq: AtomicU16,
+    /// `const1` and `const2` of the sync cookies sent along with friend messages and the
+    /// first `MessageSvc.PbGetMsg`, picked once per client.
+    sync_cookie_consts: (u32, u32),
+    /// Whoever waits for the response to a seq, along with the command it was sent with.
+    seq_packet_sender: DashMap<u16, (Cow<'static, str>, oneshot::Sender<PacketResult>)>,
+    push_handlers: PushHandlers,
+    protocol: ProtocolInfo,
+    device: DeviceInfo,
+    ecdh: Ecdh,
+    transport: Mutex<Transport>,
+    login_state: Mutex<LoginState>,
+    /// Where fetching the messages announced by `MessageSvc.PushNotify` left off.
+    sync_state: Mutex<SyncState>,
+    /// Set once `StatSvc.register` went through, so a reconnect registers again.
+    online: AtomicBool,
+    /// Where to reconnect, kept up to date by `ConfigPushSvc.PushReq`.
+    servers: Mutex<ServerList>,
+    /// The parts of group messages split over several pushes, until all have arrived.
+    group_message_parts: Mutex<MessageParts>,
 }
 
 impl RequestClient {
@@ -24,15 +81,98 @@ impl RequestClient {
         Self {
             uin: 0,
             seq: AtomicU16::new(0),
+            message_seq: AtomicU16::new(rand::random()),
+            sync_cookie_consts: rand::random(),
             seq_packet_sender: DashMap::new(),
+            push_handlers: PushHandlers::new(),
+            protocol: ProtocolInfo::ANDROID_WATCH,
+            device: DeviceInfo::random(),
+            ecdh: Ecdh::new(),
+            transport: Mutex::new(Transport::new(0)),
+            login_state: Mutex::new(LoginState::default()),
+            sync_state: Mutex::new(SyncState::default()),
+            online: AtomicBool::new(false),
+            servers: Mutex::new(ServerList::default()),
+            group_message_parts: Mutex::new(MessageParts::default()),
         }
     }
 
+    #[inline]
+    pub fn uin(&self) -> u64 {
+        self.uin
+    }
+
     pub fn next_seq(&self) -> u16 {
         self.seq.fetch_add(1, Ordering::Relaxed)
     }
 
-    pub async fn decode_packet(&self, payload: &[u8]) {}
+    pub(crate) fn next_message_seq(&self) -> i32 {
+        self.message_seq.fetch_add(1, Ordering::Relaxed) as i32
+    }
+
+    /// Decodes an incoming sso frame (length removed).
+    ///
+    /// Server pushes go through their [`PushHandler`], and the event it makes is returned.
+    /// Anything else is a response, handed to whoever waits for its seq and command.
+    pub async fn decode_packet(&self, payload: &[u8]) -> Option<EventKind> {
+        // the frame header says which key, d2 key or none, the body is under
+        let d2_key = self.transport.lock().st.d2_key;
+        let (seq, rsp) =
+            Packet::decode_sso_response(Bytes::copy_from_slice(payload), &d2_key).ok()?;
+
+        let rsp = match rsp {
+            Ok(pkt) => match self.push_handlers.get(&pkt.command) {
+                Some(handler) => return handler(self, pkt),
+                None => Ok(pkt),
+            },
+            Err(e) => Err(e),
+        };
+
+        self.complete(seq, rsp);
+        None
+    }
+
+    /// Registers interest in the response to `command`, sent under `seq`.
+    fn register(&self, seq: u16, command: Cow<'static, str>) -> oneshot::Receiver<PacketResult> {
+        let (tx, rx) = oneshot::channel();
+        self.seq_packet_sender.insert(seq, (command, tx));
+        rx
+    }
+
+    /// Hands a response to its waiter, giving it back if nobody waits for it.
+    ///
+    /// A packet only completes the request of the same seq and command, pushes the
+    /// server numbers on its own may collide with the seq of a pending request. Failures
+    /// carry no command and go to whoever waits for their seq.
+    fn complete(&self, seq: u32, rsp: PacketResult) -> Option<PacketResult> {
+        let seq = seq as u16;
+        let waiter = match &rsp {
+            Ok(pkt) => self
+                .seq_packet_sender
+                .remove_if(&seq, |_, (command, _)| *command == pkt.command),
+            Err(_) => self.seq_packet_sender.remove(&seq),
+        };
+
+        match waiter {
+            Some((_, (_, tx))) => tx.send(rsp).err(),
+            None => Some(rsp),
+        }
+    }
+
+    /// Fails every pending request, once the connection is gone.
+    pub(crate) fn cancel_pending(&self) {
+        self.seq_packet_sender.clear();
+    }
+
+    /// A snapshot of the session keys, suitable for persisting with [`Transport::to_bytes`].
+    pub fn session(&self) -> Transport {
+        self.transport.lock().clone()
+    }
+
+    /// A snapshot of the candidate servers, fastest first.
+    pub fn servers(&self) -> ServerList {
+        self.servers.lock().clone()
+    }
 }
 
 impl Default for RequestClient {
@@ -42,14 +182,41 @@ impl Default for RequestClient {
     }
 }
 
+/// A handle to the session started by [`ClientBuilder::run`], cheap to clone.
+///
+/// The session ends once every handle is dropped, including those carried by
+/// [events](ClientEvent).
+#[derive(Clone)]
 pub struct Client {
-    base: Arc<RequestClient>,
-    stop_sig: oneshot::Sender<()>,
-    request_sender: UnboundedSender<Packet>,
+    handle: Arc<Handle>,
 }
 
 type ClientResult<T> = Result<T, ClientError>;
 
+type PacketResult = Result<Packet, PacketError>;
+
+struct Handle {
+    requester: Requester,
+    teardown: Arc<Teardown>,
+    /// The supervisor of the session, done once the session is.
+    closed: Shared<JoinHandle<()>>,
+    subscribers: broadcast::Subscribers<EventKind>,
+}
+
+impl Drop for Handle {
+    fn drop(&mut self) {
+        self.teardown.stop();
+    }
+}
+
+impl fmt::Debug for Client {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Client")
+            .field("uin", &self.uin())
+            .finish_non_exhaustive()
+    }
+}
+
 impl Client {
     #[inline]
     pub fn builder() -> ClientBuilder<(), (), ()> {
@@ -58,12 +225,363 @@ impl Client {
 }
 
 impl Client {
+    #[inline]
+    fn requester(&self) -> &Requester {
+        &self.handle.requester
+    }
+
     #[inline]
     pub fn uin(&self) -> u64 {
-        self.base.uin
+        self.requester().base.uin()
+    }
+
+    #[inline]
+    pub fn session(&self) -> Transport {
+        self.requester().base.session()
+    }
+
+    /// The servers a broken session reconnects to, as last ranked.
+    #[inline]
+    pub fn servers(&self) -> ServerList {
+        self.requester().base.servers()
+    }
+
+    /// Ends the session started by [`ClientBuilder::run`], for every handle to it.
+    #[inline]
+    pub fn stop(self) {
+        self.handle.teardown.stop();
+    }
+
+    /// Waits for the session to end, such as by the connection breaking.
+    pub async fn closed(&self) {
+        let _ = self.handle.closed.clone().await;
+    }
+
+    /// Streams every event from now on, as the handler gets them too.
+    ///
+    /// Up to [`ClientBuilder::with_event_capacity`] events wait for a subscriber that
+    /// falls behind. Older ones are dropped, reported as [`Lagged`] in their place.
+    /// The stream ends along with the session.
+    ///
+    /// ```no_run
+    /// # use atri_core::client::Client;
+    /// # use atri_core::event::EventKind;
+    /// # async fn f(client: Client) {
+    /// use futures::StreamExt;
+    ///
+    /// let messages = client.subscribe().filter_map(|event| async move {
+    ///     match event.ok()?.kind {
+    ///         EventKind::GroupMessage(msg) => Some(msg),
+    ///         _ => None,
+    ///     }
+    /// });
+    /// let mut messages = std::pin::pin!(messages);
+    /// while let Some(msg) = messages.next().await {
+    ///     println!("{}: {}", msg.sender, msg.chain.text());
+    /// }
+    /// # }
+    /// ```
+    pub fn subscribe(&self) -> EventStream {
+        EventStream {
+            events: self.handle.subscribers.subscribe(),
+            handle: Arc::downgrade(&self.handle),
+        }
+    }
+
+    /// Takes the session online with `StatSvc.register`, after a login.
+    ///
+    /// Fails with [`ClientError::Refused`] if the server does not accept the tickets.
+    pub async fn register(&self) -> ClientResult<()> {
+        self.requester().register().await?;
+        let _ = self
+            .handle
+            .teardown
+            .events
+            .unbounded_send(EventKind::Online);
+        Ok(())
+    }
+
+    /// Exchanges A2 for new tickets over the running session.
+    ///
+    /// Done in the background once D2 gets close to expiring.
+    #[inline]
+    pub async fn refresh_token(&self) -> ClientResult<()> {
+        self.requester().refresh_token().await
+    }
+
+    /// Sends `packet` under a fresh seq and waits for the response carrying it.
+    ///
+    /// Fails with [`ClientError::Timeout`] if nothing arrives within `timeout`.
+    #[inline]
+    pub async fn send_and_wait(&self, packet: Packet, timeout: Duration) -> ClientResult<Packet> {
+        self.requester().send_and_wait(packet, timeout).await
+    }
+
+    /// Sends a typed request, waiting up to [`DEFAULT_TIMEOUT`] for its response.
+    #[inline]
+    pub async fn call<R: Request>(&self, req: R) -> ClientResult<R::Response> {
+        self.call_timeout(req, DEFAULT_TIMEOUT).await
+    }
+
+    #[inline]
+    pub async fn call_timeout<R: Request>(
+        &self,
+        req: R,
+        timeout: Duration,
+    ) -> ClientResult<R::Response> {
+        self.requester().call_timeout(req, timeout).await
+    }
+
+    /// Sends `chain` to `group` with `MessageSvc.PbSendMsg`.
+    ///
+    /// The group numbers its messages itself, the seq is read off the echo of the message
+    /// pushed back to this account. Without an echo within a few seconds, or with events
+    /// dropped while waiting for it, the message is still delivered but the receipt
+    /// carries no seqs, only the rand and the time the server gave.
+    ///
+    /// Fails with [`ClientError::Refused`] if the server turns the message down, such as
+    /// when muted.
+    /// Fails with [`ClientError::InvalidUin`] if an at in `chain` names a uin above
+    /// `u32::MAX`.
+    pub async fn send_group_message(
+        &self,
+        group: u64,
+        chain: MessageChain,
+    ) -> ClientResult<MessageReceipt> {
+        let uin = self.uin();
+        let seq = self.requester().base.next_message_seq();
+        let rand = rand::random_range(0..i32::MAX);
+        // subscribed before sending, so the echo cannot come first
+        let events = self.handle.subscribers.subscribe();
+
+        let time = self
+            .call(SendMessage {
+                target: Target::Group(group),
+                seq,
+                rand,
+                rich_text: chain.to_rich_text()?,
+            })
+            .await?;
+
+        let echo = events
+            .filter_map(|event| {
+                future::ready(match event {
+                    Ok(EventKind::GroupMessage(msg))
+                        if msg.group == group && msg.sender == uin && msg.id.rand == rand =>
+                    {
+                        Some(Some(msg.id))
+                    }
+                    // the echo may be among the events dropped
+                    Err(_) => Some(None),
+                    _ => None,
+                })
+            })
+            .into_future();
+
+        match Timeout::new(echo, (self.requester().sleep)(ECHO_TIMEOUT)).await {
+            Some((Some(Some(id)), _)) => Ok(MessageReceipt {
+                seqs: vec![id.seq],
+                rands: vec![rand],
+                time: id.time,
+            }),
+            // the seq sent is not the one the group gave it, so none is better than a wrong one
+            _ => Ok(MessageReceipt {
+                seqs: vec![],
+                rands: vec![rand],
+                time,
+            }),
+        }
+    }
+
+    /// Sends `chain` to the friend `uin` with `MessageSvc.PbSendMsg`.
+    ///
+    /// Fails with [`ClientError::Refused`] if the server turns the message down, or with
+    /// [`ClientError::InvalidUin`] if an at in `chain` names a uin above `u32::MAX`.
+    pub async fn send_friend_message(
+        &self,
+        uin: u64,
+        chain: MessageChain,
+    ) -> ClientResult<MessageReceipt> {
+        let seq = self.requester().base.next_message_seq();
+        let rand = rand::random_range(0..i32::MAX);
+
+        let time = self
+            .call(SendMessage {
+                target: Target::Friend(uin),
+                seq,
+                rand,
+                rich_text: chain.to_rich_text()?,
+            })
+            .await?;
+
+        Ok(MessageReceipt {
+            seqs: vec![seq],
+            rands: vec![rand],
+            time,
+        })
     }
 }
 
+/// The events of a session, made by [`Client::subscribe`].
+pub struct EventStream {
+    events: broadcast::Receiver<EventKind>,
+    /// Only upgraded for an event, the stream does not keep the session alive.
+    handle: Weak<Handle>,
+}
+
+impl Stream for EventStream {
+    type Item = Result<ClientEvent, Lagged>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let kind = match futures::ready!(self.events.poll_next_unpin(cx)) {
+            Some(Ok(kind)) => kind,
+            Some(Err(missed)) => return Poll::Ready(Some(Err(Lagged(missed)))),
+            None => return Poll::Ready(None),
+        };
+
+        Poll::Ready(self.handle.upgrade().map(|handle| {
+            Ok(ClientEvent {
+                client: Client { handle },
+                kind,
+            })
+        }))
+    }
+}
+
+/// Sends requests through the session, shared by the [`Client`] and its background tasks.
+#[derive(Clone)]
+struct Requester {
+    base: Arc<RequestClient>,
+    request_sender: UnboundedSender<Packet>,
+    sleep: SleepFn,
+}
+
+/// Sleeps on the [`Timer`] of the executor the session runs on.
+type SleepFn = Arc<dyn Fn(Duration) -> BoxFuture<'static, ()> + Send + Sync>;
+
+fn sleep_on<T>(timer: T) -> SleepFn
+where
+    T: Timer + Send + Sync + 'static,
+{
+    Arc::new(move |duration| timer.sleep(duration).boxed())
+}
+
+impl Requester {
+    async fn send_and_wait(&self, packet: Packet, timeout: Duration) -> ClientResult<Packet> {
+        let seq = self.base.next_seq();
+        self.send_with_seq(seq, packet, timeout).await
+    }
+
+    async fn send_with_seq(
+        &self,
+        seq: u16,
+        mut packet: Packet,
+        timeout: Duration,
+    ) -> ClientResult<Packet> {
+        packet.seq = seq as u32;
+
+        let rx = self.base.register(seq, packet.command.clone());
+        // removes the entry however this future ends, even if it is dropped
+        let _pending = Pending {
+            client: &self.base,
+            seq,
+        };
+
+        self.request_sender
+            .unbounded_send(packet)
+            .map_err(|_| disconnected())?;
+
+        match Timeout::new(rx, (self.sleep)(timeout)).await {
+            Some(Ok(rsp)) => Ok(rsp?),
+            Some(Err(_)) => Err(disconnected()),
+            None => Err(ClientError::Timeout),
+        }
+    }
+
+    async fn call_timeout<R: Request>(
+        &self,
+        req: R,
+        timeout: Duration,
+    ) -> ClientResult<R::Response> {
+        let packet = Packet {
+            seq: 0,
+            uin: self.base.uin(),
+            packet_detail: req.packet_detail(),
+            encrypt: req.encrypt(),
+            command: Cow::Borrowed(R::COMMAND),
+            body: req.encode(&self.base),
+            message: String::new(),
+        };
+
+        let rsp = self.send_and_wait(packet, timeout).await?;
+        req.decode(rsp.body)
+    }
+
+    async fn register(&self) -> ClientResult<()> {
+        self.call_timeout(stat::Register, DEFAULT_TIMEOUT).await?;
+        self.base.online.store(true, Ordering::Release);
+        Ok(())
+    }
+
+    async fn refresh_token(&self) -> ClientResult<()> {
+        let seq = self.base.next_seq();
+        let oicq = OicqPacket {
+            uin: self.base.uin() as u32,
+            command: 0x810,
+            body: token::exchange_emp(&self.base, seq as u32),
+        };
+        let random_key = rand::random();
+        let encrypt = self.base.ecdh.oicq_encrypt(&random_key);
+
+        let pkt = wtlogin_packet("wtlogin.exchange_emp", seq, &oicq, &encrypt);
+        let rsp = self.send_with_seq(seq, pkt, DEFAULT_TIMEOUT).await?;
+        let rsp = OicqPacket::decode(rsp.body, &encrypt)?;
+        token::decode_exchange_emp(&self.base, rsp)
+    }
+}
+
+/// How long [`Client::call`] waits for a response.
+pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(15);
+
+/// How long [`Client::send_group_message`] waits for the server to echo the message.
+const ECHO_TIMEOUT: Duration = Duration::from_secs(3);
+
+struct Pending<'a> {
+    client: &'a RequestClient,
+    seq: u16,
+}
+
+impl Drop for Pending<'_> {
+    fn drop(&mut self) {
+        self.client.seq_packet_sender.remove(&self.seq);
+    }
+}
+
+/// Wraps an oicq request into the sso packet of a `wtlogin.*` command.
+fn wtlogin_packet(
+    command: &'static str,
+    seq: u16,
+    oicq: &OicqPacket,
+    encrypt: &OicqEncrypt,
+) -> Packet {
+    Packet {
+        seq: seq as u32,
+        uin: oicq.uin as u64,
+        packet_detail: PacketDetail::Login,
+        encrypt: Encrypt::EmptyKey,
+        command: Cow::Borrowed(command),
+        body: oicq.encode(encrypt),
+        message: String::new(),
+    }
+}
+
+fn disconnected() -> ClientError {
+    ClientError::IO(io::Error::new(
+        io::ErrorKind::NotConnected,
+        "client is disconnected",
+    ))
+}
+
 pub struct ClientBuilder<F, E, C> {
     handler: F,
     base: RequestClient,
@@ -71,6 +589,9 @@ pub struct ClientBuilder<F, E, C> {
     packet_send_rx: UnboundedReceiver<Packet>,
     packet_send_tx: UnboundedSender<Packet>,
     connector: C,
+    heartbeat_interval: Duration,
+    reconnect: Reconnect,
+    event_capacity: usize,
 }
 
 impl ClientBuilder<(), (), ()> {
@@ -84,6 +605,9 @@ impl ClientBuilder<(), (), ()> {
             packet_send_rx: rx,
             packet_send_tx: tx,
             connector: (),
+            heartbeat_interval: HEARTBEAT_INTERVAL,
+            reconnect: Reconnect::default(),
+            event_capacity: EVENT_CAPACITY,
         }
     }
 }
@@ -110,6 +634,9 @@ impl<F, E, C> ClientBuilder<F, E, C> {
             packet_send_rx,
             packet_send_tx,
             connector,
+            heartbeat_interval,
+            reconnect,
+            event_capacity,
             ..
         } = self;
 
@@ -120,6 +647,9 @@ impl<F, E, C> ClientBuilder<F, E, C> {
             packet_send_rx,
             packet_send_tx,
             connector,
+            heartbeat_interval,
+            reconnect,
+            event_capacity,
         }
     }
 
@@ -140,6 +670,9 @@ impl<F, E, C> ClientBuilder<F, E, C> {
             packet_send_rx,
             packet_send_tx,
             connector,
+            heartbeat_interval,
+            reconnect,
+            event_capacity,
             ..
         } = self;
 
@@ -150,17 +683,102 @@ impl<F, E, C> ClientBuilder<F, E, C> {
             packet_send_rx,
             packet_send_tx,
             connector,
+            heartbeat_interval,
+            reconnect,
+            event_capacity,
         }
     }
 
+    pub fn with_device(mut self, device: DeviceInfo) -> Self {
+        self.base.device = device;
+        self
+    }
+
+    pub fn with_protocol(mut self, protocol: ProtocolInfo) -> Self {
+        self.base.protocol = protocol;
+        self
+    }
+
+    /// Handles server pushes of `command`, where a trailing `*` matches any suffix.
+    ///
+    /// Replaces the built-in handler of the same command.
+    pub fn with_push_handler(mut self, command: &'static str, handler: PushHandler) -> Self {
+        self.base.push_handlers.register(command, handler);
+        self
+    }
+
+    /// How often the running session sends heartbeats, [`HEARTBEAT_INTERVAL`] by default.
+    ///
+    /// A heartbeat unanswered within the interval counts as missed.
+    pub fn with_heartbeat_interval(mut self, interval: Duration) -> Self {
+        self.heartbeat_interval = interval;
+        self
+    }
+
+    /// Reconnects a broken session to one of `servers`, through connectors opened by
+    /// `factory`, instead of ending it.
+    ///
+    /// Every attempt waits out the [`Backoff`], then connects to all servers at once and
+    /// keeps the fastest. A session that was [registered](Client::register) is registered
+    /// again with the tickets it already has.
+    ///
+    /// The server lists pushed by `ConfigPushSvc.PushReq` replace `servers`, unless it is
+    /// [fixed](ServerList::fixed).
+    pub fn with_reconnect<R, S>(mut self, factory: R, servers: S) -> Self
+    where
+        R: ConnectorFactory + Send + Sync + 'static,
+        R::Connector: Send + 'static,
+        R::Future: Send + 'static,
+        S: Into<ServerList>,
+    {
+        self.reconnect.set_factory(factory);
+        *self.base.servers.lock() = servers.into();
+        self
+    }
+
+    /// How many events may wait for a [subscriber](Client::subscribe) that falls behind,
+    /// [`EVENT_CAPACITY`] by default.
+    ///
+    /// # Panics
+    ///
+    /// If `capacity` is zero.
+    pub fn with_event_capacity(mut self, capacity: usize) -> Self {
+        assert!(capacity > 0, "event capacity must not be zero");
+        self.event_capacity = capacity;
+        self
+    }
+
+    /// Delays between reconnect attempts, see [`ClientBuilder::with_reconnect`].
+    pub fn with_backoff(mut self, backoff: Backoff) -> Self {
+        self.reconnect.backoff = backoff;
+        self
+    }
+
+    /// Overrides the server public key used for the wtlogin ecdh exchange.
+    pub fn with_server_key(mut self, key: ServerPublicKey) -> Self {
+        self.base.ecdh = Ecdh::with_server_key(key);
+        self
+    }
+
     #[inline]
-    pub fn with_connector<T: Connector>(self, connector: T) -> ClientBuilder<F, E, T> {
+    pub fn session(&self) -> Transport {
+        self.base.session()
+    }
+
+    #[inline]
+    pub fn with_connector<T: Connector>(
+        self,
+        connector: T,
+    ) -> ClientBuilder<F, E, FramedConnector<T>> {
         let Self {
             handler,
             base,
             executor,
             packet_send_rx,
             packet_send_tx,
+            heartbeat_interval,
+            reconnect,
+            event_capacity,
             ..
         } = self;
 
@@ -170,64 +788,640 @@ impl<F, E, C> ClientBuilder<F, E, C> {
             executor,
             packet_send_rx,
             packet_send_tx,
-            connector,
+            connector: FramedConnector::new(connector),
+            heartbeat_interval,
+            reconnect,
+            event_capacity,
         }
     }
 }
 
-impl<F, Fu, E, C> ClientBuilder<F, E, C>
+impl<F, Fu, E, C> ClientBuilder<F, E, FramedConnector<C>>
 where
     F: Fn(ClientEvent) -> Fu,
     F: Send + 'static,
     Fu: Future<Output = ()>,
     Fu: Send + 'static,
-    E: Executor,
+    E: Executor + Timer,
     C: Connector,
 {
-    pub async fn login(&mut self) -> Result<&mut Self, ClientError> {
-        // do login here
+    /// Logs in with a password, keeping the received tickets on success.
+    ///
+    /// Anything other than [`LoginResponse::Success`] means the server wants
+    /// more from the user before it hands out tickets.
+    pub async fn login(&mut self, uin: u64, password: &str) -> ClientResult<LoginResponse> {
+        let password_md5 = login::password_md5(password);
 
-        Ok(self)
-    }
+        self.base.uin = uin;
+        self.base.transport.lock().uin = uin;
+        self.base.login_state.lock().password_md5 = password_md5;
 
-    pub fn run(self) -> Client {
-        let (tx, mut rx) = oneshot::channel();
+        let seq = self.base.next_seq();
+        let body = login::password_login(&self.base, seq as u32, &password_md5);
+        let rsp = self.wtlogin("wtlogin.login", 0x810, seq, body).await?;
 
-        let client = Arc::new(self.base);
+        login::decode_login_response(&self.base, rsp)
+    }
+
+    /// Logs in with a password, letting `verifier` answer captcha and sms challenges.
+    ///
+    /// A device lock or sms challenge the server repeats right after being answered
+    /// is handed back rather than answered again.
+    pub async fn login_with<V: LoginVerifier>(
+        &mut self,
+        uin: u64,
+        password: &str,
+        verifier: &mut V,
+    ) -> ClientResult<LoginResponse> {
+        let mut rsp = self.login(uin, password).await?;
 
-        let install = client.clone();
-        self.executor.spawn(async move {
-            let mut pkt_rx = self.packet_send_rx;
-            while let Some(pkt) = pkt_rx.next().await {
-                let len = pkt.command.len() + 20;
-                let mut out_pkt = Vec::<u8>::with_capacity(len);
-                out_pkt.extend_from_slice(&len.to_be_bytes());
-                out_pkt.extend_from_slice(&0x0Bu32.to_be_bytes());
+        loop {
+            rsp = match rsp {
+                LoginResponse::NeedCaptcha { ref url } => match verifier.slider(url).await {
+                    Some(ticket) => self.submit_ticket(&ticket).await?,
+                    None => return Ok(rsp),
+                },
+                LoginResponse::NeedSms { ref phone } => {
+                    let sent = self.request_sms().await?;
+                    if !matches!(sent, LoginResponse::NeedSms { .. }) {
+                        return Ok(sent);
+                    }
 
-                match pkt.packet_detail {
-                    PacketDetail::Uin => {}
-                    PacketDetail::Login => {}
+                    match verifier.sms(phone).await {
+                        Some(code) => match self.submit_sms(&code).await? {
+                            rsp @ LoginResponse::NeedSms { .. } => return Ok(rsp),
+                            rsp => rsp,
+                        },
+                        None => return Ok(rsp),
+                    }
                 }
+                LoginResponse::DeviceLockLogin => match self.device_lock_login().await? {
+                    LoginResponse::DeviceLockLogin => return Ok(LoginResponse::DeviceLockLogin),
+                    rsp => rsp,
+                },
+                rsp => return Ok(rsp),
+            };
+        }
+    }
 
-                out_pkt.push(0);
+    /// Answers [`LoginResponse::NeedCaptcha`] with the ticket of the solved slider.
+    pub async fn submit_ticket(&mut self, ticket: &str) -> ClientResult<LoginResponse> {
+        let body = login::submit_ticket(&self.base, ticket);
+        self.verify(body).await
+    }
 
-                match rx.try_recv() {
-                    Ok(Some(())) | Err(_) => {
-                        break;
-                    }
-                    Ok(None) => {}
+    /// Asks the server to text the code for [`LoginResponse::NeedSms`].
+    ///
+    /// The server answers with [`LoginResponse::NeedSms`] again once the code is sent.
+    pub async fn request_sms(&mut self) -> ClientResult<LoginResponse> {
+        let body = login::request_sms(&self.base);
+        self.verify(body).await
+    }
+
+    pub async fn submit_sms(&mut self, code: &str) -> ClientResult<LoginResponse> {
+        let body = login::submit_sms(&self.base, code);
+        self.verify(body).await
+    }
+
+    /// Finishes a login after [`LoginResponse::DeviceLockLogin`].
+    pub async fn device_lock_login(&mut self) -> ClientResult<LoginResponse> {
+        let body = login::device_lock_login(&self.base);
+        self.verify(body).await
+    }
+
+    async fn verify(&mut self, body: Bytes) -> ClientResult<LoginResponse> {
+        let seq = self.base.next_seq();
+        let rsp = self.wtlogin("wtlogin.login", 0x810, seq, body).await?;
+
+        login::decode_login_response(&self.base, rsp)
+    }
+
+    /// Resumes a session saved with [`Transport::to_bytes`] instead of logging in again.
+    ///
+    /// Fails with [`ClientError::TokenExpired`] once A2 is gone, in which case
+    /// only a fresh login helps. D2 close to expiring is exchanged right away.
+    pub async fn token_login(&mut self, session: Transport) -> ClientResult<()> {
+        if !token::is_resumable(&session) {
+            return Err(ClientError::TokenExpired);
+        }
+
+        let refresh = token::needs_refresh(&session);
+        self.base.uin = session.uin;
+        *self.base.transport.lock() = session;
+
+        if refresh {
+            self.refresh_token().await?;
+        }
+
+        Ok(())
+    }
+
+    /// Exchanges A2 for new tickets with `wtlogin.exchange_emp`.
+    pub async fn refresh_token(&mut self) -> ClientResult<()> {
+        let seq = self.base.next_seq();
+        let body = token::exchange_emp(&self.base, seq as u32);
+        let rsp = self
+            .wtlogin("wtlogin.exchange_emp", 0x810, seq, body)
+            .await?;
+
+        token::decode_exchange_emp(&self.base, rsp)
+    }
+
+    /// Sends an ecdh encrypted oicq request and waits for the matching reply.
+    async fn wtlogin(
+        &mut self,
+        command: &'static str,
+        oicq_command: u16,
+        seq: u16,
+        body: Bytes,
+    ) -> ClientResult<OicqResponse> {
+        let oicq = OicqPacket {
+            uin: self.base.uin as u32,
+            command: oicq_command,
+            body,
+        };
+
+        let rsp = self.wtlogin_raw(command, seq, oicq).await?;
+        Ok(OicqResponse::try_from(rsp)?)
+    }
+
+    /// Like [`Self::wtlogin`], but hands back the decrypted oicq body as is.
+    ///
+    /// Fails with [`ClientError::Timeout`] if no reply arrives within [`DEFAULT_TIMEOUT`].
+    async fn wtlogin_raw(
+        &mut self,
+        command: &'static str,
+        seq: u16,
+        oicq: OicqPacket,
+    ) -> ClientResult<OicqPacket> {
+        let random_key = rand::random();
+        let encrypt = self.base.ecdh.oicq_encrypt(&random_key);
+
+        let pkt = wtlogin_packet(command, seq, &oicq, &encrypt);
+
+        let (frame, d2_key) = {
+            let transport = self.base.transport.lock();
+            let ctx = transport.sso_context(&self.base.protocol, &self.base.device.imei);
+            (pkt.build_sso_packet(&ctx), transport.st.d2_key)
+        };
+        self.connector.send(frame).await?;
+
+        let connector = &mut self.connector;
+        let rsp = async move {
+            loop {
+                let frame = connector
+                    .next()
+                    .await
+                    .ok_or_else(|| io::Error::from(io::ErrorKind::UnexpectedEof))??;
+
+                // anything else that comes in before the reply is skipped, whether it
+                // decodes or not
+                let Ok(pkt) = Packet::decode_sso_packet(frame, &d2_key) else {
+                    continue;
+                };
+                if pkt.seq == seq as u32 && pkt.command == command {
+                    return ClientResult::Ok(pkt);
                 }
             }
+        };
+
+        let pkt = match self.executor.timeout(DEFAULT_TIMEOUT, rsp).await {
+            Some(rsp) => rsp?,
+            None => return Err(ClientError::Timeout),
+        };
+        Ok(OicqPacket::decode_raw(pkt.body, &encrypt)?)
+    }
+
+    /// Starts the session: a writer task sending queued packets through the connector,
+    /// a reader task dispatching whatever comes back, and a heartbeat every
+    /// [`ClientBuilder::with_heartbeat_interval`], which also checks whether the
+    /// tickets are due to be [refreshed](Client::refresh_token).
+    ///
+    /// The session ends when [`Client::stop`] is called, every [`Client`] is dropped
+    /// or the connection breaks, failing every request still pending. A broken
+    /// connection, including one that misses [`MISSED_HEARTBEATS`] heartbeats in a row,
+    /// is reported as [`EventKind::Disconnected`], unless the session
+    /// [reconnects](ClientBuilder::with_reconnect).
+    pub fn run(self) -> Client
+    where
+        E: Timer + Send + Sync + 'static,
+        C: Send + 'static,
+    {
+        let Self {
+            handler,
+            base,
+            executor,
+            packet_send_rx,
+            packet_send_tx,
+            connector,
+            heartbeat_interval,
+            reconnect,
+            event_capacity,
+        } = self;
+
+        let executor = Arc::new(executor);
+        let requester = Requester {
+            base: Arc::new(base),
+            request_sender: packet_send_tx,
+            sleep: sleep_on(executor.clone()),
+        };
+        let (events, event_rx) = futures::channel::mpsc::unbounded();
+        let teardown = Arc::new(Teardown::new(requester.clone(), events.clone()));
+
+        let refresh = refresh_loop(
+            requester.clone(),
+            heartbeat_interval,
+            executor.clone(),
+            events.clone(),
+        );
+        let refresh = spawn_task(&*executor, &teardown, refresh);
+
+        let session = Session {
+            requester: requester.clone(),
+            executor: executor.clone(),
+            queue: Arc::new(futures::lock::Mutex::new(packet_send_rx)),
+            events,
+            heartbeat_interval,
+        };
+        let supervisor = supervise(session, Connection::new(connector), reconnect);
+        let supervisor = spawn_task(&*executor, &teardown, supervisor);
+        teardown.attach([supervisor.abort_handle(), refresh.abort_handle()]);
+        refresh.detach();
+
+        let (subscribed, subscribers) = broadcast::channel(event_capacity);
+        let handle = Arc::new(Handle {
+            requester,
+            teardown,
+            closed: supervisor.shared(),
+            subscribers,
         });
+        executor
+            .spawn(dispatch(
+                event_rx,
+                Arc::downgrade(&handle),
+                handler,
+                subscribed,
+                executor.clone(),
+            ))
+            .detach();
+
+        Client { handle }
+    }
+}
+
+/// Spawns a task of the session, tearing the session down once it ends.
+fn spawn_task<E, F>(executor: &E, teardown: &Arc<Teardown>, task: F) -> JoinHandle<()>
+where
+    E: Executor,
+    F: Future<Output = ()> + Send + 'static,
+{
+    let closing = Closing(teardown.clone());
+    executor.spawn(async move {
+        let _closing = closing;
+        task.await;
+    })
+}
+
+/// A connection to the server, whatever [`Connector`] it runs on.
+struct Connection {
+    sink: Pin<Box<dyn Sink<Bytes, Error = io::Error> + Send>>,
+    stream: BoxStream<'static, io::Result<Bytes>>,
+}
+
+impl Connection {
+    fn new<C: Connector + Send + 'static>(connector: FramedConnector<C>) -> Self {
+        let (sink, stream) = connector.split();
+        Self {
+            sink: Box::pin(sink),
+            stream: stream.boxed(),
+        }
+    }
+}
+
+/// What the tasks of every connection of a session share.
+struct Session<E> {
+    requester: Requester,
+    executor: Arc<E>,
+    /// Outgoing packets, taken over by the writer of each connection in turn.
+    queue: Arc<futures::lock::Mutex<UnboundedReceiver<Packet>>>,
+    events: UnboundedSender<EventKind>,
+    heartbeat_interval: Duration,
+}
+
+impl<E> Session<E>
+where
+    E: Executor + Timer + Send + Sync + 'static,
+{
+    /// Spawns the writer, reader, heartbeat and sync of `connection`. The connection is gone
+    /// once any of them ends, and dropping the handles takes the rest down.
+    ///
+    /// The writer sends whatever comes through `first` before anything queued.
+    fn start(
+        &self,
+        connection: Connection,
+        first: UnboundedReceiver<Packet>,
+    ) -> Vec<JoinHandle<()>> {
+        let base = &self.requester.base;
+        let writer = write_loop(base.clone(), first, self.queue.clone(), connection.sink);
+        let (notified, notifies) = futures::channel::mpsc::unbounded();
+        let reader = read_loop(
+            base.clone(),
+            connection.stream,
+            self.events.clone(),
+            notified,
+        );
+        let heartbeat = heartbeat_loop(
+            self.requester.clone(),
+            self.heartbeat_interval,
+            self.executor.clone(),
+        );
+        let sync = sync_loop(self.requester.clone(), notifies, self.events.clone());
+
+        vec![
+            self.executor.spawn(writer),
+            self.executor.spawn(reader),
+            self.executor.spawn(heartbeat),
+            self.executor.spawn(sync),
+        ]
+    }
+}
+
+/// Runs the connections of a session, replacing a broken one as `reconnect` says.
+///
+/// Returns once the connection breaks for good, ending the session.
+async fn supervise<E>(session: Session<E>, connection: Connection, reconnect: Reconnect)
+where
+    E: Executor + Timer + Send + Sync + 'static,
+{
+    let requester = &session.requester;
+    let mut tasks = session.start(connection, futures::channel::mpsc::unbounded().1);
+
+    loop {
+        let _ = future::select_all(tasks).await;
+        requester.base.cancel_pending();
+        if !reconnect.is_enabled() || requester.base.servers.lock().is_empty() {
+            return;
+        }
+
+        let _ = session.events.unbounded_send(EventKind::Reconnecting);
+        let mut attempt = 0;
+        let server = loop {
+            let servers = &requester.base.servers;
+            let (server, connection) = match reconnect
+                .connect(servers, &*session.executor, &mut attempt)
+                .await
+            {
+                Some(c) => c,
+                None => return,
+            };
+
+            let (first, rx) = futures::channel::mpsc::unbounded();
+            tasks = session.start(connection, rx);
+            if !requester.base.online.load(Ordering::Acquire) {
+                break server;
+            }
+
+            // registered before the requests queued meanwhile go out
+            let register = Requester {
+                request_sender: first,
+                ..requester.clone()
+            };
+            match register.register().await {
+                Ok(()) => break server,
+                Err(ClientError::IO(_) | ClientError::Timeout) => {
+                    servers.lock().record(server, None)
+                }
+                // the tickets are no good anymore, another server won't take them either
+                Err(_) => return,
+            }
+        };
+
+        let _ = session
+            .events
+            .unbounded_send(EventKind::Reconnected { server });
+    }
+}
+
+async fn write_loop<S>(
+    base: Arc<RequestClient>,
+    first: UnboundedReceiver<Packet>,
+    queue: Arc<futures::lock::Mutex<UnboundedReceiver<Packet>>>,
+    mut sink: S,
+) where
+    S: Sink<Bytes> + Unpin,
+{
+    // held until the connection is gone, the writer of the next one waits for it
+    let mut queue = queue.lock().await;
+    let mut packets = first.chain(&mut *queue);
+    while let Some(pkt) = packets.next().await {
+        let frame = {
+            let transport = base.transport.lock();
+            pkt.build_sso_packet(&transport.sso_context(&base.protocol, &base.device.imei))
+        };
 
-        Client {
-            base: client,
-            stop_sig: tx,
-            request_sender: self.packet_send_tx,
+        if sink.send(frame).await.is_err() {
+            break;
         }
     }
 }
 
+/// Decodes every frame coming in, signalling `notified` on each `MessageNotify` for
+/// the messages it announces to be fetched.
+async fn read_loop<S>(
+    base: Arc<RequestClient>,
+    mut stream: S,
+    events: UnboundedSender<EventKind>,
+    notified: UnboundedSender<()>,
+) where
+    S: Stream<Item = io::Result<Bytes>> + Unpin,
+{
+    while let Some(Ok(frame)) = stream.next().await {
+        if let Some(event) = base.decode_packet(&frame).await {
+            if let EventKind::MessageNotify { .. } = event {
+                let _ = notified.unbounded_send(());
+            }
+            let _ = events.unbounded_send(event);
+        }
+    }
+}
+
+/// Fetches the messages each `MessageSvc.PushNotify` announces, ending once the reader
+/// is gone.
+async fn sync_loop(
+    requester: Requester,
+    mut notifies: UnboundedReceiver<()>,
+    events: UnboundedSender<EventKind>,
+) {
+    while notifies.next().await.is_some() {
+        // one fetch covers every notify that came meanwhile
+        while notifies.try_recv().is_ok() {}
+        let _ = sync::sync(&requester, &events).await;
+    }
+}
+
+/// Heartbeats missed in a row before the connection is given up on.
+pub const MISSED_HEARTBEATS: u32 = 3;
+
+/// The default of [`ClientBuilder::with_heartbeat_interval`].
+pub const HEARTBEAT_INTERVAL: Duration = Duration::from_secs(30);
+
+/// The default of [`ClientBuilder::with_event_capacity`].
+pub const EVENT_CAPACITY: usize = 256;
+
+/// Sends `Heartbeat.Alive` and `StatSvc.SimpleGet` every `interval`, ending once the
+/// connection is gone.
+async fn heartbeat_loop<T: Timer>(requester: Requester, interval: Duration, timer: T) {
+    let mut missed = 0;
+    let mut ticks = timer.interval(interval);
+    while ticks.next().await.is_some() {
+        let (alive, _) = future::join(
+            requester.call_timeout(stat::Heartbeat, interval),
+            requester.call_timeout(stat::SimpleGet, interval),
+        )
+        .await;
+        match alive {
+            Err(ClientError::Timeout) => {
+                missed += 1;
+                if missed >= MISSED_HEARTBEATS {
+                    return;
+                }
+            }
+            Err(ClientError::IO(_)) => return,
+            _ => missed = 0,
+        }
+    }
+}
+
+/// Exchanges the tickets once D2 gets close to expiring, checking every `interval`.
+///
+/// A refused exchange, or A2 being gone by then, is reported as
+/// [`EventKind::TokenExpired`] and ends the session, no connection gets anywhere
+/// without the tickets. Other failures are retried on the next check.
+async fn refresh_loop<T: Timer>(
+    requester: Requester,
+    interval: Duration,
+    timer: T,
+    events: UnboundedSender<EventKind>,
+) {
+    let mut ticks = timer.interval(interval);
+    while ticks.next().await.is_some() {
+        let (resumable, due) = {
+            let transport = requester.base.transport.lock();
+            (
+                token::is_resumable(&transport),
+                token::needs_refresh(&transport),
+            )
+        };
+        if !due {
+            continue;
+        }
+
+        let expired = !resumable
+            || matches!(
+                requester.refresh_token().await,
+                Err(ClientError::TokenExpired)
+            );
+        if expired {
+            let _ = events.unbounded_send(EventKind::TokenExpired);
+            return;
+        }
+    }
+}
+
+/// Hands every event to its own handler task, along with a handle to the session,
+/// and to the subscribers.
+///
+/// Only events handled keep the session alive, those coming once every handle is gone
+/// are dropped.
+async fn dispatch<F, Fu, E>(
+    mut events: UnboundedReceiver<EventKind>,
+    handle: Weak<Handle>,
+    handler: F,
+    subscribed: broadcast::Sender<EventKind>,
+    executor: Arc<E>,
+) where
+    F: Fn(ClientEvent) -> Fu,
+    Fu: Future<Output = ()> + Send + 'static,
+    E: Executor,
+{
+    while let Some(kind) = events.next().await {
+        let Some(handle) = handle.upgrade() else {
+            return;
+        };
+        subscribed.send(kind.clone());
+        let client = Client { handle };
+        executor
+            .spawn(handler(ClientEvent { client, kind }))
+            .detach();
+    }
+}
+
+/// Shared by the tasks of a session, tearing it down once any of them ends.
+struct Teardown {
+    requester: Requester,
+    events: UnboundedSender<EventKind>,
+    tasks: Mutex<(Vec<AbortHandle>, bool)>,
+    stopped: AtomicBool,
+}
+
+impl Teardown {
+    fn new(requester: Requester, events: UnboundedSender<EventKind>) -> Self {
+        Self {
+            requester,
+            events,
+            tasks: Mutex::new((vec![], false)),
+            stopped: AtomicBool::new(false),
+        }
+    }
+
+    /// Adds tasks to abort, right away if the session has already ended.
+    fn attach<I: IntoIterator<Item = AbortHandle>>(&self, handles: I) {
+        let mut tasks = self.tasks.lock();
+        tasks.0.extend(handles);
+        if tasks.1 {
+            tasks.0.drain(..).for_each(|task| task.abort());
+        }
+    }
+
+    /// Ends the session on request, without [`EventKind::Disconnected`].
+    fn stop(&self) {
+        self.stopped.store(true, Ordering::Release);
+        self.run();
+    }
+
+    fn run(&self) {
+        let tasks = {
+            let mut tasks = self.tasks.lock();
+            if std::mem::replace(&mut tasks.1, true) {
+                return;
+            }
+            std::mem::take(&mut tasks.0)
+        };
+
+        for task in tasks {
+            task.abort();
+        }
+        // refuse new requests before failing the pending ones, so none is left waiting
+        self.requester.request_sender.close_channel();
+        self.requester.base.cancel_pending();
+
+        if !self.stopped.load(Ordering::Acquire) {
+            let _ = self.events.unbounded_send(EventKind::Disconnected);
+        }
+        // the last event, subscribers end once they had it
+        self.events.close_channel();
+    }
+}
+
+/// Runs the [`Teardown`] when a task ends, however it ends.
+struct Closing(Arc<Teardown>);
+
+impl Drop for Closing {
+    fn drop(&mut self) {
+        self.0.run();
+    }
+}
+
 pub struct NopFuture;
 
 impl Future for NopFuture {
@@ -238,3 +1432,247 @@ impl Future for NopFuture {
         Poll::Ready(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::client::{sleep_on, Client, Handle, Request, RequestClient, Requester, Teardown};
+    use crate::crypto::tea::Tea;
+    use crate::data::packet::{Encrypt, Packet, PacketDetail, PacketError};
+    use crate::error::ClientError;
+    use crate::event::EventKind;
+    use crate::executor::timer::ThreadTimer;
+    use crate::executor::JoinHandle;
+    use crate::sync::broadcast;
+    use bytes::{BufMut, Bytes, BytesMut};
+    use futures::channel::mpsc;
+    use futures::StreamExt;
+    use std::borrow::Cow;
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    fn client() -> (Client, mpsc::UnboundedReceiver<Packet>) {
+        let (request_sender, rx) = mpsc::unbounded();
+        let requester = Requester {
+            base: Arc::new(RequestClient::new()),
+            request_sender,
+            sleep: sleep_on(ThreadTimer),
+        };
+        // a session without tasks, closed from the start
+        let (_, closed) = JoinHandle::pair(async {});
+        let handle = Handle {
+            teardown: Arc::new(Teardown::new(requester.clone(), mpsc::unbounded().0)),
+            requester,
+            closed: futures::FutureExt::shared(closed),
+            subscribers: broadcast::channel(1).1,
+        };
+        let client = Client {
+            handle: Arc::new(handle),
+        };
+        (client, rx)
+    }
+
+    fn packet(command: &'static str) -> Packet {
+        Packet {
+            seq: 0,
+            uin: 10001,
+            packet_detail: PacketDetail::Uin,
+            encrypt: Encrypt::UseD2Key,
+            command: Cow::Borrowed(command),
+            body: Bytes::new(),
+            message: String::new(),
+        }
+    }
+
+    #[test]
+    fn send_and_wait() {
+        let (client, mut rx) = client();
+        let base = client.requester().base.clone();
+
+        futures::executor::block_on(async {
+            let responder = async {
+                let req = rx.next().await.unwrap();
+                assert_eq!(req.command, "Test.Echo");
+                // a push numbered like the request is not its response
+                let mut push = packet("OnlinePush.SidTicketExpired");
+                push.seq = req.seq;
+                assert!(base.complete(req.seq, Ok(push)).is_some());
+
+                let mut rsp = packet("Test.Echo");
+                rsp.seq = req.seq;
+                rsp.body = Bytes::from_static(b"pong");
+                assert!(base.complete(req.seq, Ok(rsp)).is_none());
+
+                // nobody waits for this one
+                assert!(base.complete(req.seq, Ok(packet("Test.Echo"))).is_some());
+            };
+
+            let (rsp, ()) = futures::join!(
+                client.send_and_wait(packet("Test.Echo"), Duration::from_secs(5)),
+                responder
+            );
+            assert_eq!(&rsp.unwrap().body[..], b"pong");
+        });
+        assert!(base.seq_packet_sender.is_empty());
+    }
+
+    struct Echo(&'static str);
+
+    impl Request for Echo {
+        type Response = String;
+
+        const COMMAND: &'static str = "Test.Echo";
+
+        fn encode(&self, client: &RequestClient) -> Bytes {
+            format!("{}:{}", client.uin(), self.0).into()
+        }
+
+        fn decode(&self, body: Bytes) -> Result<String, ClientError> {
+            String::from_utf8(body.to_vec())
+                .map_err(|_| ClientError::Packet(PacketError::Malformed))
+        }
+    }
+
+    #[test]
+    fn call() {
+        let (client, mut rx) = client();
+        let base = client.requester().base.clone();
+
+        futures::executor::block_on(async {
+            let responder = async {
+                let req = rx.next().await.unwrap();
+                assert_eq!(req.command, "Test.Echo");
+                assert!(matches!(req.encrypt, Encrypt::UseD2Key));
+                assert!(matches!(req.packet_detail, PacketDetail::Uin));
+
+                let mut rsp = packet("Test.Echo");
+                rsp.body = req.body;
+                base.complete(req.seq, Ok(rsp));
+            };
+
+            let (rsp, ()) = futures::join!(client.call(Echo("ping")), responder);
+            assert_eq!(rsp.unwrap(), "0:ping");
+        });
+    }
+
+    /// A 0x0B frame as the server sends it, with its length removed.
+    fn frame(flag: u8, key: &[u8; 16], seq: u32, ret: i32, command: &str, body: &[u8]) -> Vec<u8> {
+        fn lv(buf: &mut BytesMut, b: &[u8]) {
+            buf.put_u32(b.len() as u32 + 4);
+            buf.put_slice(b);
+        }
+
+        let mut head = BytesMut::new();
+        head.put_u32(seq);
+        head.put_i32(ret);
+        lv(&mut head, b"");
+        lv(&mut head, command.as_bytes());
+        lv(&mut head, &[0; 4]);
+        head.put_u32(0);
+
+        let mut inner = BytesMut::new();
+        lv(&mut inner, &head);
+        lv(&mut inner, body);
+
+        let mut frame = BytesMut::new();
+        frame.put_u32(0x0B);
+        frame.put_u8(flag);
+        frame.put_u8(0);
+        lv(&mut frame, b"10001");
+        match flag {
+            0 => frame.put_slice(&inner),
+            _ => frame.put_slice(&Tea::from_bytes(key).encrypt(&inner)),
+        }
+        frame.to_vec()
+    }
+
+    #[test]
+    fn dispatch() {
+        let (client, _rx) = client();
+        let base = &client.requester().base;
+        let d2_key = [0xd2; 16];
+        base.transport.lock().st.d2_key = d2_key;
+
+        futures::executor::block_on(async {
+            let rx = base.register(1, "Test.Echo".into());
+            let ev = base
+                .decode_packet(&frame(1, &d2_key, 1, 0, "Test.Echo", b"pong"))
+                .await;
+            assert!(ev.is_none());
+            assert_eq!(&rx.await.unwrap().unwrap().body[..], b"pong");
+
+            let ev = base
+                .decode_packet(&frame(
+                    2,
+                    &[0; 16],
+                    7,
+                    0,
+                    "OnlinePush.PbPushGroupMsg",
+                    b"msg",
+                ))
+                .await;
+            assert!(matches!(
+                ev,
+                Some(EventKind::OnlinePush { command, body })
+                    if command == "OnlinePush.PbPushGroupMsg" && &body[..] == b"msg"
+            ));
+
+            let ev = base
+                .decode_packet(&frame(0, &[0; 16], 8, 0, "ConfigPushSvc.PushReq", b"cfg"))
+                .await;
+            assert!(matches!(ev, Some(EventKind::ConfigPush { .. })));
+
+            // failures still reach their waiter
+            let rx = base.register(2, "Test.Echo".into());
+            base.decode_packet(&frame(1, &d2_key, 2, -10008, "Test.Echo", b""))
+                .await;
+            assert!(matches!(rx.await, Ok(Err(PacketError::SessionExpired))));
+
+            // garbage and strays are dropped
+            assert!(base.decode_packet(b"\0\0\0\x0b").await.is_none());
+            assert!(base
+                .decode_packet(&frame(1, &d2_key, 3, 0, "Test.Echo", b""))
+                .await
+                .is_none());
+        });
+        assert!(base.seq_packet_sender.is_empty());
+    }
+
+    #[test]
+    fn timeout_and_cancel() {
+        let (client, _rx) = client();
+
+        let rsp = futures::executor::block_on(
+            client.send_and_wait(packet("Test.Echo"), Duration::from_millis(10)),
+        );
+        assert!(matches!(rsp, Err(ClientError::Timeout)));
+        assert!(client.requester().base.seq_packet_sender.is_empty());
+
+        // dropping the future mid-flight must not leak its entry
+        let mut fu = Box::pin(client.send_and_wait(packet("Test.Echo"), Duration::from_secs(5)));
+        assert!(futures::FutureExt::now_or_never(&mut fu).is_none());
+        assert_eq!(client.requester().base.seq_packet_sender.len(), 1);
+        drop(fu);
+        assert!(client.requester().base.seq_packet_sender.is_empty());
+    }
+
+    #[test]
+    fn disconnect() {
+        let (client, rx) = client();
+
+        let wait = client.send_and_wait(packet("Test.Echo"), Duration::from_secs(5));
+        // join polls in order, so the request is registered by the time this runs
+        let cancel = async {
+            assert_eq!(client.requester().base.seq_packet_sender.len(), 1);
+            client.requester().base.cancel_pending();
+        };
+        let (rsp, ()) = futures::executor::block_on(async { futures::join!(wait, cancel) });
+        assert!(matches!(rsp, Err(ClientError::IO(_))));
+
+        drop(rx);
+        let rsp = futures::executor::block_on(
+            client.send_and_wait(packet("Test.Echo"), Duration::from_secs(5)),
+        );
+        assert!(matches!(rsp, Err(ClientError::IO(_))));
+        assert!(client.requester().base.seq_packet_sender.is_empty());
+    }
+}
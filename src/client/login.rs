@@ -0,0 +1,454 @@
+use crate::client::RequestClient;
+use crate::crypto::tea::Tea;
+use crate::crypto::Transport;
+use crate::data::oicq::{OicqError, OicqResponse};
+use crate::data::tlv::*;
+use crate::error::ClientError;
+use bytes::{Buf, BufMut, Bytes, BytesMut};
+use std::future::Future;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Outcome of a wtlogin request that reached the server.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LoginResponse {
+    Success,
+    /// Slider captcha, the ticket is obtained by solving `url`.
+    NeedCaptcha {
+        url: String,
+    },
+    /// Device lock that has to be lifted in a browser.
+    DeviceLocked {
+        url: String,
+    },
+    /// Device lock verified by an SMS code sent to the masked `phone`.
+    NeedSms {
+        phone: String,
+    },
+    /// Device lock already lifted, finish with a device lock login.
+    DeviceLockLogin,
+    WrongPassword {
+        message: String,
+    },
+    AccountFrozen {
+        message: String,
+    },
+    TooManyAttempts {
+        message: String,
+    },
+    Unknown {
+        status: u8,
+        message: String,
+    },
+}
+
+/// Answers the challenges of an interactive login.
+///
+/// Returning `None` gives up, and the pending [`LoginResponse`] is handed back
+/// to the caller of [`ClientBuilder::login_with`](crate::client::ClientBuilder::login_with).
+pub trait LoginVerifier {
+    /// Solves the slider captcha at `url`, returning its ticket.
+    fn slider(&mut self, url: &str) -> impl Future<Output = Option<String>>;
+
+    /// Returns the code texted to the masked `phone`.
+    fn sms(&mut self, phone: &str) -> impl Future<Output = Option<String>>;
+}
+
+/// Tlvs the server hands back during an interactive login, echoed in follow-up requests.
+#[derive(Default)]
+pub(crate) struct LoginState {
+    pub password_md5: [u8; 16],
+    pub t104: Bytes,
+    pub t174: Bytes,
+    pub t402: Bytes,
+    pub t403: Bytes,
+    /// `md5(guid | dpwd | t402)`, sent back as `t401`.
+    pub g: [u8; 16],
+}
+
+pub(crate) fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+fn md5(data: &[u8]) -> [u8; 16] {
+    use digest::Digest;
+
+    let mut arr = [0; 16];
+    arr.copy_from_slice(&md5::Md5::digest(data));
+    arr
+}
+
+pub(crate) fn password_md5(password: &str) -> [u8; 16] {
+    md5(password.as_bytes())
+}
+
+/// Builds tlvs into `u16 sub_command | u16 count | tlv*`.
+pub(crate) fn wtlogin_body<I: IntoIterator<Item = Bytes>>(sub_command: u16, tlvs: I) -> Bytes {
+    let mut buf = BytesMut::new();
+    buf.put_u16(sub_command);
+    buf.put_u16(0);
+
+    let mut count = 0u16;
+    for tlv in tlvs {
+        buf.put_slice(&tlv);
+        count += 1;
+    }
+    buf[2..4].copy_from_slice(&count.to_be_bytes());
+
+    buf.freeze()
+}
+
+/// `wtlogin.login` sub command 9.
+pub(crate) fn password_login(client: &RequestClient, seq: u32, password_md5: &[u8; 16]) -> Bytes {
+    let protocol = &client.protocol;
+    let device = &client.device;
+    let guid = device.guid();
+    let transport = client.transport.lock();
+    let uin = transport.uin as u32;
+
+    wtlogin_body(
+        9,
+        [
+            tlv18(protocol, uin),
+            tlv1(device, uin, rand::random(), now() as u32),
+            tlv106(
+                protocol,
+                uin,
+                0,
+                password_md5,
+                &guid,
+                &transport.tgtgt_key,
+                rand::random(),
+                now() as u32,
+            ),
+            tlv116(protocol),
+            tlv100(protocol),
+            tlv107(0),
+            tlv142(protocol),
+            tlv144(device, &guid, &transport.tgtgt_key),
+            tlv145(&guid),
+            tlv147(protocol),
+            tlv154(seq),
+            tlv141(device),
+            tlv8(2052),
+            tlv511(DOMAINS),
+            tlv187(device),
+            tlv188(device),
+            tlv194(device),
+            tlv191(0x82),
+            tlv202(device),
+            tlv177(protocol),
+            tlv516(),
+            tlv521(0),
+            tlv525(&tlv536(&[1, 0])),
+        ],
+    )
+}
+
+/// Follow-up requests all start with `t8 | t104 | t116`.
+fn verify_body<I: IntoIterator<Item = Bytes>>(
+    client: &RequestClient,
+    sub_command: u16,
+    tlvs: I,
+) -> Bytes {
+    let t104 = client.login_state.lock().t104.clone();
+
+    wtlogin_body(
+        sub_command,
+        [tlv8(2052), tlv104(&t104), tlv116(&client.protocol)]
+            .into_iter()
+            .chain(tlvs),
+    )
+}
+
+/// `wtlogin.login` sub command 2.
+pub(crate) fn submit_ticket(client: &RequestClient, ticket: &str) -> Bytes {
+    verify_body(client, 2, [tlv193(ticket)])
+}
+
+/// `wtlogin.login` sub command 8.
+pub(crate) fn request_sms(client: &RequestClient) -> Bytes {
+    let t174 = client.login_state.lock().t174.clone();
+    verify_body(client, 8, [tlv174(&t174), tlv17a(9), tlv197()])
+}
+
+/// `wtlogin.login` sub command 7.
+pub(crate) fn submit_sms(client: &RequestClient, code: &str) -> Bytes {
+    let (t174, g) = {
+        let state = client.login_state.lock();
+        (state.t174.clone(), state.g)
+    };
+    verify_body(
+        client,
+        7,
+        [tlv174(&t174), tlv17c(code), tlv401(&g), tlv198()],
+    )
+}
+
+/// `wtlogin.login` sub command 20.
+pub(crate) fn device_lock_login(client: &RequestClient) -> Bytes {
+    let g = client.login_state.lock().g;
+    verify_body(client, 20, [tlv401(&g)])
+}
+
+fn read_string_short(buf: &mut Bytes) -> String {
+    if buf.len() < 2 {
+        return String::new();
+    }
+
+    let len = (buf.get_u16() as usize).min(buf.len());
+    String::from_utf8_lossy(&buf.split_to(len)).into_owned()
+}
+
+/// Message of `t146` (or `t149`) as `title: content`.
+fn error_message(tlvs: &TlvMap) -> String {
+    let (mut buf, skip) = match (tlvs.get(0x146), tlvs.get(0x149)) {
+        (Some(t), _) => (t.clone(), 4),
+        (None, Some(t)) => (t.clone(), 2),
+        (None, None) => return String::new(),
+    };
+
+    if buf.len() < skip {
+        return String::new();
+    }
+    buf.advance(skip);
+
+    let title = read_string_short(&mut buf);
+    let content = read_string_short(&mut buf);
+    if title.is_empty() {
+        content
+    } else {
+        format!("{}: {}", title, content)
+    }
+}
+
+pub(crate) fn decode_login_response(
+    client: &RequestClient,
+    rsp: OicqResponse,
+) -> Result<LoginResponse, ClientError> {
+    let OicqResponse { status, tlvs, .. } = rsp;
+
+    {
+        let state = &mut *client.login_state.lock();
+        for (tag, slot) in [
+            (0x104, &mut state.t104),
+            (0x174, &mut state.t174),
+            (0x402, &mut state.t402),
+            (0x403, &mut state.t403),
+        ] {
+            if let Some(v) = tlvs.get(tag) {
+                *slot = v.clone();
+            }
+        }
+
+        if tlvs.contains(0x402) {
+            let dpwd: [u8; 16] = rand::random();
+            let mut buf = BytesMut::new();
+            buf.put_slice(&client.device.guid());
+            buf.put_slice(&dpwd);
+            buf.put_slice(&state.t402);
+            state.g = md5(&buf);
+        }
+    }
+
+    let message = error_message(&tlvs);
+    let url = |tag| {
+        tlvs.get(tag)
+            .map(|v| String::from_utf8_lossy(v).into_owned())
+            .unwrap_or_default()
+    };
+
+    let rsp = match status {
+        0 => {
+            let key = client.transport.lock().tgtgt_key;
+            save_t119(client, &tlvs, &key)?;
+            LoginResponse::Success
+        }
+        1 => LoginResponse::WrongPassword { message },
+        2 if tlvs.contains(0x192) => LoginResponse::NeedCaptcha { url: url(0x192) },
+        40 => LoginResponse::AccountFrozen { message },
+        160 | 239 if tlvs.contains(0x174) => {
+            let phone = tlvs
+                .get(0x178)
+                .map(|t| {
+                    let mut t = t.clone();
+                    let _country = read_string_short(&mut t);
+                    read_string_short(&mut t)
+                })
+                .unwrap_or_default();
+            LoginResponse::NeedSms { phone }
+        }
+        160 | 239 if tlvs.contains(0x204) => LoginResponse::DeviceLocked { url: url(0x204) },
+        162 | 237 => LoginResponse::TooManyAttempts { message },
+        204 => LoginResponse::DeviceLockLogin,
+        status => LoginResponse::Unknown { status, message },
+    };
+
+    Ok(rsp)
+}
+
+/// Decrypts `t119` with `key` and stores the tickets it carries.
+pub(crate) fn save_t119(
+    client: &RequestClient,
+    tlvs: &TlvMap,
+    key: &[u8; 16],
+) -> Result<(), ClientError> {
+    let t119 = tlvs.require(0x119).map_err(oicq_tlv)?;
+    let t119 = Tea::from_bytes(key)
+        .decrypt(t119)
+        .map_err(|e| ClientError::Oicq(OicqError::Decrypt(e)))?;
+    let m = TlvMap::decode_counted(t119).map_err(oicq_tlv)?;
+
+    let mut transport = client.transport.lock();
+    apply_tickets(&mut transport, &m, now());
+
+    if let Some(a1) = m.get(0x106) {
+        let password_md5 = client.login_state.lock().password_md5;
+        let mut key = [0; 24];
+        key[..16].copy_from_slice(&password_md5);
+        key[20..].copy_from_slice(&(transport.uin as u32).to_be_bytes());
+
+        // the tgtgt key sealed in A1 has to be reused when logging in with A1 later
+        if let Ok(a1) = Tea::from_bytes(&md5(&key)).decrypt(a1) {
+            if a1.len() >= 51 + 16 {
+                transport.tgtgt_key.copy_from_slice(&a1[51..67]);
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn oicq_tlv(e: TlvError) -> ClientError {
+    ClientError::Oicq(OicqError::Tlv(e))
+}
+
+fn apply_tickets(transport: &mut Transport, m: &TlvMap, now: u64) {
+    fn key(v: &Bytes) -> [u8; 16] {
+        let mut k = [0; 16];
+        let n = v.len().min(16);
+        k[..n].copy_from_slice(&v[..n]);
+        k
+    }
+
+    let st = &mut transport.st;
+    for (tag, slot) in [
+        (0x106, &mut st.a1),
+        (0x10a, &mut st.a2),
+        (0x143, &mut st.d2),
+        (0x133, &mut st.wt_session_ticket),
+        (0x120, &mut st.s_key),
+        (0x10e, &mut st.user_st_key),
+        (0x103, &mut st.user_st_web_sig),
+        (0x322, &mut st.device_token),
+        (0x16a, &mut st.srm_token),
+    ] {
+        if let Some(v) = m.get(tag) {
+            *slot = v.clone();
+        }
+    }
+
+    for (tag, slot) in [
+        (0x10d, &mut st.a2_key),
+        (0x305, &mut st.d2_key),
+        (0x134, &mut st.wt_session_ticket_key),
+    ] {
+        if let Some(v) = m.get(tag) {
+            *slot = key(v);
+        }
+    }
+
+    if let Some(ksid) = m.get(0x108) {
+        transport.ksid = ksid.clone();
+    }
+
+    let st = &mut transport.st;
+    st.login_time = now;
+    st.a2_expire_time = now + 30 * 24 * 3600;
+    st.d2_expire_time = now + 24 * 3600;
+    st.s_key_expire_time = now + 6 * 3600;
+
+    // t138: u32 count, then (u16 tag, u32 seconds, u32) per ticket
+    if let Some(t138) = m.get(0x138) {
+        let mut t138 = t138.clone();
+        if t138.len() >= 4 {
+            let count = t138.get_u32();
+            for _ in 0..count {
+                if t138.len() < 10 {
+                    break;
+                }
+
+                let tag = t138.get_u16();
+                let secs = t138.get_u32() as u64;
+                t138.advance(4);
+                match tag {
+                    0x10a => st.a2_expire_time = now + secs,
+                    0x143 => st.d2_expire_time = now + secs,
+                    0x120 => st.s_key_expire_time = now + secs,
+                    _ => {}
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::client::login::{apply_tickets, error_message};
+    use crate::crypto::Transport;
+    use crate::data::tlv::{TlvMap, TlvWriter};
+    use bytes::{BufMut, BytesMut};
+
+    fn tlvs(entries: &[(u16, &[u8])]) -> TlvMap {
+        let mut buf = BytesMut::new();
+        for (tag, value) in entries {
+            let mut w = TlvWriter::new(*tag);
+            w.put_slice(value);
+            buf.put_slice(&w.into_bytes());
+        }
+        TlvMap::decode(buf.freeze()).unwrap()
+    }
+
+    #[test]
+    fn tickets() {
+        let mut t138 = BytesMut::new();
+        t138.put_u32(2);
+        t138.put_u16(0x143);
+        t138.put_u32(3600);
+        t138.put_u32(0);
+        t138.put_u16(0x10a);
+        t138.put_u32(7200);
+        t138.put_u32(0);
+
+        let m = tlvs(&[
+            (0x10a, b"a2"),
+            (0x10d, &[1; 16]),
+            (0x143, b"d2"),
+            (0x305, &[2; 16]),
+            (0x108, b"ksid"),
+            (0x138, &t138),
+        ]);
+
+        let mut transport = Transport::new(10001);
+        apply_tickets(&mut transport, &m, 1000);
+        assert_eq!(&transport.st.a2[..], b"a2");
+        assert_eq!(transport.st.a2_key, [1; 16]);
+        assert_eq!(&transport.st.d2[..], b"d2");
+        assert_eq!(transport.st.d2_key, [2; 16]);
+        assert_eq!(&transport.ksid[..], b"ksid");
+        assert_eq!(transport.st.d2_expire_time, 4600);
+        assert_eq!(transport.st.a2_expire_time, 8200);
+        assert_eq!(transport.st.s_key_expire_time, 1000 + 6 * 3600);
+    }
+
+    #[test]
+    fn message() {
+        let m = tlvs(&[(0x146, b"\0\0\0\x01\0\x05title\0\x07content")]);
+        assert_eq!(error_message(&m), "title: content");
+
+        let m = tlvs(&[(0x149, b"\0\0\0\0\0\x07content")]);
+        assert_eq!(error_message(&m), "content");
+    }
+}
@@ -0,0 +1,149 @@
+use crate::client::login::now;
+use crate::client::sync::sync_cookie;
+use crate::client::{Request, RequestClient};
+use crate::error::ClientError;
+use crate::proto::message::{
+    C2c, ContentHead, Grp, MessageBody, PbSendMsgReq, PbSendMsgResp, RichText, RoutingHead,
+};
+use bytes::Bytes;
+use prost::Message;
+
+/// Who a message goes to.
+#[derive(Clone, Copy, Debug)]
+pub(crate) enum Target {
+    Group(u64),
+    Friend(u64),
+}
+
+/// `MessageSvc.PbSendMsg`, answered with the time the message was sent at.
+pub(crate) struct SendMessage {
+    pub target: Target,
+    pub seq: i32,
+    pub rand: i32,
+    /// The chain to send, encoded beforehand as that can fail.
+    pub rich_text: RichText,
+}
+
+impl Request for SendMessage {
+    type Response = i32;
+
+    const COMMAND: &'static str = "MessageSvc.PbSendMsg";
+
+    fn encode(&self, client: &RequestClient) -> Bytes {
+        let (routing_head, sync_cookie) = match self.target {
+            Target::Group(group_code) => (
+                RoutingHead {
+                    c2c: None,
+                    grp: Some(Grp { group_code }),
+                },
+                Bytes::new(),
+            ),
+            Target::Friend(to_uin) => (
+                RoutingHead {
+                    c2c: Some(C2c { to_uin }),
+                    grp: None,
+                },
+                sync_cookie(client),
+            ),
+        };
+
+        let req = PbSendMsgReq {
+            routing_head: Some(routing_head),
+            content_head: Some(ContentHead {
+                pkg_num: 1,
+                ..Default::default()
+            }),
+            body: Some(MessageBody {
+                rich_text: Some(self.rich_text.clone()),
+            }),
+            msg_seq: self.seq,
+            msg_rand: self.rand,
+            sync_cookie,
+            msg_via: 1,
+        };
+        req.encode_to_vec().into()
+    }
+
+    fn decode(&self, body: Bytes) -> Result<i32, ClientError> {
+        let rsp = PbSendMsgResp::decode(body)?;
+
+        if rsp.result != 0 {
+            return Err(ClientError::Refused {
+                code: rsp.result as i64,
+                message: rsp.errmsg,
+            });
+        }
+        Ok(match rsp.send_time {
+            0 => now() as i32,
+            time => time as i32,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::client::message::{SendMessage, Target};
+    use crate::client::{Request, RequestClient};
+    use crate::error::ClientError;
+    use crate::message::MessageChain;
+    use crate::proto::message::{PbSendMsgReq, PbSendMsgResp, SyncCookie};
+    use bytes::Bytes;
+    use prost::Message;
+
+    fn response(result: i32, errmsg: &str, send_time: u32) -> Bytes {
+        let rsp = PbSendMsgResp {
+            result,
+            errmsg: errmsg.into(),
+            send_time,
+        };
+        rsp.encode_to_vec().into()
+    }
+
+    #[test]
+    fn send_message() {
+        let client = RequestClient::new();
+        let mut req = SendMessage {
+            target: Target::Group(20001),
+            seq: 7,
+            rand: 42,
+            rich_text: MessageChain::new().with("hi").to_rich_text().unwrap(),
+        };
+
+        let sent = PbSendMsgReq::decode(req.encode(&client)).unwrap();
+        let routing = sent.routing_head.unwrap();
+        assert_eq!(routing.grp.unwrap().group_code, 20001);
+        assert!(routing.c2c.is_none());
+        assert!(sent.sync_cookie.is_empty());
+        assert_eq!((sent.msg_seq, sent.msg_rand), (7, 42));
+        assert_eq!(sent.content_head.unwrap().pkg_num, 1);
+        let rich_text = sent.body.unwrap().rich_text.unwrap();
+        assert_eq!(
+            MessageChain::from_rich_text(rich_text),
+            MessageChain::new().with("hi")
+        );
+
+        req.target = Target::Friend(10002);
+        let sent = PbSendMsgReq::decode(req.encode(&client)).unwrap();
+        assert_eq!(sent.routing_head.unwrap().c2c.unwrap().to_uin, 10002);
+        let cookie = SyncCookie::decode(sent.sync_cookie).unwrap();
+        assert_eq!(cookie.const3, 0x1d);
+        assert_ne!(cookie.time, 0);
+
+        let sent = PbSendMsgReq::decode(req.encode(&client)).unwrap();
+        let again = SyncCookie::decode(sent.sync_cookie).unwrap();
+        assert_eq!((again.const1, again.const2), (cookie.const1, cookie.const2));
+
+        assert_eq!(
+            req.decode(response(0, "", 1_600_000_000)).unwrap(),
+            1_600_000_000
+        );
+        assert!(matches!(
+            req.decode(response(55, "muted", 0)),
+            Err(ClientError::Refused { code: 55, ref message }) if message == "muted"
+        ));
+        assert!(matches!(
+            req.decode(Bytes::from_static(b"\xff")),
+            Err(ClientError::Proto(_))
+        ));
+    }
+}
@@ -0,0 +1,1263 @@
+use crate::client::RequestClient;
+use crate::data::packet::Packet;
+use crate::event::{
+    EventKind, FriendAdd, FriendDelete, FriendMessage, FriendRequest, GroupJoinRequest,
+    GroupMessage, GroupNameChange, Kicked, MemberJoin, MemberLeave, MemberMute, MessageId,
+    MessageRecall, Offline, Poke, TempMessage,
+};
+use crate::jce::{JceError, JceStruct, UniPacket};
+use crate::message::MessageChain;
+use crate::proto::message::{MessageHead, MessageHeadType, Msg, PushMessagePacket, RichText};
+use crate::proto::notify::{
+    ForwardBody, GeneralGrayTipInfo, NotifyMsgBody, RecalledMessageMeta, Sub27, Sub8a, SubB3,
+    TransMsgInfo,
+};
+use crate::proto::structmsg::StructMsg;
+use bytes::{Buf, Bytes};
+use prost::Message;
+use std::collections::HashMap;
+use std::net::{IpAddr, SocketAddr};
+use std::time::Duration;
+
+crate::jce_struct! {
+    #[derive(Debug, Default)]
+    struct PushReq {
+        ty: i32 = 1,
+        jce_buf: Bytes = 2,
+        seq: i64 = 3,
+    }
+}
+
+crate::jce_struct! {
+    /// The `jce_buf` of a [`PushReq`] of type 1.
+    #[derive(Debug, Default)]
+    struct SsoServerList {
+        servers: Vec<SsoServerInfo> = 1,
+    }
+}
+
+crate::jce_struct! {
+    #[derive(Debug, Default)]
+    struct SsoServerInfo {
+        server: String = 1,
+        port: i32 = 2,
+        location: String = 8,
+    }
+}
+
+crate::jce_struct! {
+    /// `OnlinePush.ReqPush`, under `req`.
+    #[derive(Debug, Default)]
+    struct SvcReqPushMsg {
+        uin: i64 = 0,
+        msg_time: i64 = 1,
+        msg_infos: Vec<PushMessageInfo> = 2,
+    }
+}
+
+crate::jce_struct! {
+    #[derive(Debug, Default)]
+    struct PushMessageInfo {
+        from_uin: i64 = 0,
+        msg_time: i64 = 1,
+        msg_type: i16 = 2,
+        msg_seq: i16 = 3,
+        v_msg: Bytes = 6,
+    }
+}
+
+crate::jce_struct! {
+    /// The `v_msg` of a [`PushMessageInfo`] of type `0x210`.
+    #[derive(Debug, Default)]
+    struct MsgType0x210 {
+        sub_msg_type: i64 = 0,
+        msg_content: Bytes = 10,
+    }
+}
+
+crate::jce_struct! {
+    /// `StatSvc.ReqMSFOffline`, under `RequestMSFForceOffline`.
+    #[derive(Debug, Default)]
+    struct RequestMsfForceOffline {
+        uin: i64 = 0,
+        info: String = 3,
+        title: String = 4,
+    }
+}
+
+crate::jce_struct! {
+    /// `MessageSvc.PushForceOffline`, under `req_PushForceOffline`.
+    #[derive(Debug, Default)]
+    struct RequestPushForceOffline {
+        uin: i64 = 0,
+        title: String = 1,
+        tips: String = 2,
+        same_device: bool = 3,
+    }
+}
+
+/// Turns a server initiated packet into an event, if it is worth one.
+pub type PushHandler = fn(&RequestClient, Packet) -> Option<EventKind>;
+
+/// Push handlers by command, where a trailing `*` matches any suffix.
+///
+/// Exact commands win over patterns, and later registrations over earlier ones.
+pub(crate) struct PushHandlers {
+    handlers: Vec<(&'static str, PushHandler)>,
+}
+
+impl PushHandlers {
+    pub fn new() -> Self {
+        let mut handlers = Self { handlers: vec![] };
+        handlers.register("OnlinePush.*", online_push);
+        handlers.register("OnlinePush.PbPushGroupMsg", group_message);
+        handlers.register("OnlinePush.ReqPush", req_push);
+        handlers.register("OnlinePush.PbPushTransMsg", trans_message);
+        handlers.register("MessageSvc.PushNotify", message_notify);
+        handlers.register("MessageSvc.PushForceOffline", kicked);
+        handlers.register("ConfigPushSvc.PushReq", config_push);
+        handlers.register("StatSvc.ReqMSFOffline", offline);
+        handlers
+    }
+
+    pub fn register(&mut self, command: &'static str, handler: PushHandler) {
+        self.handlers.push((command, handler));
+    }
+
+    pub fn get(&self, command: &str) -> Option<PushHandler> {
+        let mut pattern = None;
+        for &(c, handler) in self.handlers.iter().rev() {
+            if c == command {
+                return Some(handler);
+            }
+
+            if pattern.is_none() {
+                if let Some(prefix) = c.strip_suffix('*') {
+                    if command.starts_with(prefix) {
+                        pattern = Some(handler);
+                    }
+                }
+            }
+        }
+
+        pattern
+    }
+}
+
+fn online_push(_: &RequestClient, pkt: Packet) -> Option<EventKind> {
+    Some(EventKind::OnlinePush {
+        command: pkt.command.into_owned(),
+        body: pkt.body,
+    })
+}
+
+/// How many split group messages are waited on at once, the oldest is dropped beyond.
+const MAX_SPLIT_MESSAGES: usize = 16;
+
+/// The parts of split group messages, by group and `div_seq`.
+#[derive(Default)]
+pub(crate) struct MessageParts {
+    parts: HashMap<(u64, i32), Vec<Msg>>,
+}
+
+impl MessageParts {
+    /// Adds a part, handing back the whole message once the last has arrived.
+    fn add(&mut self, key: (u64, i32), msg: Msg) -> Option<Msg> {
+        let (index, num) = msg
+            .content_head
+            .as_ref()
+            .map_or((0, 1), |c| (c.pkg_index, c.pkg_num));
+        if !self.parts.contains_key(&key) && self.parts.len() >= MAX_SPLIT_MESSAGES {
+            // some part of the oldest never came
+            let oldest = self
+                .parts
+                .iter()
+                .min_by_key(|(_, parts)| parts[0].head.as_ref().map(|h| h.msg_time))
+                .map(|(&key, _)| key);
+            if let Some(oldest) = oldest {
+                self.parts.remove(&oldest);
+            }
+        }
+
+        let parts = self.parts.entry(key).or_default();
+        // a part pushed again
+        if parts.iter().any(|p| pkg_index(p) == index) {
+            return None;
+        }
+        parts.push(msg);
+        if parts.len() < num as usize {
+            return None;
+        }
+
+        let mut parts = self.parts.remove(&key)?;
+        parts.sort_by_key(pkg_index);
+        Some(merge_parts(parts))
+    }
+}
+
+fn pkg_index(msg: &Msg) -> i32 {
+    msg.content_head.as_ref().map_or(0, |c| c.pkg_index)
+}
+
+/// The first part, with the elems of the others appended to its own.
+fn merge_parts(parts: Vec<Msg>) -> Msg {
+    let mut parts = parts.into_iter();
+    let mut msg = parts.next().unwrap_or_default();
+    let rich_text = msg
+        .body
+        .get_or_insert_with(Default::default)
+        .rich_text
+        .get_or_insert_with(Default::default);
+    for part in parts.filter_map(|p| p.body?.rich_text) {
+        rich_text.elems.extend(part.elems);
+        rich_text.ptt = rich_text.ptt.take().or(part.ptt);
+    }
+    msg
+}
+
+/// A group message, put together first if it was split over several pushes.
+fn group_message(client: &RequestClient, pkt: Packet) -> Option<EventKind> {
+    let Some(msg) = PushMessagePacket::decode(&*pkt.body)
+        .ok()
+        .and_then(|p| p.message)
+    else {
+        return online_push(client, pkt);
+    };
+
+    let div_seq = msg
+        .content_head
+        .as_ref()
+        .filter(|c| c.pkg_num > 1)
+        .map(|c| c.div_seq);
+    let msg = match div_seq {
+        Some(div_seq) => {
+            let group = msg
+                .head
+                .as_ref()
+                .and_then(|h| h.group_info.as_ref())
+                .map_or(0, |g| g.group_code);
+            client
+                .group_message_parts
+                .lock()
+                .add((group, div_seq), msg)?
+        }
+        None => msg,
+    };
+
+    match decode_group_message(msg) {
+        Some(msg) => Some(EventKind::GroupMessage(msg)),
+        None => online_push(client, pkt),
+    }
+}
+
+fn decode_group_message(msg: Msg) -> Option<GroupMessage> {
+    let head = msg.head?;
+    let rich_text = msg.body.and_then(|b| b.rich_text).unwrap_or_default();
+    let id = message_id(&head, &rich_text);
+    let group = head.group_info?;
+
+    Some(GroupMessage {
+        id,
+        group: group.group_code,
+        group_name: String::from_utf8_lossy(&group.group_name).into_owned(),
+        sender: head.from_uin,
+        sender_card: String::from_utf8_lossy(&group.group_card).into_owned(),
+        chain: MessageChain::from_rich_text(rich_text),
+    })
+}
+
+fn message_id(head: &MessageHead, rich_text: &RichText) -> MessageId {
+    MessageId {
+        seq: head.msg_seq,
+        rand: rich_text
+            .attr
+            .as_ref()
+            .map(|a| a.random)
+            .unwrap_or_default(),
+        time: head.msg_time,
+    }
+}
+
+/// A message fetched by `MessageSvc.PbGetMsg`, if its type is made into an event.
+pub(crate) fn decode_c2c_message(msg: Msg) -> Option<EventKind> {
+    let head = msg.head?;
+    let rich_text = msg.body.and_then(|b| b.rich_text).unwrap_or_default();
+    let id = message_id(&head, &rich_text);
+
+    match MessageHeadType::try_from(head.msg_type).ok()? {
+        MessageHeadType::Friend | MessageHeadType::FriendVoice => {
+            Some(EventKind::FriendMessage(FriendMessage {
+                id,
+                sender: head.from_uin,
+                target: head.to_uin,
+                chain: MessageChain::from_rich_text(rich_text),
+            }))
+        }
+        MessageHeadType::Temp => {
+            let tmp = head.c2c_tmp_msg_head?;
+            let group = match tmp.group_code {
+                0 => group_code(tmp.group_uin),
+                code => code,
+            };
+            Some(EventKind::TempMessage(TempMessage {
+                id,
+                group,
+                sender: head.from_uin,
+                chain: MessageChain::from_rich_text(rich_text),
+            }))
+        }
+        // sent by the group, under its uin
+        MessageHeadType::MemberJoin => Some(EventKind::MemberJoin(MemberJoin {
+            group: group_code(head.from_uin),
+            member: head.auth_uin,
+        })),
+        _ => None,
+    }
+}
+
+/// The code a group is known by, from the uin it sends system messages under.
+fn group_code(uin: u64) -> u64 {
+    let left = uin / 1_000_000;
+    let left = match left {
+        202..=212 => left - 202,
+        480..=488 => left - 469,
+        2010..=2099 => left - 1943,
+        2100..=2146 => left - 2080,
+        2147..=2199 => left - 2077,
+        2600..=2651 => left - 2265,
+        3800..=3989 => left - 3490,
+        4100..=4199 => left - 3890,
+        _ => left,
+    };
+    left * 1_000_000 + uin % 1_000_000
+}
+
+/// `sub_type` of a request no one answered yet.
+const UNANSWERED: i32 = 1;
+
+/// A friend request listed by `ProfileService.Pb.ReqSystemMsgNew.Friend`, unless it
+/// was answered already.
+pub(crate) fn friend_request(msg: StructMsg) -> Option<EventKind> {
+    let system = msg.msg.filter(|m| m.sub_type == UNANSWERED)?;
+    Some(EventKind::FriendRequest(FriendRequest {
+        seq: msg.msg_seq,
+        uin: msg.req_uin,
+        nickname: system.req_uin_nick,
+        message: system.msg_additional,
+    }))
+}
+
+/// A request to join a group, listed by `ProfileService.Pb.ReqSystemMsgNew.Group`,
+/// unless it was answered already. Invitations of this account are left out.
+pub(crate) fn group_join_request(msg: StructMsg) -> Option<EventKind> {
+    const JOIN: i32 = 1;
+    const INVITED_BY_MEMBER: i32 = 22;
+
+    let system = msg.msg.filter(|m| m.sub_type == UNANSWERED)?;
+    let invitor = match system.group_msg_type {
+        JOIN => None,
+        INVITED_BY_MEMBER => Some(system.action_uin),
+        _ => return None,
+    };
+    Some(EventKind::GroupJoinRequest(GroupJoinRequest {
+        seq: msg.msg_seq,
+        group: system.group_code,
+        uin: msg.req_uin,
+        nickname: system.req_uin_nick,
+        message: system.msg_additional,
+        invitor,
+    }))
+}
+
+/// Group and friend notices in `OnlinePush.ReqPush`, the first one made into an event.
+fn req_push(client: &RequestClient, pkt: Packet) -> Option<EventKind> {
+    const GROUP_NOTICE: i16 = 0x2dc;
+    const FRIEND_NOTICE: i16 = 0x210;
+
+    let event = UniPacket::decode(pkt.body.clone())
+        .and_then(|pkt| pkt.get::<SvcReqPushMsg>("req"))
+        .ok()
+        .and_then(|req| {
+            req.msg_infos
+                .into_iter()
+                .find_map(|info| match info.msg_type {
+                    GROUP_NOTICE => group_notice(info.v_msg),
+                    FRIEND_NOTICE => friend_notice(info.v_msg),
+                    _ => None,
+                })
+        });
+
+    match event {
+        Some(event) => Some(event),
+        None => online_push(client, pkt),
+    }
+}
+
+/// Message type `0x2dc`: the group code, the notice type, then the notice.
+fn group_notice(mut buf: Bytes) -> Option<EventKind> {
+    const MUTE: u8 = 0x0c;
+    const RECALL: u8 = 0x11;
+    const GRAY_TIP: u8 = 0x14;
+
+    if buf.remaining() < 6 {
+        return None;
+    }
+    let group = buf.get_u32() as u64;
+    let ty = buf.get_u8();
+    buf.advance(1);
+
+    match ty {
+        // the operator, the time and how many targets, of which there is always one
+        MUTE if buf.remaining() >= 18 => {
+            let operator = buf.get_u32() as u64;
+            buf.advance(6);
+            let target = buf.get_u32() as u64;
+            let duration = Duration::from_secs(buf.get_u32() as u64);
+            Some(EventKind::MemberMute(MemberMute {
+                group,
+                operator,
+                target: Some(target).filter(|&t| t != 0),
+                duration,
+            }))
+        }
+        // another byte, then a protobuf
+        RECALL | GRAY_TIP if buf.has_remaining() => {
+            buf.advance(1);
+            let body = NotifyMsgBody::decode(buf).ok()?;
+            match body.opt_msg_recall {
+                Some(recall) => {
+                    let operator = recall.uin;
+                    let msg = recall.recalled_msg_list.into_iter().next()?;
+                    Some(group_recall(group, operator, msg))
+                }
+                None => poke(Some(group), body.opt_general_gray_tip?),
+            }
+        }
+        _ => None,
+    }
+}
+
+fn group_recall(group: u64, operator: u64, msg: RecalledMessageMeta) -> EventKind {
+    EventKind::MessageRecall(MessageRecall {
+        id: MessageId {
+            seq: msg.seq,
+            rand: msg.msg_random,
+            time: msg.time,
+        },
+        group: Some(group),
+        sender: msg.author_uin,
+        operator,
+    })
+}
+
+/// The gray tip a poke leaves, naming who poked whom.
+fn poke(group: Option<u64>, tip: GeneralGrayTipInfo) -> Option<EventKind> {
+    const POKE: (i64, i64) = (12, 1061);
+
+    if (tip.busi_type, tip.busi_id) != POKE {
+        return None;
+    }
+    let param = |name: &str| {
+        let param = tip.msg_templ_param.iter().find(|p| p.name == name)?;
+        param.value.parse().ok()
+    };
+    Some(EventKind::Poke(Poke {
+        group,
+        sender: param("uin_str1")?,
+        target: param("uin_str2")?,
+    }))
+}
+
+/// Message type `0x210`: a jce struct holding the notice type and a protobuf.
+fn friend_notice(v_msg: Bytes) -> Option<EventKind> {
+    const PROFILE: i64 = 0x27;
+    const RECALL: i64 = 0x8a;
+    const FRIEND_ADD: i64 = 0xb3;
+    const GRAY_TIP: i64 = 0x122;
+
+    let msg = MsgType0x210::from_bytes(v_msg).ok()?;
+    let content = msg.msg_content;
+    match msg.sub_msg_type {
+        PROFILE => Sub27::decode(content)
+            .ok()?
+            .mod_infos
+            .into_iter()
+            .find_map(profile_change),
+        RECALL => {
+            let msg = Sub8a::decode(content).ok()?.msg_info.into_iter().next()?;
+            Some(EventKind::MessageRecall(MessageRecall {
+                id: MessageId {
+                    seq: msg.msg_seq,
+                    rand: msg.msg_random,
+                    time: msg.msg_time,
+                },
+                group: None,
+                sender: msg.from_uin,
+                operator: msg.from_uin,
+            }))
+        }
+        FRIEND_ADD => {
+            let notify = SubB3::decode(content).ok()?.msg_add_frd_notify?;
+            Some(EventKind::FriendAdd(FriendAdd {
+                uin: notify.fuin,
+                nickname: notify.fuin_nick,
+            }))
+        }
+        GRAY_TIP => poke(None, GeneralGrayTipInfo::decode(content).ok()?),
+        _ => None,
+    }
+}
+
+/// Friend deletions and group renames, out of the changes a `0x27` notice lists.
+fn profile_change(change: ForwardBody) -> Option<EventKind> {
+    const GROUP_NAME: u32 = 1;
+
+    if let Some(deleted) = change.del_friend {
+        let uin = *deleted.uins.first()?;
+        return Some(EventKind::FriendDelete(FriendDelete { uin }));
+    }
+
+    let profile = change.mod_group_profile?;
+    let name = profile
+        .group_profile_infos
+        .into_iter()
+        .find(|info| info.field == GROUP_NAME)?;
+    Some(EventKind::GroupNameChange(GroupNameChange {
+        group: profile.group_code,
+        operator: profile.cmd_uin,
+        name: String::from_utf8_lossy(&name.value).into_owned(),
+    }))
+}
+
+/// Group member changes, of which members leaving are made into events.
+fn trans_message(client: &RequestClient, pkt: Packet) -> Option<EventKind> {
+    let event = TransMsgInfo::decode(pkt.body.clone())
+        .ok()
+        .and_then(member_leave);
+
+    match event {
+        Some(event) => Some(event),
+        None => online_push(client, pkt),
+    }
+}
+
+/// Message type `34`: the group code, a byte, the member, how they left, then who
+/// kicked them.
+fn member_leave(info: TransMsgInfo) -> Option<EventKind> {
+    const MEMBER_LEAVE: i32 = 34;
+
+    let mut buf = info.msg_data;
+    if info.msg_type != MEMBER_LEAVE || buf.remaining() < 10 {
+        return None;
+    }
+    let group = buf.get_u32() as u64;
+    buf.advance(1);
+    let member = buf.get_u32() as u64;
+    let operator = match buf.get_u8() {
+        0x02 | 0x82 => None,
+        0x03 | 0x83 if buf.remaining() >= 4 => Some(buf.get_u32() as u64),
+        _ => return None,
+    };
+
+    Some(EventKind::MemberLeave(MemberLeave {
+        group,
+        member,
+        operator,
+    }))
+}
+
+fn message_notify(_: &RequestClient, pkt: Packet) -> Option<EventKind> {
+    Some(EventKind::MessageNotify { body: pkt.body })
+}
+
+fn config_push(client: &RequestClient, pkt: Packet) -> Option<EventKind> {
+    match sso_servers(pkt.body.clone()) {
+        Ok(servers) if !servers.is_empty() => client.servers.lock().update(servers),
+        _ => {}
+    }
+    Some(EventKind::ConfigPush { body: pkt.body })
+}
+
+/// The sso servers listed by a `ConfigPushSvc.PushReq`, none if it pushes something else.
+fn sso_servers(body: Bytes) -> Result<Vec<SocketAddr>, JceError> {
+    let req: PushReq = UniPacket::decode(body)?.get("PushReq")?;
+    if req.ty != 1 {
+        return Ok(vec![]);
+    }
+
+    let list = SsoServerList::from_bytes(req.jce_buf)?;
+    let servers = list.servers.into_iter().filter_map(|info| {
+        // host names are left out, resolving them is up to the caller
+        let ip = info.server.parse::<IpAddr>().ok()?;
+        Some(SocketAddr::new(ip, u16::try_from(info.port).ok()?))
+    });
+    Ok(servers.collect())
+}
+
+fn offline(client: &RequestClient, pkt: Packet) -> Option<EventKind> {
+    // nothing pending will be answered anymore
+    client.cancel_pending();
+    let req: RequestMsfForceOffline = UniPacket::decode(pkt.body)
+        .and_then(|pkt| pkt.get("RequestMSFForceOffline"))
+        .unwrap_or_default();
+    Some(EventKind::Offline(Offline {
+        title: req.title,
+        message: req.info,
+    }))
+}
+
+fn kicked(client: &RequestClient, pkt: Packet) -> Option<EventKind> {
+    client.cancel_pending();
+    let req: RequestPushForceOffline = UniPacket::decode(pkt.body)
+        .and_then(|pkt| pkt.get("req_PushForceOffline"))
+        .unwrap_or_default();
+    Some(EventKind::Kicked(Kicked {
+        title: req.title,
+        message: req.tips,
+        same_device: req.same_device,
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::client::push::{
+        decode_c2c_message, friend_request, group_join_request, MsgType0x210, PushHandlers,
+        PushMessageInfo, PushReq, RequestPushForceOffline, SsoServerInfo, SsoServerList,
+        SvcReqPushMsg,
+    };
+    use crate::client::RequestClient;
+    use crate::data::packet::{Encrypt, Packet, PacketDetail};
+    use crate::event::{EventKind, MessageId};
+    use crate::jce::{JceStruct, UniPacket};
+    use crate::net::server::ServerList;
+    use crate::proto::message::{
+        Attr, C2cTmpMsgHead, ContentHead, GroupInfo, MessageBody, MessageHead, MessageHeadType,
+        Msg, PushMessagePacket, RichText,
+    };
+    use crate::proto::notify::{
+        DelFriend, ForwardBody, GeneralGrayTipInfo, GroupProfileInfo, MessageRecallReminder,
+        ModGroupProfile, NotifyMsgBody, RecalledMessageMeta, Sub27, Sub8a, Sub8aMsgInfo, SubB3,
+        SubB3AddFrdNotify, TemplParam, TransMsgInfo,
+    };
+    use crate::proto::structmsg::{StructMsg, SystemMsg};
+    use bytes::{BufMut, Bytes, BytesMut};
+    use prost::Message;
+    use std::borrow::Cow;
+    use std::net::SocketAddr;
+    use std::time::Duration;
+
+    fn packet(command: &'static str, body: Bytes) -> Packet {
+        Packet {
+            seq: 0,
+            uin: 0,
+            packet_detail: PacketDetail::Uin,
+            encrypt: Encrypt::UseD2Key,
+            command: Cow::Borrowed(command),
+            body,
+            message: String::new(),
+        }
+    }
+
+    fn test(_: &RequestClient, _: Packet) -> Option<EventKind> {
+        Some(EventKind::Online)
+    }
+
+    #[test]
+    fn lookup() {
+        let mut handlers = PushHandlers::new();
+        assert!(handlers.get("OnlinePush.PbPushGroupMsg").is_some());
+        assert!(handlers.get("OnlinePush").is_none());
+        assert!(handlers.get("MessageSvc.PushNotify").is_some());
+        assert!(handlers.get("MessageSvc.PushNotify2").is_none());
+        assert!(handlers.get("Heartbeat.Alive").is_none());
+
+        handlers.register("OnlinePush.ReqPush", test);
+        handlers.register("Heartbeat.*", test);
+
+        let client = RequestClient::new();
+        let event = |command: &'static str| {
+            let handler = handlers.get(command).unwrap();
+            handler(&client, packet(command, Bytes::new()))
+        };
+        assert!(matches!(
+            event("OnlinePush.ReqPush"),
+            Some(EventKind::Online)
+        ));
+        assert!(matches!(
+            event("OnlinePush.PbPushGroupMsg"),
+            Some(EventKind::OnlinePush { .. })
+        ));
+        assert!(matches!(event("Heartbeat.Alive"), Some(EventKind::Online)));
+    }
+
+    fn push_req(ty: i32, servers: &[(&str, i32)]) -> Bytes {
+        let list = SsoServerList {
+            servers: servers
+                .iter()
+                .map(|&(server, port)| SsoServerInfo {
+                    server: server.into(),
+                    port,
+                    location: "sh".into(),
+                })
+                .collect(),
+        };
+        let req = PushReq {
+            ty,
+            jce_buf: list.to_bytes(),
+            seq: 1,
+        };
+
+        let mut pkt = UniPacket::new("QQService.ConfigPushSvc.MainServant", "PushReq");
+        pkt.put("PushReq", &req);
+        pkt.encode()
+    }
+
+    #[test]
+    fn config_push() {
+        let handlers = PushHandlers::new();
+        let handler = handlers.get("ConfigPushSvc.PushReq").unwrap();
+        let client = RequestClient::new();
+        *client.servers.lock() = ServerList::new(["1.1.1.1:80".parse().unwrap()]);
+
+        let servers = [
+            ("10.0.0.1", 8080),
+            ("msfwifi.3g.qq.com", 8080),
+            ("10.0.0.2", 443),
+        ];
+        let event = handler(
+            &client,
+            packet("ConfigPushSvc.PushReq", push_req(1, &servers)),
+        );
+        assert!(matches!(event, Some(EventKind::ConfigPush { .. })));
+        let expected: [SocketAddr; 2] = [
+            "10.0.0.1:8080".parse().unwrap(),
+            "10.0.0.2:443".parse().unwrap(),
+        ];
+        assert_eq!(client.servers().addrs(), expected);
+
+        // other pushes leave the servers alone
+        handler(
+            &client,
+            packet("ConfigPushSvc.PushReq", push_req(2, &[("10.0.0.3", 80)])),
+        );
+        handler(
+            &client,
+            packet("ConfigPushSvc.PushReq", Bytes::from_static(b"junk")),
+        );
+        assert_eq!(client.servers().addrs(), expected);
+    }
+
+    fn push(command: &'static str, body: Bytes) -> Option<EventKind> {
+        let handlers = PushHandlers::new();
+        handlers.get(command).unwrap()(&RequestClient::new(), packet(command, body))
+    }
+
+    #[test]
+    fn group_message() {
+        let msg = PushMessagePacket {
+            message: Some(Msg {
+                head: Some(MessageHead {
+                    from_uin: 10001,
+                    msg_seq: 42,
+                    msg_time: 1_600_000_000,
+                    group_info: Some(GroupInfo {
+                        group_code: 20001,
+                        group_card: Bytes::from_static(b"card"),
+                        group_name: Bytes::from_static(b"group"),
+                        ..Default::default()
+                    }),
+                    ..Default::default()
+                }),
+                content_head: None,
+                body: Some(MessageBody {
+                    rich_text: Some(RichText {
+                        attr: Some(Attr {
+                            random: 7,
+                            ..Default::default()
+                        }),
+                        elems: vec![Bytes::from_static(b"\x0a\x04\x0a\x02hi")],
+                        ptt: None,
+                    }),
+                }),
+            }),
+        };
+
+        match push("OnlinePush.PbPushGroupMsg", msg.encode_to_vec().into()) {
+            Some(EventKind::GroupMessage(msg)) => {
+                let id = MessageId {
+                    seq: 42,
+                    rand: 7,
+                    time: 1_600_000_000,
+                };
+                assert_eq!(msg.id, id);
+                assert_eq!((msg.group, msg.sender), (20001, 10001));
+                assert_eq!(msg.group_name, "group");
+                assert_eq!(msg.sender_card, "card");
+                assert_eq!(msg.chain.text(), "hi");
+            }
+            e => panic!("unexpected event {:?}", e),
+        }
+    }
+
+    fn group_message_part(seq: i32, index: i32, elem: &'static [u8]) -> Bytes {
+        let msg = PushMessagePacket {
+            message: Some(Msg {
+                head: Some(MessageHead {
+                    from_uin: 10001,
+                    msg_seq: seq,
+                    msg_time: 1_600_000_000,
+                    group_info: Some(GroupInfo {
+                        group_code: 20001,
+                        ..Default::default()
+                    }),
+                    ..Default::default()
+                }),
+                content_head: Some(ContentHead {
+                    pkg_num: 2,
+                    pkg_index: index,
+                    div_seq: 5,
+                }),
+                body: Some(MessageBody {
+                    rich_text: Some(RichText {
+                        attr: None,
+                        elems: vec![Bytes::from_static(elem)],
+                        ptt: None,
+                    }),
+                }),
+            }),
+        };
+        msg.encode_to_vec().into()
+    }
+
+    #[test]
+    fn split_group_message() {
+        let client = RequestClient::new();
+        let handler = PushHandlers::new()
+            .get("OnlinePush.PbPushGroupMsg")
+            .unwrap();
+        let push = |body| handler(&client, packet("OnlinePush.PbPushGroupMsg", body));
+
+        // the second part first, and pushed twice
+        let first = group_message_part(42, 0, b"\x0a\x04\x0a\x02hi");
+        let second = group_message_part(43, 1, b"\x0a\x09\x0a\x07 there!");
+        assert!(push(second.clone()).is_none());
+        assert!(push(second).is_none());
+        match push(first) {
+            Some(EventKind::GroupMessage(msg)) => {
+                assert_eq!(msg.id.seq, 42);
+                assert_eq!(msg.chain.text(), "hi there!");
+            }
+            e => panic!("unexpected event {:?}", e),
+        }
+        assert!(client.group_message_parts.lock().parts.is_empty());
+    }
+
+    fn req_push(msg_type: i16, v_msg: Bytes) -> Bytes {
+        let req = SvcReqPushMsg {
+            uin: 10001,
+            msg_time: 0,
+            msg_infos: vec![PushMessageInfo {
+                from_uin: 20001,
+                msg_type,
+                v_msg,
+                ..Default::default()
+            }],
+        };
+        let mut pkt = UniPacket::new("OnlinePush", "SvcReqPushMsg");
+        pkt.put("req", &req);
+        pkt.encode()
+    }
+
+    fn mute(target: u32, secs: u32) -> Bytes {
+        let mut buf = BytesMut::new();
+        buf.put_u32(20001);
+        buf.put_u8(0x0c);
+        buf.put_u8(0);
+        buf.put_u32(10002);
+        buf.put_u32(1_600_000_000);
+        buf.put_u16(1);
+        buf.put_u32(target);
+        buf.put_u32(secs);
+        buf.freeze()
+    }
+
+    #[test]
+    fn member_mute() {
+        match push("OnlinePush.ReqPush", req_push(0x2dc, mute(10003, 600))) {
+            Some(EventKind::MemberMute(mute)) => {
+                assert_eq!((mute.group, mute.operator), (20001, 10002));
+                assert_eq!(mute.target, Some(10003));
+                assert_eq!(mute.duration, Duration::from_secs(600));
+            }
+            e => panic!("unexpected event {:?}", e),
+        }
+
+        let event = push("OnlinePush.ReqPush", req_push(0x2dc, mute(0, 0)));
+        assert!(matches!(
+            event,
+            Some(EventKind::MemberMute(mute)) if mute.target.is_none() && mute.duration.is_zero()
+        ));
+
+        // anything else is left as it came
+        for body in [req_push(0x210, mute(0, 0)), Bytes::from_static(b"junk")] {
+            let event = push("OnlinePush.ReqPush", body);
+            assert!(matches!(event, Some(EventKind::OnlinePush { .. })));
+        }
+    }
+
+    #[test]
+    fn kicked() {
+        let req = RequestPushForceOffline {
+            uin: 10001,
+            title: "offline".into(),
+            tips: "logged in elsewhere".into(),
+            same_device: false,
+        };
+        let mut pkt = UniPacket::new("PushNotify", "RequestPushForceOffline");
+        pkt.put("req_PushForceOffline", &req);
+
+        match push("MessageSvc.PushForceOffline", pkt.encode()) {
+            Some(EventKind::Kicked(kicked)) => {
+                assert_eq!(kicked.title, "offline");
+                assert_eq!(kicked.message, "logged in elsewhere");
+                assert!(!kicked.same_device);
+            }
+            e => panic!("unexpected event {:?}", e),
+        }
+    }
+
+    fn c2c(msg_type: MessageHeadType, head: MessageHead) -> Msg {
+        Msg {
+            head: Some(MessageHead {
+                msg_type: msg_type as i32,
+                msg_seq: 42,
+                msg_time: 1_600_000_000,
+                ..head
+            }),
+            content_head: None,
+            body: Some(MessageBody {
+                rich_text: Some(RichText {
+                    attr: Some(Attr {
+                        random: 7,
+                        ..Default::default()
+                    }),
+                    elems: vec![Bytes::from_static(b"\x0a\x04\x0a\x02hi")],
+                    ptt: None,
+                }),
+            }),
+        }
+    }
+
+    const ID: MessageId = MessageId {
+        seq: 42,
+        rand: 7,
+        time: 1_600_000_000,
+    };
+
+    #[test]
+    fn friend_message() {
+        let head = MessageHead {
+            from_uin: 10002,
+            to_uin: 10001,
+            ..Default::default()
+        };
+        match decode_c2c_message(c2c(MessageHeadType::Friend, head)) {
+            Some(EventKind::FriendMessage(msg)) => {
+                assert_eq!(msg.id, ID);
+                assert_eq!((msg.sender, msg.target), (10002, 10001));
+                assert_eq!(msg.chain.text(), "hi");
+            }
+            e => panic!("unexpected event {:?}", e),
+        }
+
+        // types left to the caller
+        let head = MessageHead::default();
+        assert!(decode_c2c_message(c2c(MessageHeadType::Troop, head)).is_none());
+    }
+
+    #[test]
+    fn temp_message() {
+        let temp = |group_uin, group_code| {
+            let head = MessageHead {
+                from_uin: 10002,
+                c2c_tmp_msg_head: Some(C2cTmpMsgHead {
+                    group_uin,
+                    group_code,
+                    ..Default::default()
+                }),
+                ..Default::default()
+            };
+            decode_c2c_message(c2c(MessageHeadType::Temp, head))
+        };
+
+        match temp(0, 20001) {
+            Some(EventKind::TempMessage(msg)) => {
+                assert_eq!(msg.id, ID);
+                assert_eq!((msg.group, msg.sender), (20001, 10002));
+                assert_eq!(msg.chain.text(), "hi");
+            }
+            e => panic!("unexpected event {:?}", e),
+        }
+
+        // only the uin of the group given
+        let event = temp(202_020_001, 0);
+        assert!(matches!(event, Some(EventKind::TempMessage(msg)) if msg.group == 20001));
+    }
+
+    #[test]
+    fn member_join() {
+        let head = MessageHead {
+            from_uin: 2_157_020_001,
+            auth_uin: 10002,
+            ..Default::default()
+        };
+        match decode_c2c_message(c2c(MessageHeadType::MemberJoin, head)) {
+            Some(EventKind::MemberJoin(join)) => {
+                assert_eq!((join.group, join.member), (80_020_001, 10002));
+            }
+            e => panic!("unexpected event {:?}", e),
+        }
+    }
+
+    fn member_leave(how: u8, operator: Option<u32>) -> Bytes {
+        let mut msg_data = BytesMut::new();
+        msg_data.put_u32(20001);
+        msg_data.put_u8(1);
+        msg_data.put_u32(10002);
+        msg_data.put_u8(how);
+        if let Some(operator) = operator {
+            msg_data.put_u32(operator);
+        }
+
+        let info = TransMsgInfo {
+            msg_type: 34,
+            msg_data: msg_data.freeze(),
+            ..Default::default()
+        };
+        info.encode_to_vec().into()
+    }
+
+    #[test]
+    fn member_leave_or_kicked() {
+        match push("OnlinePush.PbPushTransMsg", member_leave(0x02, None)) {
+            Some(EventKind::MemberLeave(leave)) => {
+                assert_eq!((leave.group, leave.member), (20001, 10002));
+                assert_eq!(leave.operator, None);
+            }
+            e => panic!("unexpected event {:?}", e),
+        }
+
+        let event = push("OnlinePush.PbPushTransMsg", member_leave(0x83, Some(10003)));
+        assert!(matches!(
+            event,
+            Some(EventKind::MemberLeave(leave)) if leave.operator == Some(10003)
+        ));
+
+        // other changes are left as they came
+        let event = push("OnlinePush.PbPushTransMsg", member_leave(0x01, None));
+        assert!(matches!(event, Some(EventKind::OnlinePush { .. })));
+    }
+
+    /// A group notice carrying a protobuf.
+    fn group_notice(ty: u8, body: NotifyMsgBody) -> Bytes {
+        let mut buf = BytesMut::new();
+        buf.put_u32(20001);
+        buf.put_u8(ty);
+        buf.put_u16(0);
+        buf.put_slice(&body.encode_to_vec());
+        req_push(0x2dc, buf.freeze())
+    }
+
+    /// A friend notice of `sub_msg_type`.
+    fn friend_notice(sub_msg_type: i64, content: impl Message) -> Bytes {
+        let msg = MsgType0x210 {
+            sub_msg_type,
+            msg_content: content.encode_to_vec().into(),
+        };
+        req_push(0x210, msg.to_bytes())
+    }
+
+    #[test]
+    fn message_recall() {
+        let body = NotifyMsgBody {
+            opt_msg_recall: Some(MessageRecallReminder {
+                uin: 10002,
+                recalled_msg_list: vec![RecalledMessageMeta {
+                    seq: 42,
+                    time: 1_600_000_000,
+                    msg_random: 7,
+                    author_uin: 10003,
+                    ..Default::default()
+                }],
+                ..Default::default()
+            }),
+            opt_general_gray_tip: None,
+        };
+        match push("OnlinePush.ReqPush", group_notice(0x11, body)) {
+            Some(EventKind::MessageRecall(recall)) => {
+                assert_eq!(recall.id, ID);
+                assert_eq!(recall.group, Some(20001));
+                assert_eq!((recall.sender, recall.operator), (10003, 10002));
+            }
+            e => panic!("unexpected event {:?}", e),
+        }
+
+        let sub = Sub8a {
+            msg_info: vec![Sub8aMsgInfo {
+                from_uin: 10002,
+                to_uin: 10001,
+                msg_seq: 42,
+                msg_time: 1_600_000_000,
+                msg_random: 7,
+                ..Default::default()
+            }],
+        };
+        match push("OnlinePush.ReqPush", friend_notice(0x8a, sub)) {
+            Some(EventKind::MessageRecall(recall)) => {
+                assert_eq!(recall.id, ID);
+                assert_eq!(recall.group, None);
+                assert_eq!((recall.sender, recall.operator), (10002, 10002));
+            }
+            e => panic!("unexpected event {:?}", e),
+        }
+    }
+
+    #[test]
+    fn profile_change() {
+        let sub = Sub27 {
+            mod_infos: vec![ForwardBody {
+                mod_group_profile: Some(ModGroupProfile {
+                    group_code: 20001,
+                    cmd_uin: 10002,
+                    group_profile_infos: vec![GroupProfileInfo {
+                        field: 1,
+                        value: Bytes::from_static(b"renamed"),
+                    }],
+                    ..Default::default()
+                }),
+                ..Default::default()
+            }],
+        };
+        match push("OnlinePush.ReqPush", friend_notice(0x27, sub)) {
+            Some(EventKind::GroupNameChange(change)) => {
+                assert_eq!((change.group, change.operator), (20001, 10002));
+                assert_eq!(change.name, "renamed");
+            }
+            e => panic!("unexpected event {:?}", e),
+        }
+
+        let sub = Sub27 {
+            mod_infos: vec![ForwardBody {
+                del_friend: Some(DelFriend { uins: vec![10002] }),
+                ..Default::default()
+            }],
+        };
+        let event = push("OnlinePush.ReqPush", friend_notice(0x27, sub));
+        assert!(matches!(event, Some(EventKind::FriendDelete(d)) if d.uin == 10002));
+    }
+
+    #[test]
+    fn friend_add() {
+        let sub = SubB3 {
+            r#type: 0,
+            msg_add_frd_notify: Some(SubB3AddFrdNotify {
+                fuin: 10002,
+                fuin_nick: "friend".into(),
+            }),
+        };
+        match push("OnlinePush.ReqPush", friend_notice(0xb3, sub)) {
+            Some(EventKind::FriendAdd(add)) => {
+                assert_eq!(add.uin, 10002);
+                assert_eq!(add.nickname, "friend");
+            }
+            e => panic!("unexpected event {:?}", e),
+        }
+    }
+
+    fn gray_tip(busi_id: i64) -> GeneralGrayTipInfo {
+        let param = |name: &str, value: &str| TemplParam {
+            name: name.into(),
+            value: value.into(),
+        };
+        GeneralGrayTipInfo {
+            busi_type: 12,
+            busi_id,
+            msg_templ_param: vec![param("uin_str1", "10002"), param("uin_str2", "10003")],
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn poke() {
+        let body = NotifyMsgBody {
+            opt_msg_recall: None,
+            opt_general_gray_tip: Some(gray_tip(1061)),
+        };
+        match push("OnlinePush.ReqPush", group_notice(0x14, body)) {
+            Some(EventKind::Poke(poke)) => {
+                assert_eq!(poke.group, Some(20001));
+                assert_eq!((poke.sender, poke.target), (10002, 10003));
+            }
+            e => panic!("unexpected event {:?}", e),
+        }
+
+        let event = push("OnlinePush.ReqPush", friend_notice(0x122, gray_tip(1061)));
+        assert!(matches!(
+            event,
+            Some(EventKind::Poke(poke)) if poke.group.is_none() && poke.target == 10003
+        ));
+
+        // other gray tips are left as they came
+        let event = push("OnlinePush.ReqPush", friend_notice(0x122, gray_tip(1)));
+        assert!(matches!(event, Some(EventKind::OnlinePush { .. })));
+    }
+
+    fn struct_msg(sub_type: i32, group_msg_type: i32) -> StructMsg {
+        StructMsg {
+            msg_seq: 1_600_000_000_000,
+            req_uin: 10002,
+            msg: Some(SystemMsg {
+                sub_type,
+                msg_additional: "hello".into(),
+                group_code: 20001,
+                action_uin: 10003,
+                group_msg_type,
+                req_uin_nick: "nick".into(),
+                ..Default::default()
+            }),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn system_messages() {
+        match friend_request(struct_msg(1, 0)) {
+            Some(EventKind::FriendRequest(req)) => {
+                assert_eq!((req.seq, req.uin), (1_600_000_000_000, 10002));
+                assert_eq!(
+                    (req.nickname.as_str(), req.message.as_str()),
+                    ("nick", "hello")
+                );
+            }
+            e => panic!("unexpected event {:?}", e),
+        }
+        assert!(friend_request(struct_msg(2, 0)).is_none());
+
+        match group_join_request(struct_msg(1, 1)) {
+            Some(EventKind::GroupJoinRequest(req)) => {
+                assert_eq!((req.group, req.uin), (20001, 10002));
+                assert_eq!(req.message, "hello");
+                assert_eq!(req.invitor, None);
+            }
+            e => panic!("unexpected event {:?}", e),
+        }
+        let event = group_join_request(struct_msg(1, 22));
+        assert!(matches!(
+            event,
+            Some(EventKind::GroupJoinRequest(req)) if req.invitor == Some(10003)
+        ));
+        // answered, or this account invited
+        assert!(group_join_request(struct_msg(2, 1)).is_none());
+        assert!(group_join_request(struct_msg(1, 2)).is_none());
+    }
+}
@@ -0,0 +1,449 @@
+use crate::client::login::{self, now, wtlogin_body};
+use crate::client::{ClientBuilder, ClientResult, LoginResponse, RequestClient};
+use crate::data::oicq::{OicqError, OicqPacket};
+use crate::data::tlv::*;
+use crate::event::ClientEvent;
+use crate::executor::{Executor, Timer};
+use crate::net::connector::Connector;
+use crate::net::framed::FramedConnector;
+use bytes::{Buf, BufMut, Bytes, BytesMut};
+use futures::Stream;
+use std::future::Future;
+use std::mem::{discriminant, Discriminant};
+
+/// A login qrcode, shown to the user and scanned with the mobile app.
+#[derive(Debug, Clone)]
+pub struct QrCode {
+    /// PNG image of the code.
+    pub image: Bytes,
+    /// Identifies the code when polling its state.
+    pub sig: Bytes,
+}
+
+/// Credentials granted once the user confirms the login on their phone.
+#[derive(Debug, Clone)]
+pub struct QrCodeLoginInfo {
+    pub uin: u64,
+    tmp_pwd: Bytes,
+    no_pic_sig: Bytes,
+    tgt_qr: Bytes,
+    tgtgt_key: [u8; 16],
+}
+
+#[derive(Debug, Clone)]
+pub enum QrCodeState {
+    WaitingForScan,
+    /// Scanned, waiting for the user to confirm on their phone.
+    WaitingForConfirm,
+    Confirmed(QrCodeLoginInfo),
+    Expired,
+    Canceled,
+}
+
+impl QrCodeState {
+    /// Whether the code will not change state anymore.
+    pub fn is_finished(&self) -> bool {
+        matches!(self, Self::Confirmed(_) | Self::Expired | Self::Canceled)
+    }
+}
+
+const CMD_FETCH: u16 = 0x31;
+const CMD_QUERY: u16 = 0x12;
+
+/// Wraps `body` into `trans header | u32 time | code2d packet`.
+fn trans_emp(app_id: u32, seq: u32, command: u16, body: &[u8]) -> Bytes {
+    let mut buf = BytesMut::new();
+    // 0x00 | u16 len | u32 app id | u32 0x72 | u24 0
+    buf.put_u8(0);
+    buf.put_u16((body.len() + 53) as u16);
+    buf.put_u32(app_id);
+    buf.put_u32(0x72);
+    buf.put_bytes(0, 3);
+    buf.put_u32(now() as u32);
+
+    // 0x02 | u16 len | u16 cmd | 21 | 0x03 | u16 0 | u16 version | u32 seq | u64 0 | body | 0x03
+    buf.put_u8(0x02);
+    buf.put_u16((43 + body.len() + 1) as u16);
+    buf.put_u16(command);
+    buf.put_bytes(0, 21);
+    buf.put_u8(0x03);
+    buf.put_u16(0);
+    buf.put_u16(50);
+    buf.put_u32(seq);
+    buf.put_u64(0);
+    buf.put_slice(body);
+    buf.put_u8(0x03);
+
+    buf.freeze()
+}
+
+fn fetch_request(client: &RequestClient) -> Bytes {
+    let protocol = &client.protocol;
+    let device = &client.device;
+    let guid = device.guid();
+
+    let mut body = BytesMut::new();
+    body.put_u16(0);
+    body.put_u32(protocol.appid);
+    body.put_u64(0);
+    body.put_u8(8);
+    body.put_u16(0);
+
+    let tlvs = [
+        tlv16(protocol, &guid),
+        tlv1b(),
+        tlv1d(protocol),
+        tlv1f(device),
+        tlv33(&guid),
+        tlv35(8),
+    ];
+    body.put_u16(tlvs.len() as u16);
+    for tlv in tlvs {
+        body.put_slice(&tlv);
+    }
+
+    trans_emp(protocol.appid, 0, CMD_FETCH, &body)
+}
+
+fn query_request(client: &RequestClient, sig: &[u8]) -> Bytes {
+    let mut body = BytesMut::new();
+    body.put_u16(5);
+    body.put_u8(1);
+    body.put_u32(8); // product type
+    body.put_u32(client.protocol.appid);
+    body.put_u16(sig.len() as u16);
+    body.put_slice(sig);
+    body.put_u64(0);
+    body.put_u8(8);
+    body.put_u16(0);
+    body.put_u16(0);
+
+    trans_emp(client.protocol.appid, 1, CMD_QUERY, &body)
+}
+
+/// Strips the trans header off a reply, leaving the code2d command and body.
+fn decode_trans_emp(mut buf: Bytes) -> Result<(u16, Bytes), OicqError> {
+    if buf.len() < 49 || buf.last() != Some(&0x03) {
+        return Err(OicqError::Malformed);
+    }
+    buf.truncate(buf.len() - 1);
+
+    buf.advance(5 + 1 + 2);
+    let command = buf.get_u16();
+    buf.advance(21 + 1 + 2 + 2 + 4 + 8);
+
+    Ok((command, buf))
+}
+
+fn decode_fetch(buf: Bytes) -> Result<QrCode, OicqError> {
+    let (command, mut body) = decode_trans_emp(buf)?;
+    if command != CMD_FETCH || body.len() < 9 {
+        return Err(OicqError::Malformed);
+    }
+
+    body.advance(2 + 4);
+    let code = body.get_u8();
+    if code != 0 {
+        return Err(OicqError::Status(code));
+    }
+
+    let len = body.get_u16() as usize;
+    if body.len() < len {
+        return Err(OicqError::Malformed);
+    }
+    let sig = body.split_to(len);
+
+    let mut tlvs = TlvMap::decode_counted(body).map_err(OicqError::Tlv)?;
+    let image = tlvs
+        .remove(0x17)
+        .ok_or(OicqError::Tlv(TlvError::Missing(0x17)))?;
+
+    Ok(QrCode { image, sig })
+}
+
+fn decode_query(buf: Bytes) -> Result<QrCodeState, OicqError> {
+    let (command, mut body) = decode_trans_emp(buf)?;
+    if command != CMD_QUERY || body.len() < 2 {
+        return Err(OicqError::Malformed);
+    }
+
+    // u16 len, then optionally u8 2 | u64 uin, padded up to len
+    let mut len = body.get_u16() as usize;
+    if len != 0 && !body.is_empty() {
+        len -= 1;
+        if body.get_u8() == 2 && body.len() >= 8 {
+            body.advance(8);
+            len = len.saturating_sub(8);
+        }
+    }
+    if body.len() < len + 5 {
+        return Err(OicqError::Malformed);
+    }
+    body.advance(len);
+
+    body.advance(4); // app id
+    match body.get_u8() {
+        0 => {}
+        0x30 => return Ok(QrCodeState::WaitingForScan),
+        0x35 => return Ok(QrCodeState::WaitingForConfirm),
+        0x36 => return Ok(QrCodeState::Canceled),
+        0x11 => return Ok(QrCodeState::Expired),
+        code => return Err(OicqError::Status(code)),
+    }
+
+    if body.len() < 8 + 4 {
+        return Err(OicqError::Malformed);
+    }
+    let uin = body.get_u64();
+    body.advance(4); // sig create time
+
+    let mut tlvs = TlvMap::decode_counted(body).map_err(OicqError::Tlv)?;
+    let mut take = |tag| {
+        tlvs.remove(tag)
+            .ok_or(OicqError::Tlv(TlvError::Missing(tag)))
+    };
+
+    let tmp_pwd = take(0x18)?;
+    let no_pic_sig = take(0x19)?;
+    let tgtgt_key = take(0x1e)?[..]
+        .try_into()
+        .map_err(|_| OicqError::Malformed)?;
+    let tgt_qr = take(0x65).unwrap_or_default();
+
+    Ok(QrCodeState::Confirmed(QrCodeLoginInfo {
+        uin,
+        tmp_pwd,
+        no_pic_sig,
+        tgt_qr,
+        tgtgt_key,
+    }))
+}
+
+/// `wtlogin.login` sub command 9, authenticated by a confirmed qrcode.
+fn qrcode_login(client: &RequestClient, seq: u32, info: &QrCodeLoginInfo) -> Bytes {
+    let protocol = &client.protocol;
+    let device = &client.device;
+    let guid = device.guid();
+    let uin = info.uin as u32;
+
+    let mut t106 = TlvWriter::with_capacity(0x106, info.tmp_pwd.len());
+    t106.put_slice(&info.tmp_pwd);
+
+    wtlogin_body(
+        9,
+        [
+            tlv18(protocol, uin),
+            tlv1(device, uin, rand::random(), now() as u32),
+            t106.into_bytes(),
+            tlv116(protocol),
+            tlv100(protocol),
+            tlv107(0),
+            tlv142(protocol),
+            tlv144(device, &guid, &info.tgtgt_key),
+            tlv145(&guid),
+            tlv147(protocol),
+            tlv16a(&info.no_pic_sig),
+            tlv154(seq),
+            tlv141(device),
+            tlv8(2052),
+            tlv511(DOMAINS),
+            tlv187(device),
+            tlv188(device),
+            tlv194(device),
+            tlv191(0),
+            tlv202(device),
+            tlv177(protocol),
+            tlv516(),
+            tlv521(8),
+            tlv318(&info.tgt_qr),
+        ],
+    )
+}
+
+impl<F, Fu, E, C> ClientBuilder<F, E, FramedConnector<C>>
+where
+    F: Fn(ClientEvent) -> Fu,
+    F: Send + 'static,
+    Fu: Future<Output = ()>,
+    Fu: Send + 'static,
+    E: Executor + Timer,
+    C: Connector,
+{
+    /// Requests a new login qrcode.
+    pub async fn fetch_qrcode(&mut self) -> ClientResult<QrCode> {
+        let seq = self.base.next_seq();
+        let oicq = OicqPacket {
+            uin: 0,
+            command: 0x812,
+            body: fetch_request(&self.base),
+        };
+
+        let rsp = self.wtlogin_raw("wtlogin.trans_emp", seq, oicq).await?;
+        Ok(decode_fetch(rsp.body)?)
+    }
+
+    /// Asks the server once for the state of the qrcode identified by `sig`.
+    pub async fn query_qrcode(&mut self, sig: &[u8]) -> ClientResult<QrCodeState> {
+        let seq = self.base.next_seq();
+        let oicq = OicqPacket {
+            uin: 0,
+            command: 0x812,
+            body: query_request(&self.base, sig),
+        };
+
+        let rsp = self.wtlogin_raw("wtlogin.trans_emp", seq, oicq).await?;
+        Ok(decode_query(rsp.body)?)
+    }
+
+    /// Polls the qrcode state, awaiting `delay` between two queries.
+    ///
+    /// Only changes are yielded, and the stream ends after a state that
+    /// [finishes](QrCodeState::is_finished) the code or after an error.
+    pub fn watch_qrcode<'a, D, DFu>(
+        &'a mut self,
+        sig: Bytes,
+        delay: D,
+    ) -> impl Stream<Item = ClientResult<QrCodeState>> + 'a
+    where
+        D: FnMut() -> DFu + 'a,
+        DFu: Future<Output = ()> + 'a,
+    {
+        type Last = Option<Discriminant<QrCodeState>>;
+
+        futures::stream::unfold(Some((self, delay, None as Last)), move |state| {
+            let sig = sig.clone();
+            async move {
+                let (builder, mut delay, mut last) = state?;
+                loop {
+                    if last.is_some() {
+                        delay().await;
+                    }
+
+                    let state = match builder.query_qrcode(&sig).await {
+                        Ok(state) => state,
+                        Err(e) => return Some((Err(e), None)),
+                    };
+
+                    if state.is_finished() {
+                        return Some((Ok(state), None));
+                    }
+
+                    let kind = Some(discriminant(&state));
+                    if last != kind {
+                        return Some((Ok(state), Some((builder, delay, kind))));
+                    }
+                    last = kind;
+                }
+            }
+        })
+    }
+
+    /// Finishes a login confirmed through a qrcode.
+    pub async fn qrcode_login(&mut self, info: &QrCodeLoginInfo) -> ClientResult<LoginResponse> {
+        self.base.uin = info.uin;
+        {
+            let mut transport = self.base.transport.lock();
+            transport.uin = info.uin;
+            transport.tgtgt_key = info.tgtgt_key;
+        }
+        self.base.login_state.lock().password_md5 = [0; 16];
+
+        let seq = self.base.next_seq();
+        let body = qrcode_login(&self.base, seq as u32, info);
+        let rsp = self.wtlogin("wtlogin.login", 0x810, seq, body).await?;
+
+        login::decode_login_response(&self.base, rsp)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::client::qrcode::{
+        decode_fetch, decode_query, fetch_request, query_request, trans_emp, QrCodeState,
+    };
+    use crate::client::RequestClient;
+    use crate::data::oicq::OicqError;
+    use bytes::{BufMut, Bytes, BytesMut};
+
+    /// A reply as the server sends it: 5 unknown bytes, then a code2d packet.
+    fn reply(command: u16, body: &[u8]) -> Bytes {
+        let mut buf = BytesMut::new();
+        buf.put_bytes(0, 5);
+        buf.put_slice(&trans_emp(16, 0, command, body)[18..]);
+        buf.freeze()
+    }
+
+    fn state(code: u8) -> Result<QrCodeState, OicqError> {
+        let mut body = BytesMut::new();
+        body.put_u16(0);
+        body.put_u32(16);
+        body.put_u8(code);
+        decode_query(reply(0x12, &body))
+    }
+
+    #[test]
+    fn fetch() {
+        let mut body = BytesMut::new();
+        body.put_u16(0);
+        body.put_u32(0);
+        body.put_u8(0);
+        body.put_u16(3);
+        body.put_slice(b"sig");
+        body.put_u16(1);
+        body.put_slice(b"\0\x17\0\x03png");
+
+        let qrcode = decode_fetch(reply(0x31, &body)).unwrap();
+        assert_eq!(&qrcode.sig[..], b"sig");
+        assert_eq!(&qrcode.image[..], b"png");
+
+        body[6] = 1;
+        assert!(matches!(
+            decode_fetch(reply(0x31, &body)),
+            Err(OicqError::Status(1))
+        ));
+    }
+
+    #[test]
+    fn states() {
+        assert!(matches!(state(0x30), Ok(QrCodeState::WaitingForScan)));
+        assert!(matches!(state(0x35), Ok(QrCodeState::WaitingForConfirm)));
+        assert!(matches!(state(0x36), Ok(QrCodeState::Canceled)));
+        assert!(matches!(state(0x11), Ok(QrCodeState::Expired)));
+        assert!(matches!(state(0x99), Err(OicqError::Status(0x99))));
+        assert!(matches!(state(0), Err(OicqError::Malformed)));
+
+        let mut body = BytesMut::new();
+        body.put_u16(9);
+        body.put_u8(2);
+        body.put_u64(10001);
+        body.put_u32(16);
+        body.put_u8(0);
+        body.put_u64(10001);
+        body.put_u32(0);
+        body.put_u16(3);
+        body.put_slice(b"\0\x18\0\x03pwd\0\x19\0\x03sig\0\x1e\0\x10");
+        body.put_bytes(7, 16);
+
+        let Ok(QrCodeState::Confirmed(info)) = decode_query(reply(0x12, &body)) else {
+            panic!("not confirmed");
+        };
+        assert_eq!(info.uin, 10001);
+        assert_eq!(&info.tmp_pwd[..], b"pwd");
+        assert_eq!(&info.no_pic_sig[..], b"sig");
+        assert!(info.tgt_qr.is_empty());
+        assert_eq!(info.tgtgt_key, [7; 16]);
+    }
+
+    #[test]
+    fn header() {
+        let client = RequestClient::new();
+        let query = query_request(&client, b"qr-sig");
+        // the length counts the code2d body, 26 bytes around the sig
+        assert_eq!(&query[..7], &[0, 0, 26 + 6 + 53, 0, 0, 0, 16]);
+        assert_eq!(&query[7..14], &[0, 0, 0, 0x72, 0, 0, 0]);
+
+        let fetch = fetch_request(&client);
+        let len = u16::from_be_bytes([fetch[1], fetch[2]]) as usize;
+        assert_eq!(len, fetch.len() - 9);
+    }
+}
@@ -0,0 +1,162 @@
+use crate::client::Connection;
+use crate::executor::Timer;
+use crate::net::connector::ConnectorFactory;
+use crate::net::framed::FramedConnector;
+use crate::net::server::{self, ServerList};
+use crate::sync::Mutex;
+use futures::future::{BoxFuture, FutureExt};
+use std::io;
+use std::net::SocketAddr;
+use std::time::Duration;
+
+/// Delays between reconnect attempts, doubling from `initial` up to `max`.
+///
+/// Each delay is cut by a random jitter of up to half, so clients dropped together
+/// don't come back in lockstep.
+#[derive(Clone, Debug)]
+pub struct Backoff {
+    pub initial: Duration,
+    pub max: Duration,
+    /// Attempts before giving up, `None` to keep trying.
+    pub max_attempts: Option<u32>,
+}
+
+impl Backoff {
+    /// The delay before attempt `attempt`, counted from zero.
+    pub fn delay(&self, attempt: u32) -> Duration {
+        let factor = 2u32.saturating_pow(attempt);
+        let delay = self.initial.saturating_mul(factor).min(self.max);
+        delay.mul_f64(1.0 - rand::random::<f64>() / 2.0)
+    }
+}
+
+impl Default for Backoff {
+    fn default() -> Self {
+        Self {
+            initial: Duration::from_secs(1),
+            max: Duration::from_secs(60),
+            max_attempts: None,
+        }
+    }
+}
+
+type ConnectFn =
+    Box<dyn Fn(SocketAddr) -> BoxFuture<'static, io::Result<Connection>> + Send + Sync>;
+
+/// How a broken session reconnects, see
+/// [`ClientBuilder::with_reconnect`](crate::client::ClientBuilder::with_reconnect).
+#[derive(Default)]
+pub(super) struct Reconnect {
+    connect: Option<ConnectFn>,
+    pub(super) backoff: Backoff,
+}
+
+impl Reconnect {
+    pub(super) fn set_factory<R>(&mut self, factory: R)
+    where
+        R: ConnectorFactory + Send + Sync + 'static,
+        R::Connector: Send + 'static,
+        R::Future: Send + 'static,
+    {
+        self.connect = Some(Box::new(move |addr| {
+            factory
+                .connect(addr)
+                .map(|c| c.map(|c| Connection::new(FramedConnector::new(c))))
+                .boxed()
+        }));
+    }
+
+    #[inline]
+    pub(super) fn is_enabled(&self) -> bool {
+        self.connect.is_some()
+    }
+
+    /// Waits out the backoff of `attempt` and connects to the fastest of `servers`,
+    /// trying them all at once.
+    ///
+    /// Gives `None` once [`Backoff::max_attempts`] are used up.
+    pub(super) async fn connect<T: Timer>(
+        &self,
+        servers: &Mutex<ServerList>,
+        timer: &T,
+        attempt: &mut u32,
+    ) -> Option<(SocketAddr, Connection)> {
+        let connect = self.connect.as_ref()?;
+
+        while self.backoff.max_attempts.is_none_or(|max| *attempt < max) {
+            timer.sleep(self.backoff.delay(*attempt)).await;
+            *attempt += 1;
+
+            if let Some(connected) = server::connect_fastest(servers, connect, timer).await {
+                return Some(connected);
+            }
+        }
+
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::client::reconnect::{Backoff, Reconnect};
+    use crate::executor::timer::ThreadTimer;
+    use crate::net::server::ServerList;
+    use crate::sync::Mutex;
+    use futures::future;
+    use std::net::{TcpListener, TcpStream};
+    use std::time::Duration;
+
+    #[test]
+    fn backoff() {
+        let backoff = Backoff {
+            initial: Duration::from_millis(100),
+            max: Duration::from_secs(1),
+            max_attempts: None,
+        };
+
+        for (attempt, base) in [(0, 100), (1, 200), (3, 800), (4, 1000), (100, 1000)] {
+            let base = Duration::from_millis(base);
+            let delay = backoff.delay(attempt);
+            assert!(delay <= base && delay >= base / 2, "{:?}", delay);
+        }
+    }
+
+    #[test]
+    fn failover() {
+        let live = TcpListener::bind("127.0.0.1:0").unwrap();
+        let dead = TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap();
+        let live = live.local_addr().unwrap();
+        let servers = Mutex::new(ServerList::new([dead, live]));
+
+        let mut reconnect = Reconnect {
+            backoff: Backoff {
+                initial: Duration::from_millis(1),
+                max: Duration::from_millis(1),
+                max_attempts: Some(2),
+            },
+            ..Reconnect::default()
+        };
+        reconnect.set_factory(|addr| future::ready(TcpStream::connect(addr)));
+
+        futures::executor::block_on(async {
+            let mut attempt = 0;
+            let (server, _) = reconnect
+                .connect(&servers, &ThreadTimer, &mut attempt)
+                .await
+                .unwrap();
+            assert_eq!(server, live);
+            assert_eq!(attempt, 1);
+            assert_eq!(servers.lock().fastest(), Some(live));
+
+            servers.lock().update([dead]);
+            assert!(reconnect
+                .connect(&servers, &ThreadTimer, &mut attempt)
+                .await
+                .is_none());
+            assert_eq!(attempt, 2);
+        });
+    }
+}
@@ -0,0 +1,27 @@
+use crate::client::RequestClient;
+use crate::data::packet::{Encrypt, PacketDetail};
+use crate::error::ClientError;
+use bytes::Bytes;
+
+/// A request the server answers under the same seq, typed by its response.
+///
+/// Sent with [`Client::call`](crate::client::Client::call).
+pub trait Request {
+    type Response;
+
+    /// The sso command, e.g. `friendlist.getFriendGroupList`.
+    const COMMAND: &'static str;
+
+    /// How the body is encrypted, D2 key unless said otherwise.
+    fn encrypt(&self) -> Encrypt {
+        Encrypt::UseD2Key
+    }
+
+    fn packet_detail(&self) -> PacketDetail {
+        PacketDetail::Uin
+    }
+
+    fn encode(&self, client: &RequestClient) -> Bytes;
+
+    fn decode(&self, body: Bytes) -> Result<Self::Response, ClientError>;
+}
@@ -0,0 +1,192 @@
+use crate::client::{Request, RequestClient};
+use crate::data::packet::{Encrypt, PacketDetail};
+use crate::error::ClientError;
+use crate::jce::UniPacket;
+use bytes::Bytes;
+
+crate::jce_struct! {
+    #[derive(Debug, Default)]
+    struct SvcReqRegister {
+        uin: u64 = 0,
+        bid: i64 = 1,
+        conn_type: u8 = 2,
+        other: String = 3,
+        status: i32 = 4,
+        online_push: bool = 5,
+        is_online: bool = 6,
+        is_show_online: bool = 7,
+        kick_pc: bool = 8,
+        kick_weak: bool = 9,
+        timestamp: i64 = 10,
+        sdk_version: i64 = 11,
+        net_type: u8 = 12,
+        build_version: String = 13,
+        reg_type: bool = 14,
+        dev_param: Bytes = 15,
+        guid: Bytes = 16,
+        locale_id: i32 = 17,
+        silent_push: bool = 18,
+        dev_name: String = 19,
+        dev_type: String = 20,
+        os_version: String = 21,
+        open_push: bool = 22,
+        large_seq: i64 = 23,
+        vendor_name: String = 30,
+        vendor_os_name: String = 31,
+        b769: Bytes = 33,
+        set_status: bool = 34,
+    }
+}
+
+crate::jce_struct! {
+    #[derive(Debug, Default)]
+    struct SvcRespRegister {
+        uin: u64 = 0,
+        bid: i64 = 1,
+        reply_code: i64 = 2,
+        result: String = 3,
+    }
+}
+
+/// `StatSvc.register`, taking the logged in session online.
+pub(crate) struct Register;
+
+impl Request for Register {
+    type Response = ();
+
+    const COMMAND: &'static str = "StatSvc.register";
+
+    fn packet_detail(&self) -> PacketDetail {
+        PacketDetail::Login
+    }
+
+    fn encode(&self, client: &RequestClient) -> Bytes {
+        let device = &client.device;
+
+        let req = SvcReqRegister {
+            uin: client.uin(),
+            bid: 1 | 2 | 4,
+            status: 11, // online
+            sdk_version: device.version.sdk as i64,
+            net_type: 1, // wifi
+            guid: Bytes::copy_from_slice(&device.guid()),
+            locale_id: 2052,
+            dev_name: device.model.clone(),
+            dev_type: device.model.clone(),
+            os_version: device.version.release.clone(),
+            open_push: true,
+            large_seq: 1551,
+            vendor_name: device.brand.clone(),
+            vendor_os_name: device.os_type.as_str().into(),
+            b769: Bytes::from_static(&[
+                0x0A, 0x04, 0x08, 0x2E, 0x10, 0x00, 0x0A, 0x05, 0x08, 0x9B, 0x02, 0x10, 0x00,
+            ]),
+            ..SvcReqRegister::default()
+        };
+
+        let mut pkt = UniPacket::new("PushService", "SvcReqRegister");
+        pkt.put("SvcReqRegister", &req);
+        pkt.encode()
+    }
+
+    fn decode(&self, body: Bytes) -> Result<(), ClientError> {
+        let rsp: SvcRespRegister = UniPacket::decode(body)?.get("SvcRespRegister")?;
+
+        if rsp.reply_code != 0 || !rsp.result.is_empty() {
+            return Err(ClientError::Refused {
+                code: rsp.reply_code,
+                message: rsp.result,
+            });
+        }
+        Ok(())
+    }
+}
+
+/// `Heartbeat.Alive`, proving the connection is still there.
+pub(crate) struct Heartbeat;
+
+impl Request for Heartbeat {
+    type Response = ();
+
+    const COMMAND: &'static str = "Heartbeat.Alive";
+
+    fn encrypt(&self) -> Encrypt {
+        Encrypt::NoEncrypt
+    }
+
+    fn packet_detail(&self) -> PacketDetail {
+        PacketDetail::Login
+    }
+
+    fn encode(&self, _: &RequestClient) -> Bytes {
+        Bytes::new()
+    }
+
+    fn decode(&self, _: Bytes) -> Result<(), ClientError> {
+        Ok(())
+    }
+}
+
+/// `StatSvc.SimpleGet`, keeping the registration alive.
+pub(crate) struct SimpleGet;
+
+impl Request for SimpleGet {
+    type Response = ();
+
+    const COMMAND: &'static str = "StatSvc.SimpleGet";
+
+    fn encode(&self, _: &RequestClient) -> Bytes {
+        Bytes::new()
+    }
+
+    fn decode(&self, _: Bytes) -> Result<(), ClientError> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::client::stat::{Register, SvcReqRegister, SvcRespRegister};
+    use crate::client::{Request, RequestClient};
+    use crate::error::ClientError;
+    use crate::jce::UniPacket;
+    use bytes::Bytes;
+
+    fn response(code: i64, message: &str) -> Bytes {
+        let rsp = SvcRespRegister {
+            uin: 10003,
+            reply_code: code,
+            result: message.into(),
+            ..SvcRespRegister::default()
+        };
+        let mut pkt = UniPacket::new("PushService", "SvcRespRegister");
+        pkt.put("SvcRespRegister", &rsp);
+        pkt.encode()
+    }
+
+    #[test]
+    fn register() {
+        let mut client = RequestClient::new();
+        client.uin = 10003;
+
+        let body = Register.encode(&client);
+        let pkt = UniPacket::decode(body).unwrap();
+        assert_eq!(pkt.packet.servant, "PushService");
+        let req: SvcReqRegister = pkt.get("SvcReqRegister").unwrap();
+        assert_eq!(req.uin, 10003);
+        assert_eq!(req.bid, 7);
+        assert_eq!(req.status, 11);
+        assert_eq!(&req.guid[..], client.device.guid());
+        assert_eq!(req.dev_name, client.device.model);
+
+        assert!(Register.decode(response(0, "")).is_ok());
+        assert!(matches!(
+            Register.decode(response(-1, "reg failed")),
+            Err(ClientError::Refused { code: -1, ref message }) if message == "reg failed"
+        ));
+        assert!(matches!(
+            Register.decode(Bytes::new()),
+            Err(ClientError::Jce(_))
+        ));
+    }
+}
@@ -0,0 +1,302 @@
+//! Fetching what `MessageSvc.PushNotify` announces: the messages waiting on the server,
+//! and the friend and group requests some of them point at.
+
+use crate::client::login::now;
+use crate::client::push::{decode_c2c_message, friend_request, group_join_request};
+use crate::client::{ClientResult, Request, RequestClient, Requester, DEFAULT_TIMEOUT};
+use crate::error::ClientError;
+use crate::event::EventKind;
+use crate::proto::message::{
+    GetMessageRequest, GetMessageResponse, MessageHeadType, Msg, MsgItem, PbDeleteMsgReq,
+    SyncCookie, SyncFlag,
+};
+use crate::proto::structmsg::{FlagInfo, ReqSystemMsgNew, RspSystemMsgNew, StructMsg};
+use bytes::Bytes;
+use futures::channel::mpsc::UnboundedSender;
+use prost::Message;
+
+/// Where fetching left off.
+#[derive(Default)]
+pub(crate) struct SyncState {
+    /// Handed back by every `MessageSvc.PbGetMsg`, empty before the first.
+    cookie: Bytes,
+    /// The newest friend and group requests reported, as those waiting for an answer
+    /// are listed again and again.
+    friend_request_seq: i64,
+    group_request_seq: i64,
+}
+
+/// A fresh [`SyncCookie`], as sent along with friend messages and the first
+/// `MessageSvc.PbGetMsg`.
+pub(crate) fn sync_cookie(client: &RequestClient) -> Bytes {
+    let time = now() as i64;
+    let (const1, const2) = client.sync_cookie_consts;
+    let cookie = SyncCookie {
+        time,
+        ran1: rand::random::<u32>() as i64,
+        ran2: rand::random::<u32>() as i64,
+        const1: const1 as i64,
+        const2: const2 as i64,
+        const3: 0x1d,
+        last_sync_time: time,
+        ..Default::default()
+    };
+    cookie.encode_to_vec().into()
+}
+
+/// `MessageSvc.PbGetMsg`, the next batch of the messages waiting.
+pub(crate) struct GetMessages {
+    pub flag: SyncFlag,
+    /// Where the last batch left off, empty to start afresh.
+    pub cookie: Bytes,
+}
+
+pub(crate) struct Messages {
+    pub messages: Vec<Msg>,
+    pub cookie: Bytes,
+    /// Another batch waits.
+    pub more: bool,
+}
+
+impl Request for GetMessages {
+    type Response = Messages;
+
+    const COMMAND: &'static str = "MessageSvc.PbGetMsg";
+
+    fn encode(&self, client: &RequestClient) -> Bytes {
+        let sync_cookie = match self.cookie.is_empty() {
+            true => sync_cookie(client),
+            false => self.cookie.clone(),
+        };
+
+        let req = GetMessageRequest {
+            sync_flag: self.flag as i32,
+            sync_cookie,
+            latest_ramble_number: 20,
+            other_ramble_number: 3,
+            context_flag: 1,
+            msg_req_type: 1,
+            ..Default::default()
+        };
+        req.encode_to_vec().into()
+    }
+
+    fn decode(&self, body: Bytes) -> Result<Messages, ClientError> {
+        let rsp = GetMessageResponse::decode(body)?;
+        if rsp.result != 0 {
+            return Err(ClientError::Refused {
+                code: rsp.result as i64,
+                message: rsp.error_message,
+            });
+        }
+
+        Ok(Messages {
+            messages: rsp
+                .uin_pair_msgs
+                .into_iter()
+                .flat_map(|pair| pair.messages)
+                .collect(),
+            cookie: rsp.sync_cookie,
+            more: rsp.sync_flag != SyncFlag::Stop as i32,
+        })
+    }
+}
+
+/// `MessageSvc.PbDeleteMsg`, so that fetched messages are not fetched again by the
+/// next session.
+pub(crate) struct DeleteMessages(pub Vec<MsgItem>);
+
+impl Request for DeleteMessages {
+    type Response = ();
+
+    const COMMAND: &'static str = "MessageSvc.PbDeleteMsg";
+
+    fn encode(&self, _: &RequestClient) -> Bytes {
+        let req = PbDeleteMsgReq {
+            msg_items: self.0.clone(),
+        };
+        req.encode_to_vec().into()
+    }
+
+    fn decode(&self, _: Bytes) -> Result<(), ClientError> {
+        Ok(())
+    }
+}
+
+/// `ProfileService.Pb.ReqSystemMsgNew.Friend`, the latest friend requests.
+pub(crate) struct FriendRequests;
+
+impl Request for FriendRequests {
+    type Response = Vec<StructMsg>;
+
+    const COMMAND: &'static str = "ProfileService.Pb.ReqSystemMsgNew.Friend";
+
+    fn encode(&self, _: &RequestClient) -> Bytes {
+        let req = ReqSystemMsgNew {
+            msg_num: 20,
+            version: 1000,
+            checktype: 2,
+            flag: Some(FlagInfo {
+                frd_msg_discuss2_many_chat: 1,
+                frd_msg_get_busi_card: 1,
+                frd_msg_need_waiting_msg: 1,
+                frd_msg_uint32_need_all_unread_msg: 1,
+                grp_msg_mask_invite_auto_join: 1,
+                ..Default::default()
+            }),
+            friend_msg_type_flag: 1,
+            ..Default::default()
+        };
+        req.encode_to_vec().into()
+    }
+
+    fn decode(&self, body: Bytes) -> Result<Vec<StructMsg>, ClientError> {
+        Ok(decode_system_msgs(body)?.friendmsgs)
+    }
+}
+
+/// `ProfileService.Pb.ReqSystemMsgNew.Group`, the latest requests to join the groups
+/// this account manages.
+pub(crate) struct GroupRequests;
+
+impl Request for GroupRequests {
+    type Response = Vec<StructMsg>;
+
+    const COMMAND: &'static str = "ProfileService.Pb.ReqSystemMsgNew.Group";
+
+    fn encode(&self, _: &RequestClient) -> Bytes {
+        let req = ReqSystemMsgNew {
+            msg_num: 100,
+            version: 1000,
+            checktype: 3,
+            flag: Some(FlagInfo {
+                grp_msg_kick_admin: 1,
+                grp_msg_hidden_grp: 1,
+                grp_msg_wording_down: 1,
+                grp_msg_get_official_account: 1,
+                grp_msg_get_pay_in_group: 1,
+                frd_msg_discuss2_many_chat: 1,
+                grp_msg_not_allow_join_grp_invite_not_frd: 1,
+                frd_msg_need_waiting_msg: 1,
+                frd_msg_uint32_need_all_unread_msg: 1,
+                grp_msg_need_auto_admin_wording: 1,
+                grp_msg_get_transfer_group_msg_flag: 1,
+                grp_msg_get_quit_pay_group_msg_flag: 1,
+                grp_msg_support_invite_auto_join: 1,
+                grp_msg_mask_invite_auto_join: 1,
+                grp_msg_get_disbanded_by_admin: 1,
+                grp_msg_get_c2c_invite_join_group: 1,
+                ..Default::default()
+            }),
+            friend_msg_type_flag: 1,
+            ..Default::default()
+        };
+        req.encode_to_vec().into()
+    }
+
+    fn decode(&self, body: Bytes) -> Result<Vec<StructMsg>, ClientError> {
+        Ok(decode_system_msgs(body)?.groupmsgs)
+    }
+}
+
+fn decode_system_msgs(body: Bytes) -> Result<RspSystemMsgNew, ClientError> {
+    let rsp = RspSystemMsgNew::decode(body)?;
+    match rsp.head {
+        Some(ref head) if head.result != 0 => Err(ClientError::Refused {
+            code: head.result as i64,
+            message: head.msg_fail.clone(),
+        }),
+        _ => Ok(rsp),
+    }
+}
+
+/// Fetches every message waiting, batch by batch, sending those made into events to
+/// `events`. Then the requests some of them announced, unless reported before.
+///
+/// Gives up on the first request failing, with the events of the batches before it sent.
+pub(super) async fn sync(
+    requester: &Requester,
+    events: &UnboundedSender<EventKind>,
+) -> ClientResult<()> {
+    let base = &requester.base;
+    let (mut friend_requests, mut group_requests) = (false, false);
+
+    let mut flag = SyncFlag::Start;
+    loop {
+        let cookie = base.sync_state.lock().cookie.clone();
+        let batch = requester
+            .call_timeout(GetMessages { flag, cookie }, DEFAULT_TIMEOUT)
+            .await?;
+        if !batch.cookie.is_empty() {
+            base.sync_state.lock().cookie = batch.cookie;
+        }
+
+        let mut fetched = Vec::with_capacity(batch.messages.len());
+        for msg in batch.messages {
+            if let Some(head) = &msg.head {
+                match MessageHeadType::try_from(head.msg_type) {
+                    Ok(MessageHeadType::FriendRequest) => friend_requests = true,
+                    Ok(MessageHeadType::GroupRequest | MessageHeadType::GroupInvite) => {
+                        group_requests = true
+                    }
+                    _ => {}
+                }
+                fetched.push(MsgItem {
+                    from_uin: head.from_uin,
+                    to_uin: head.to_uin,
+                    msg_type: head.msg_type,
+                    msg_seq: head.msg_seq,
+                    msg_uid: head.msg_uid,
+                });
+            }
+
+            if let Some(event) = decode_c2c_message(msg) {
+                let _ = events.unbounded_send(event);
+            }
+        }
+
+        // an empty batch ends it too, whatever the server says
+        if fetched.is_empty() {
+            break;
+        }
+        requester
+            .call_timeout(DeleteMessages(fetched), DEFAULT_TIMEOUT)
+            .await?;
+        if !batch.more {
+            break;
+        }
+        flag = SyncFlag::Continue;
+    }
+
+    if friend_requests {
+        let msgs = requester
+            .call_timeout(FriendRequests, DEFAULT_TIMEOUT)
+            .await?;
+        let msgs = unseen(msgs, &mut base.sync_state.lock().friend_request_seq);
+        for event in msgs.into_iter().filter_map(friend_request) {
+            let _ = events.unbounded_send(event);
+        }
+    }
+
+    if group_requests {
+        let msgs = requester
+            .call_timeout(GroupRequests, DEFAULT_TIMEOUT)
+            .await?;
+        let msgs = unseen(msgs, &mut base.sync_state.lock().group_request_seq);
+        for event in msgs.into_iter().filter_map(group_join_request) {
+            let _ = events.unbounded_send(event);
+        }
+    }
+
+    Ok(())
+}
+
+/// The requests newer than `seen`, oldest first, moving `seen` up to the newest.
+fn unseen(msgs: Vec<StructMsg>, seen: &mut i64) -> Vec<StructMsg> {
+    let last = *seen;
+    *seen = msgs.iter().map(|msg| msg.msg_seq).fold(last, i64::max);
+
+    let mut msgs: Vec<_> = msgs.into_iter().filter(|msg| msg.msg_seq > last).collect();
+    msgs.sort_by_key(|msg| msg.msg_seq);
+    msgs
+}
@@ -0,0 +1,73 @@
+use crate::client::login::{now, save_t119, wtlogin_body};
+use crate::client::RequestClient;
+use crate::crypto::Transport;
+use crate::data::oicq::OicqResponse;
+use crate::data::tlv::*;
+use crate::error::ClientError;
+use bytes::Bytes;
+use digest::Digest;
+
+/// Tickets are exchanged once D2 gets this close to expiring, in seconds.
+pub(crate) const REFRESH_MARGIN: u64 = 3600;
+
+/// Whether `session` still holds a usable A2, the ticket exchange_emp is authorized by.
+pub(crate) fn is_resumable(session: &Transport) -> bool {
+    !session.st.a2.is_empty() && !session.a2_expires_within(now(), 0)
+}
+
+/// Whether D2 has to be exchanged before it expires.
+pub(crate) fn needs_refresh(session: &Transport) -> bool {
+    session.d2_expires_within(now(), REFRESH_MARGIN)
+}
+
+fn d2_key_md5(transport: &Transport) -> [u8; 16] {
+    let mut key = [0; 16];
+    key.copy_from_slice(&md5::Md5::digest(transport.st.d2_key));
+    key
+}
+
+/// `wtlogin.exchange_emp` sub command 11, trading A2 for fresh tickets.
+pub(crate) fn exchange_emp(client: &RequestClient, seq: u32) -> Bytes {
+    let protocol = &client.protocol;
+    let device = &client.device;
+    let guid = device.guid();
+    let transport = client.transport.lock();
+    let uin = transport.uin as u32;
+
+    wtlogin_body(
+        11,
+        [
+            tlv100(protocol),
+            tlv10a(&transport.st.a2),
+            tlv116(protocol),
+            tlv108(&transport.ksid),
+            tlv144(device, &guid, &d2_key_md5(&transport)),
+            tlv143(&transport.st.d2),
+            tlv142(protocol),
+            tlv154(seq),
+            tlv18(protocol, uin),
+            tlv141(device),
+            tlv8(2052),
+            tlv147(protocol),
+            tlv177(protocol),
+            tlv187(device),
+            tlv188(device),
+            tlv194(device),
+            tlv511(DOMAINS),
+            tlv202(device),
+        ],
+    )
+}
+
+/// Stores the exchanged tickets, or reports the old ones as expired.
+pub(crate) fn decode_exchange_emp(
+    client: &RequestClient,
+    rsp: OicqResponse,
+) -> Result<(), ClientError> {
+    if rsp.status != 0 {
+        return Err(ClientError::TokenExpired);
+    }
+
+    let key = d2_key_md5(&client.transport.lock());
+    save_t119(client, &rsp.tlvs, &key)
+}
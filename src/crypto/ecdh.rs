@@ -1,4 +1,9 @@
-pub struct Ecdh {}
+use crate::data::oicq::OicqEncrypt;
+use digest::Digest;
+use p256::elliptic_curve::sec1::ToEncodedPoint;
+use p256::{PublicKey, SecretKey};
+use std::error::Error;
+use std::fmt::{Debug, Display, Formatter};
 
 static PUBLIC_KEY: [u8; 65] = [
     4, 235, 202, 148, 215, 51, 227, 153, 178, 219, 150, 234, 205, 211, 246, 154, 139, 176, 247, 66,
@@ -6,3 +11,225 @@ static PUBLIC_KEY: [u8; 65] = [
     58, 121, 154, 220, 127, 118, 254, 178, 8, 218, 124, 101, 34, 205, 176, 113, 154, 48, 81, 128,
     204, 84, 168, 46,
 ];
+
+/// Versioned server public key, as published by the key rotation service.
+#[derive(Clone)]
+pub struct ServerPublicKey {
+    pub version: u16,
+    key: PublicKey,
+}
+
+impl ServerPublicKey {
+    pub fn new(version: u16, sec1: &[u8]) -> Result<Self, EcdhError> {
+        let key = PublicKey::from_sec1_bytes(sec1).map_err(|_| EcdhError::InvalidPublicKey)?;
+        Ok(Self { version, key })
+    }
+
+    /// Parses the hex encoded `PubKey` of a rotation response.
+    pub fn from_hex(version: u16, hex: &str) -> Result<Self, EcdhError> {
+        if hex.len() % 2 == 1 {
+            return Err(EcdhError::InvalidPublicKey);
+        }
+
+        let sec1 = (0..hex.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok())
+            .collect::<Option<Vec<u8>>>()
+            .ok_or(EcdhError::InvalidPublicKey)?;
+
+        Self::new(version, &sec1)
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        self.key.to_encoded_point(false).as_bytes().to_vec()
+    }
+}
+
+impl Default for ServerPublicKey {
+    fn default() -> Self {
+        Self::new(1, &PUBLIC_KEY).expect("builtin server public key is valid")
+    }
+}
+
+pub struct Ecdh {
+    secret: SecretKey,
+    public_key: Vec<u8>,
+    server: ServerPublicKey,
+    share_key: [u8; 16],
+}
+
+impl Ecdh {
+    #[inline]
+    pub fn new() -> Self {
+        Self::with_server_key(ServerPublicKey::default())
+    }
+
+    pub fn with_server_key(server: ServerPublicKey) -> Self {
+        loop {
+            if let Ok(ecdh) = Self::from_secret(&rand::random(), server.clone()) {
+                return ecdh;
+            }
+        }
+    }
+
+    pub fn from_secret(secret: &[u8; 32], server: ServerPublicKey) -> Result<Self, EcdhError> {
+        let secret = SecretKey::from_be_bytes(secret).map_err(|_| EcdhError::InvalidSecret)?;
+        let share_key = share_key(&secret, &server.key);
+        let public_key = secret
+            .public_key()
+            .to_encoded_point(false)
+            .as_bytes()
+            .to_vec();
+
+        Ok(Self {
+            secret,
+            public_key,
+            server,
+            share_key,
+        })
+    }
+
+    /// Switches to a rotated server key, keeping the client key pair.
+    pub fn rotate(&mut self, server: ServerPublicKey) {
+        self.share_key = share_key(&self.secret, &server.key);
+        self.server = server;
+    }
+
+    #[inline]
+    pub fn share_key(&self) -> &[u8; 16] {
+        &self.share_key
+    }
+
+    #[inline]
+    pub fn key_version(&self) -> u16 {
+        self.server.version
+    }
+
+    /// Uncompressed sec1 encoding of the client public key.
+    #[inline]
+    pub fn public_key(&self) -> &[u8] {
+        &self.public_key
+    }
+
+    pub fn compressed_public_key(&self) -> Vec<u8> {
+        self.secret
+            .public_key()
+            .to_encoded_point(true)
+            .as_bytes()
+            .to_vec()
+    }
+
+    pub fn oicq_encrypt<'a>(&'a self, random_key: &'a [u8; 16]) -> OicqEncrypt<'a> {
+        OicqEncrypt::Ecdh {
+            public_key: &self.public_key,
+            public_key_version: self.key_version(),
+            share_key: &self.share_key,
+            random_key,
+        }
+    }
+}
+
+impl Default for Ecdh {
+    #[inline]
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// `md5` of the first 16 bytes of the shared point's x coordinate.
+fn share_key(secret: &SecretKey, public: &PublicKey) -> [u8; 16] {
+    let shared = p256::ecdh::diffie_hellman(secret.to_nonzero_scalar(), public.as_affine());
+
+    let mut key = [0; 16];
+    key.copy_from_slice(&md5::Md5::digest(&shared.raw_secret_bytes()[..16]));
+    key
+}
+
+#[derive(Debug)]
+pub enum EcdhError {
+    InvalidPublicKey,
+    InvalidSecret,
+}
+
+impl Display for EcdhError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::InvalidPublicKey => f.write_str("Invalid ecdh public key"),
+            Self::InvalidSecret => f.write_str("Invalid ecdh secret"),
+        }
+    }
+}
+
+impl Error for EcdhError {}
+
+#[cfg(test)]
+mod tests {
+    use crate::crypto::ecdh::{Ecdh, ServerPublicKey, PUBLIC_KEY};
+
+    fn scalar(n: u8) -> [u8; 32] {
+        let mut s = [0; 32];
+        s[31] = n;
+        s
+    }
+
+    #[test]
+    fn generator() {
+        let ecdh = Ecdh::from_secret(&scalar(1), ServerPublicKey::default()).unwrap();
+
+        let g = "6b17d1f2e12c4247f8bce6e563a440f277037d812deb33a0f4a13945d898c296";
+        let public_key = ecdh.public_key().to_vec();
+        assert_eq!(public_key.len(), 65);
+        assert_eq!(public_key[0], 4);
+        assert_eq!(
+            ServerPublicKey::from_hex(1, &format!("03{}", g))
+                .unwrap()
+                .to_bytes(),
+            public_key
+        );
+        assert_eq!(ecdh.compressed_public_key()[0], 3);
+        assert_eq!(&ecdh.compressed_public_key()[1..], &public_key[1..33]);
+
+        // 1 * server = server, so the share key hashes the server's own x coordinate
+        assert_eq!(
+            ecdh.share_key(),
+            &[
+                0x57, 0xd6, 0xbe, 0x70, 0x05, 0x50, 0xa8, 0x82, 0x58, 0x66, 0x17, 0x56, 0xac, 0xf5,
+                0xc1, 0x4b
+            ]
+        );
+        assert_eq!(ecdh.key_version(), 1);
+    }
+
+    #[test]
+    fn exchange() {
+        let server = Ecdh::from_secret(&scalar(7), ServerPublicKey::default()).unwrap();
+        let server_key = ServerPublicKey::new(2, server.public_key()).unwrap();
+
+        let client = Ecdh::from_secret(&scalar(11), server_key).unwrap();
+        let client_key = ServerPublicKey::new(0, client.public_key()).unwrap();
+        let server = Ecdh::from_secret(&scalar(7), client_key).unwrap();
+
+        assert_eq!(client.share_key(), server.share_key());
+        assert_eq!(client.key_version(), 2);
+    }
+
+    #[test]
+    fn rotate() {
+        let mut ecdh = Ecdh::new();
+        let share_key = *ecdh.share_key();
+        let public_key = ecdh.public_key().to_vec();
+
+        let rotated = Ecdh::from_secret(&scalar(3), ServerPublicKey::default()).unwrap();
+        ecdh.rotate(ServerPublicKey::new(5, rotated.public_key()).unwrap());
+
+        assert_eq!(ecdh.key_version(), 5);
+        assert_ne!(ecdh.share_key(), &share_key);
+        assert_eq!(ecdh.public_key(), public_key);
+
+        ecdh.rotate(ServerPublicKey::new(1, &PUBLIC_KEY).unwrap());
+        assert_eq!(ecdh.share_key(), &share_key);
+
+        assert!(ServerPublicKey::from_hex(1, "04zz").is_err());
+        assert!(ServerPublicKey::new(1, &[4, 1, 2]).is_err());
+    }
+}
@@ -1,12 +1,310 @@
 pub mod ecdh;
 pub mod tea;
 
+use crate::data::packet::SsoContext;
+use crate::data::protocol::ProtocolInfo;
+use crate::data::tlv::{TlvError, TlvMap, TlvWriter};
+use bytes::{Buf, BufMut, Bytes, BytesMut};
+use std::error::Error;
+use std::fmt::{Debug, Display, Formatter};
+
+/// Keys and tickets of a logged-in session.
+#[derive(Clone, Default)]
 pub struct Transport {
-    s_key: [u8; 16],
-    s_gt_key_new_st: [u8; 16],
-    a1_key: [u8; 16],
+    pub uin: u64,
+    pub ksid: Bytes,
+    pub session_id: [u8; 4],
+    pub tgtgt_key: [u8; 16],
+    pub st: StInfomation,
+}
+
+/// Tickets issued by wtlogin, named after the tlv carrying them.
+#[derive(Clone, Default)]
+pub struct StInfomation {
+    /// Encrypted A1 (`t106`), used to log in again without a password.
+    pub a1: Bytes,
+    /// A2, also called tgt (`t10a`).
+    pub a2: Bytes,
+    /// `t10d`
+    pub a2_key: [u8; 16],
+    /// `t143`
+    pub d2: Bytes,
+    /// `t305`
+    pub d2_key: [u8; 16],
+    /// `t133`
+    pub wt_session_ticket: Bytes,
+    /// `t134`
+    pub wt_session_ticket_key: [u8; 16],
+    /// `t120`
+    pub s_key: Bytes,
+    /// `t10e`
+    pub user_st_key: Bytes,
+    /// `t103`
+    pub user_st_web_sig: Bytes,
+    /// `t322`
+    pub device_token: Bytes,
+    /// `t16a`
+    pub srm_token: Bytes,
+    /// Unix timestamps in seconds.
+    pub login_time: u64,
+    pub a2_expire_time: u64,
+    pub d2_expire_time: u64,
+    pub s_key_expire_time: u64,
+}
+
+const MAGIC: &[u8; 4] = b"ATRI";
+const VERSION: u16 = 1;
+
+const TAG_UIN: u16 = 0x1;
+const TAG_KSID: u16 = 0x2;
+const TAG_SESSION_ID: u16 = 0x3;
+const TAG_TGTGT_KEY: u16 = 0x4;
+const TAG_LOGIN_TIME: u16 = 0x1001;
+const TAG_A2_EXPIRE_TIME: u16 = 0x1002;
+const TAG_D2_EXPIRE_TIME: u16 = 0x1003;
+const TAG_S_KEY_EXPIRE_TIME: u16 = 0x1004;
+
+impl Transport {
+    pub fn new(uin: u64) -> Self {
+        Self {
+            uin,
+            session_id: rand::random(),
+            tgtgt_key: rand::random(),
+            ..Default::default()
+        }
+    }
+
+    pub fn sso_context<'a>(&'a self, protocol: &'a ProtocolInfo, imei: &'a str) -> SsoContext<'a> {
+        SsoContext {
+            protocol,
+            imei,
+            ksid: &self.ksid,
+            session_id: &self.session_id,
+            tgt: &self.st.a2,
+            d2: &self.st.d2,
+            d2_key: &self.st.d2_key,
+        }
+    }
+
+    #[inline]
+    pub fn is_logged_in(&self) -> bool {
+        !self.st.d2.is_empty()
+    }
+
+    /// Whether D2 expires within `margin` seconds of `now`.
+    pub fn d2_expires_within(&self, now: u64, margin: u64) -> bool {
+        self.st.d2_expire_time <= now.saturating_add(margin)
+    }
+
+    /// Whether A2 expires within `margin` seconds of `now`.
+    pub fn a2_expires_within(&self, now: u64, margin: u64) -> bool {
+        self.st.a2_expire_time <= now.saturating_add(margin)
+    }
+
+    /// Serializes the session as `"ATRI" | u16 version | u16 count | tlv*`.
+    pub fn to_bytes(&self) -> Bytes {
+        fn tlv(buf: &mut BytesMut, tag: u16, value: &[u8]) {
+            let mut w = TlvWriter::with_capacity(tag, value.len());
+            w.put_slice(value);
+            buf.put_slice(&w.into_bytes());
+        }
+
+        let st = &self.st;
+        let fields: [(u16, &[u8]); 20] = [
+            (TAG_UIN, &self.uin.to_be_bytes()),
+            (TAG_KSID, &self.ksid),
+            (TAG_SESSION_ID, &self.session_id),
+            (TAG_TGTGT_KEY, &self.tgtgt_key),
+            (0x106, &st.a1),
+            (0x10a, &st.a2),
+            (0x10d, &st.a2_key),
+            (0x143, &st.d2),
+            (0x305, &st.d2_key),
+            (0x133, &st.wt_session_ticket),
+            (0x134, &st.wt_session_ticket_key),
+            (0x120, &st.s_key),
+            (0x10e, &st.user_st_key),
+            (0x103, &st.user_st_web_sig),
+            (0x322, &st.device_token),
+            (0x16a, &st.srm_token),
+            (TAG_LOGIN_TIME, &st.login_time.to_be_bytes()),
+            (TAG_A2_EXPIRE_TIME, &st.a2_expire_time.to_be_bytes()),
+            (TAG_D2_EXPIRE_TIME, &st.d2_expire_time.to_be_bytes()),
+            (TAG_S_KEY_EXPIRE_TIME, &st.s_key_expire_time.to_be_bytes()),
+        ];
+
+        let mut buf = BytesMut::new();
+        buf.put_slice(MAGIC);
+        buf.put_u16(VERSION);
+        buf.put_u16(fields.len() as u16);
+        for (tag, value) in fields {
+            tlv(&mut buf, tag, value);
+        }
+
+        buf.freeze()
+    }
+
+    /// Restores a session written by [`Transport::to_bytes`].
+    /// Unknown tags are ignored so newer fields can be added without a version bump.
+    pub fn from_bytes(mut buf: Bytes) -> Result<Self, SessionError> {
+        if buf.len() < 6 || &buf[..4] != MAGIC {
+            return Err(SessionError::InvalidMagic);
+        }
+        buf.advance(4);
+
+        let version = buf.get_u16();
+        if version != VERSION {
+            return Err(SessionError::UnsupportedVersion(version));
+        }
+
+        let tlvs = TlvMap::decode_counted(buf).map_err(SessionError::Tlv)?;
+
+        fn bytes(tlvs: &TlvMap, tag: u16) -> Bytes {
+            tlvs.get(tag).cloned().unwrap_or_default()
+        }
+
+        fn array<const N: usize>(tlvs: &TlvMap, tag: u16) -> Result<[u8; N], SessionError> {
+            match tlvs.get(tag) {
+                Some(b) => b[..]
+                    .try_into()
+                    .map_err(|_| SessionError::InvalidField(tag)),
+                None => Ok([0; N]),
+            }
+        }
+
+        fn u64(tlvs: &TlvMap, tag: u16) -> Result<u64, SessionError> {
+            array(tlvs, tag).map(u64::from_be_bytes)
+        }
+
+        Ok(Self {
+            uin: u64(&tlvs, TAG_UIN)?,
+            ksid: bytes(&tlvs, TAG_KSID),
+            session_id: array(&tlvs, TAG_SESSION_ID)?,
+            tgtgt_key: array(&tlvs, TAG_TGTGT_KEY)?,
+            st: StInfomation {
+                a1: bytes(&tlvs, 0x106),
+                a2: bytes(&tlvs, 0x10a),
+                a2_key: array(&tlvs, 0x10d)?,
+                d2: bytes(&tlvs, 0x143),
+                d2_key: array(&tlvs, 0x305)?,
+                wt_session_ticket: bytes(&tlvs, 0x133),
+                wt_session_ticket_key: array(&tlvs, 0x134)?,
+                s_key: bytes(&tlvs, 0x120),
+                user_st_key: bytes(&tlvs, 0x10e),
+                user_st_web_sig: bytes(&tlvs, 0x103),
+                device_token: bytes(&tlvs, 0x322),
+                srm_token: bytes(&tlvs, 0x16a),
+                login_time: u64(&tlvs, TAG_LOGIN_TIME)?,
+                a2_expire_time: u64(&tlvs, TAG_A2_EXPIRE_TIME)?,
+                d2_expire_time: u64(&tlvs, TAG_D2_EXPIRE_TIME)?,
+                s_key_expire_time: u64(&tlvs, TAG_S_KEY_EXPIRE_TIME)?,
+            },
+        })
+    }
 }
 
-impl Transport {}
+#[derive(Debug)]
+pub enum SessionError {
+    InvalidMagic,
+    UnsupportedVersion(u16),
+    InvalidField(u16),
+    Tlv(TlvError),
+}
+
+impl Display for SessionError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::InvalidMagic => f.write_str("Not a session file"),
+            Self::UnsupportedVersion(v) => write!(f, "Unsupported session version: {}", v),
+            Self::InvalidField(tag) => write!(f, "Invalid session field {:#x}", tag),
+            Self::Tlv(e) => write!(f, "Session tlv error: {}", e),
+        }
+    }
+}
+
+impl Error for SessionError {}
+
+#[cfg(test)]
+mod tests {
+    use crate::crypto::{SessionError, StInfomation, Transport};
+    use crate::data::protocol::ProtocolInfo;
+    use bytes::{Bytes, BytesMut};
 
-pub struct StInfomation {}
+    fn session() -> Transport {
+        Transport {
+            uin: 10001,
+            ksid: Bytes::from_static(b"ksid"),
+            session_id: [1, 2, 3, 4],
+            tgtgt_key: [5; 16],
+            st: StInfomation {
+                a1: Bytes::from_static(b"a1"),
+                a2: Bytes::from_static(b"a2"),
+                a2_key: [6; 16],
+                d2: Bytes::from_static(b"d2"),
+                d2_key: [7; 16],
+                wt_session_ticket: Bytes::from_static(b"st"),
+                wt_session_ticket_key: [8; 16],
+                s_key: Bytes::from_static(b"skey"),
+                login_time: 1600000000,
+                a2_expire_time: 1602592000,
+                d2_expire_time: 1600086400,
+                ..Default::default()
+            },
+        }
+    }
+
+    #[test]
+    fn round_trip() {
+        let bytes = session().to_bytes();
+        assert_eq!(&bytes[..6], b"ATRI\0\x01");
+
+        let restored = Transport::from_bytes(bytes).unwrap();
+        assert_eq!(restored.to_bytes(), session().to_bytes());
+        assert_eq!(restored.uin, 10001);
+        assert_eq!(restored.session_id, [1, 2, 3, 4]);
+        assert_eq!(restored.st.d2_key, [7; 16]);
+        assert_eq!(&restored.st.a2[..], b"a2");
+        assert!(restored.st.device_token.is_empty());
+        assert_eq!(restored.st.d2_expire_time, 1600086400);
+
+        assert!(restored.is_logged_in());
+        assert!(!restored.d2_expires_within(1600000000, 3600));
+        assert!(restored.d2_expires_within(1600083000, 3600));
+        assert!(!restored.a2_expires_within(1600083000, 3600));
+
+        let protocol = ProtocolInfo::ANDROID_WATCH;
+        let ctx = restored.sso_context(&protocol, "imei");
+        assert_eq!(ctx.d2, b"d2");
+        assert_eq!(ctx.tgt, b"a2");
+        assert_eq!(ctx.ksid, b"ksid");
+    }
+
+    #[test]
+    fn invalid() {
+        assert!(matches!(
+            Transport::from_bytes(Bytes::from_static(b"ATRX\0\x01\0\0")),
+            Err(SessionError::InvalidMagic)
+        ));
+        assert!(matches!(
+            Transport::from_bytes(Bytes::from_static(b"ATRI\0\x02\0\0")),
+            Err(SessionError::UnsupportedVersion(2))
+        ));
+
+        let mut bytes = BytesMut::from(&session().to_bytes()[..]);
+        bytes.truncate(bytes.len() - 3);
+        assert!(matches!(
+            Transport::from_bytes(bytes.freeze()),
+            Err(SessionError::Tlv(_))
+        ));
+
+        // d2 key of the wrong size
+        let bytes = Bytes::from_static(b"ATRI\0\x01\0\x01\x03\x05\0\x02ab");
+        assert!(matches!(
+            Transport::from_bytes(bytes),
+            Err(SessionError::InvalidField(0x305))
+        ));
+
+        let empty = Transport::from_bytes(Bytes::from_static(b"ATRI\0\x01\0\0")).unwrap();
+        assert!(!empty.is_logged_in());
+    }
+}
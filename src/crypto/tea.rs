@@ -21,6 +21,17 @@ impl Tea {
         Self { key }
     }
 
+    pub fn from_bytes(key: &[u8; 16]) -> Self {
+        let mut k = [0; 4];
+        for (k, chunk) in k.iter_mut().zip(key.chunks_exact(4)) {
+            let mut buf = [0; 4];
+            buf.copy_from_slice(chunk);
+            *k = u32::from_be_bytes(buf);
+        }
+
+        Self::from_key(k)
+    }
+
     pub fn encrypt(&self, data: &[u8]) -> Bytes {
         let n = 6usize.wrapping_sub(data.len());
         let n = (n % 8) + 2;
@@ -67,7 +78,7 @@ impl Tea {
 
     pub fn decrypt(&self, encrypted: &[u8]) -> Result<Bytes, DecryptError> {
         let len = encrypted.len();
-        if len % 8 != 0 {
+        if len < 16 || len & 7 != 0 {
             return Err(DecryptError);
         }
 
@@ -76,8 +87,6 @@ impl Tea {
         let k2 = self.key[2];
         let k3 = self.key[3];
 
-        let mut r1 = 0;
-        let mut r2 = 0;
         let mut t1 = 0;
         let mut t2 = 0;
 
@@ -97,8 +106,8 @@ impl Tea {
 
             (x, y) = _decrypt(b1, b2, k0, k1, k2, k3);
 
-            r1 = x ^ t1;
-            r2 = y ^ t2;
+            let r1 = x ^ t1;
+            let r2 = y ^ t2;
             t1 = a1;
             t2 = a2;
 
@@ -108,7 +117,8 @@ impl Tea {
 
         let start = (dec[0] as usize & 0x07) + 3;
 
-        if dec.len() < 7 + start {
+        // the padding ends in 7 zero bytes, anything else was encrypted with another key
+        if dec.len() < 7 + start || dec[len - 7..].iter().any(|&b| b != 0) {
             return Err(DecryptError);
         }
 
@@ -183,5 +193,20 @@ mod tests {
 
         assert!(d.is_ok());
         assert_eq!(Ok(TEST_TEXT), std::str::from_utf8(&d.unwrap()));
+
+        assert!(tea.decrypt(&[]).is_err());
+        assert!(tea.decrypt(&b[..8]).is_err());
+    }
+
+    #[test]
+    fn wrong_key() {
+        let b = Tea { key: TEST_KEY }.encrypt(TEST_TEXT.as_bytes());
+        assert!(Tea { key: [1, 2, 3, 4] }.decrypt(&b).is_err());
+
+        // right key, but the last block no longer ends in zeros
+        let mut tampered = b.to_vec();
+        let last = tampered.len() - 1;
+        tampered[last] ^= 1;
+        assert!(Tea { key: TEST_KEY }.decrypt(&tampered).is_err());
     }
 }
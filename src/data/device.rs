@@ -1,30 +1,27 @@
-use crate::data::protocol::ProtocolInfo;
-use crate::data::tlv::tlv16;
-use bytes::{BufMut, BytesMut};
 use digest::Digest;
 
 pub struct DeviceInfo {
-    display: String,
-    product: String,
-    device: String,
-    board: String,
-    brand: String,
-    model: String,
-    bootloader: String,
-    boot_id: String,
-    proc_version: String,
-    base_band: String,
-    sim: String,
-    os_type: OSType,
-    mac_address: String,
-    ip_address: [u8; 4],
-    wifi_bssid: String,
-    wifi_ssid: String,
-    imei: String,
-    android_id: String,
-    apn: Apn,
-    version: DeviceVersion,
-    imsi: [u8; 16],
+    pub display: String,
+    pub product: String,
+    pub device: String,
+    pub board: String,
+    pub brand: String,
+    pub model: String,
+    pub bootloader: String,
+    pub boot_id: String,
+    pub proc_version: String,
+    pub base_band: String,
+    pub sim: String,
+    pub os_type: OSType,
+    pub mac_address: String,
+    pub ip_address: [u8; 4],
+    pub wifi_bssid: String,
+    pub wifi_ssid: String,
+    pub imei: String,
+    pub android_id: String,
+    pub apn: Apn,
+    pub version: DeviceVersion,
+    pub imsi: [u8; 16],
 }
 
 pub enum OSType {
@@ -32,18 +29,104 @@ pub enum OSType {
     Unknown,
 }
 
+impl OSType {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Android => "android",
+            Self::Unknown => "unknown",
+        }
+    }
+}
+
 pub enum Apn {
     WiFi,
 }
 
+impl Apn {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::WiFi => "wifi",
+        }
+    }
+
+    pub fn network_type(&self) -> u16 {
+        match self {
+            Self::WiFi => 2,
+        }
+    }
+}
+
 pub struct DeviceVersion {
-    incremental: u32,
-    release: String,
-    codename: String,
-    sdk: u8,
+    pub incremental: u32,
+    pub release: String,
+    pub codename: String,
+    pub sdk: u8,
 }
 
 impl DeviceInfo {
+    pub fn random() -> Self {
+        fn digits(n: usize) -> String {
+            (0..n)
+                .map(|_| char::from(b'0' + rand::random_range(0..10u8)))
+                .collect()
+        }
+
+        fn hex(bytes: &[u8]) -> String {
+            bytes.iter().map(|b| format!("{:02x}", b)).collect()
+        }
+
+        fn mac() -> String {
+            let mac: [u8; 6] = rand::random();
+            mac.iter()
+                .map(|b| format!("{:02X}", b))
+                .collect::<Vec<_>>()
+                .join(":")
+        }
+
+        let boot_id: [u8; 16] = rand::random();
+        let boot_id = hex(&boot_id);
+        let mac_address = mac();
+
+        Self {
+            display: format!("ATRI.{}.001", digits(6)),
+            product: "atri".into(),
+            device: "atri".into(),
+            board: "atri".into(),
+            brand: "Xiaomi".into(),
+            model: "MI 6".into(),
+            bootloader: "unknown".into(),
+            boot_id: format!(
+                "{}-{}-{}-{}-{}",
+                &boot_id[..8],
+                &boot_id[8..12],
+                &boot_id[12..16],
+                &boot_id[16..20],
+                &boot_id[20..]
+            ),
+            proc_version: format!(
+                "Linux version 3.0.31-{} (android-build@xxx.xxx.xxx.xxx.com)",
+                hex(&rand::random::<[u8; 4]>())
+            ),
+            base_band: "".into(),
+            sim: "T-Mobile".into(),
+            os_type: OSType::Android,
+            wifi_bssid: mac_address.clone(),
+            mac_address,
+            ip_address: [10, 0, 1, rand::random_range(2..255)],
+            wifi_ssid: "AndroidAP".into(),
+            imei: format!("86{}", digits(13)),
+            android_id: hex(&rand::random::<[u8; 8]>()),
+            apn: Apn::WiFi,
+            version: DeviceVersion {
+                incremental: rand::random_range(1000000..10000000),
+                release: "10".into(),
+                codename: "REL".into(),
+                sdk: 29,
+            },
+            imsi: rand::random(),
+        }
+    }
+
     pub fn fingerprint(&self) -> String {
         format!(
             "{}/{}/{}:10/{}/{}:user/release-keys",
@@ -62,9 +145,9 @@ impl DeviceInfo {
     }
 }
 
-#[test]
-fn a() {
-    let device = DeviceInfo {
+#[cfg(test)]
+pub(crate) fn sample() -> DeviceInfo {
+    DeviceInfo {
         display: "RICQ.448911.001".into(),
         product: "iarim".into(),
         device: "sagit".into(),
@@ -93,19 +176,39 @@ fn a() {
         ],
         android_id: "d7fc70a09f4cc4f5".into(),
         apn: Apn::WiFi,
-    };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::data::device::sample;
+    use crate::data::protocol::ProtocolInfo;
+    use crate::data::tlv::tlv16;
+    use bytes::{BufMut, BytesMut};
 
-    let protocol = ProtocolInfo::ANDROID_WATCH;
-    let guid = device.guid();
+    #[test]
+    fn a() {
+        let device = sample();
 
-    let mut b = BytesMut::new();
-    b.put_u16(0);
-    b.put_u32(16);
-    b.put_u64(0);
-    b.put_u8(8);
-    b.put_u16(0);
-    b.put_u16(6);
-    b.put_slice(&tlv16(&protocol, &guid));
+        let protocol = ProtocolInfo::ANDROID_WATCH;
+        let guid = device.guid();
 
-    println!("{:?}", &*b);
+        let mut b = BytesMut::new();
+        b.put_u16(0);
+        b.put_u32(16);
+        b.put_u64(0);
+        b.put_u8(8);
+        b.put_u16(0);
+        b.put_u16(6);
+        b.put_slice(&tlv16(&protocol, &guid));
+
+        assert_eq!(
+            &*b,
+            b"\x00\x00\x00\x00\x00\x10\x00\x00\x00\x00\x00\x00\x00\x00\x08\x00\x00\x00\x06\
+              \x00\x16\x00\x49\x00\x00\x00\x05\x00\x00\x00\x10\x20\x02\xf3\xfe\
+              \x64\xa2\x67\xcc\xf6\xab\xbb\xb9\x28\x44\x9f\xf5\x6c\x44\x58\x21\
+              \x00\x12com.tencent.qqlite\x00\x052.0.5\
+              \x00\x10\xa6\xb7\x45\xbf\x24\xa2\xc2\x77\x52\x77\x16\xf6\xf3\x6e\xb6\x8d"
+        );
+    }
 }
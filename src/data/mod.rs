@@ -1,4 +1,5 @@
 pub mod device;
+pub mod oicq;
 pub mod packet;
 pub mod protocol;
 pub mod tlv;
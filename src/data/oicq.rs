@@ -0,0 +1,334 @@
+use crate::crypto::tea::{DecryptError, Tea};
+use crate::data::tlv::{TlvError, TlvMap};
+use bytes::{Buf, BufMut, Bytes, BytesMut};
+use std::error::Error;
+use std::fmt::{Debug, Display, Formatter};
+
+/// `wtlogin.*` request: `0x02 | len | 8001 | cmd | 1 | uin | ... | body | 0x03`.
+pub struct OicqPacket {
+    pub uin: u32,
+    pub command: u16,
+    pub body: Bytes,
+}
+
+pub enum OicqEncrypt<'a> {
+    /// `0x87`, body encrypted with the ecdh share key.
+    Ecdh {
+        public_key: &'a [u8],
+        public_key_version: u16,
+        share_key: &'a [u8; 16],
+        random_key: &'a [u8; 16],
+    },
+    /// `0x45`, body encrypted with the wt session ticket key.
+    SessionTicket { ticket: &'a [u8], key: &'a [u8; 16] },
+}
+
+impl OicqEncrypt<'_> {
+    fn id(&self) -> u8 {
+        match self {
+            Self::Ecdh { .. } => 0x87,
+            Self::SessionTicket { .. } => 0x45,
+        }
+    }
+
+    fn encrypt(&self, body: &[u8]) -> Bytes {
+        let mut buf = BytesMut::new();
+
+        match self {
+            Self::Ecdh {
+                public_key,
+                public_key_version,
+                share_key,
+                random_key,
+            } => {
+                buf.put_u8(0x02);
+                buf.put_u8(0x01);
+                buf.put_slice(*random_key);
+                buf.put_u16(0x0131);
+                buf.put_u16(*public_key_version);
+                buf.put_u16(public_key.len() as u16);
+                buf.put_slice(public_key);
+                buf.put_slice(&Tea::from_bytes(share_key).encrypt(body));
+            }
+            Self::SessionTicket { ticket, key } => {
+                buf.put_u16(ticket.len() as u16);
+                buf.put_slice(ticket);
+                buf.put_slice(&Tea::from_bytes(key).encrypt(body));
+            }
+        }
+
+        buf.freeze()
+    }
+
+    fn decrypt(&self, encrypt_type: u8, body: &[u8]) -> Result<Bytes, OicqError> {
+        match (encrypt_type, self) {
+            (
+                0,
+                Self::Ecdh {
+                    share_key,
+                    random_key,
+                    ..
+                },
+            ) => Tea::from_bytes(share_key)
+                .decrypt(body)
+                .or_else(|_| Tea::from_bytes(random_key).decrypt(body))
+                .map_err(OicqError::Decrypt),
+            (3, Self::SessionTicket { key, .. }) => Tea::from_bytes(key)
+                .decrypt(body)
+                .map_err(OicqError::Decrypt),
+            (t, _) => Err(OicqError::UnknownEncryptType(t)),
+        }
+    }
+}
+
+/// Decoded `wtlogin.*` response body.
+pub struct OicqResponse {
+    pub uin: u32,
+    pub command: u16,
+    pub sub_command: u16,
+    pub status: u8,
+    pub tlvs: TlvMap,
+}
+
+const HEADER_LEN: usize = 28;
+
+impl OicqPacket {
+    pub fn encode(&self, encrypt: &OicqEncrypt) -> Bytes {
+        let body = encrypt.encrypt(&self.body);
+
+        let mut buf = BytesMut::with_capacity(HEADER_LEN + body.len() + 1);
+        buf.put_u8(0x02);
+        buf.put_u16((HEADER_LEN + body.len() + 1) as u16);
+        buf.put_u16(8001);
+        buf.put_u16(self.command);
+        buf.put_u16(1);
+        buf.put_u32(self.uin);
+        buf.put_u8(3);
+        buf.put_u8(encrypt.id());
+        buf.put_u8(0);
+        buf.put_u32(2);
+        buf.put_u32(0);
+        buf.put_u32(0);
+        buf.put_slice(&body);
+        buf.put_u8(0x03);
+
+        buf.freeze()
+    }
+
+    /// Decodes and decrypts a reply, leaving its body as is.
+    pub fn decode_raw(mut frame: Bytes, encrypt: &OicqEncrypt) -> Result<Self, OicqError> {
+        // flag, len, ver, cmd, 1, uin, 0, encrypt type, 0, .., 0x03
+        if frame.len() < 17 {
+            return Err(OicqError::Malformed);
+        }
+
+        if frame.get_u8() != 0x02 || frame.last() != Some(&0x03) {
+            return Err(OicqError::Malformed);
+        }
+
+        let len = frame.get_u16() as usize;
+        if len != frame.len() + 3 {
+            return Err(OicqError::Malformed);
+        }
+
+        frame.advance(2); // 8001
+        let command = frame.get_u16();
+        frame.advance(2); // 1
+        let uin = frame.get_u32();
+        frame.advance(1);
+        let encrypt_type = frame.get_u8();
+        frame.advance(1);
+
+        let body = encrypt.decrypt(encrypt_type, &frame[..frame.len() - 1])?;
+
+        Ok(Self { uin, command, body })
+    }
+
+    /// Decodes a reply whose body is `u16 sub_command | u8 status | u16 count | tlv*`.
+    pub fn decode(frame: Bytes, encrypt: &OicqEncrypt) -> Result<OicqResponse, OicqError> {
+        Self::decode_raw(frame, encrypt).and_then(OicqResponse::try_from)
+    }
+}
+
+impl TryFrom<OicqPacket> for OicqResponse {
+    type Error = OicqError;
+
+    fn try_from(pkt: OicqPacket) -> Result<Self, OicqError> {
+        let OicqPacket {
+            uin,
+            command,
+            mut body,
+        } = pkt;
+
+        if body.len() < 5 {
+            return Err(OicqError::Malformed);
+        }
+
+        let sub_command = body.get_u16();
+        let status = body.get_u8();
+        body.advance(2); // tlv count
+        let tlvs = TlvMap::decode(body).map_err(OicqError::Tlv)?;
+
+        Ok(Self {
+            uin,
+            command,
+            sub_command,
+            status,
+            tlvs,
+        })
+    }
+}
+
+#[derive(Debug)]
+pub enum OicqError {
+    Malformed,
+    UnknownEncryptType(u8),
+    /// The server refused the request with a status this client doesn't know.
+    Status(u8),
+    Decrypt(DecryptError),
+    Tlv(TlvError),
+}
+
+impl Display for OicqError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Malformed => f.write_str("Malformed oicq packet"),
+            Self::UnknownEncryptType(t) => write!(f, "Unknown oicq encrypt type: {}", t),
+            Self::Status(s) => write!(f, "Unexpected oicq status: {}", s),
+            Self::Decrypt(e) => write!(f, "Oicq decrypt error: {}", e),
+            Self::Tlv(e) => write!(f, "Oicq tlv error: {}", e),
+        }
+    }
+}
+
+impl Error for OicqError {}
+
+#[cfg(test)]
+mod tests {
+    use crate::crypto::tea::Tea;
+    use crate::data::oicq::{OicqEncrypt, OicqError, OicqPacket, HEADER_LEN};
+    use crate::data::tlv::{tlv104, tlv8};
+    use bytes::{Buf, BufMut, Bytes, BytesMut};
+
+    const SHARE_KEY: [u8; 16] = [1; 16];
+    const RANDOM_KEY: [u8; 16] = [2; 16];
+    const PUBLIC_KEY: [u8; 4] = [4, 3, 2, 1];
+
+    fn ecdh() -> OicqEncrypt<'static> {
+        OicqEncrypt::Ecdh {
+            public_key: &PUBLIC_KEY,
+            public_key_version: 1,
+            share_key: &SHARE_KEY,
+            random_key: &RANDOM_KEY,
+        }
+    }
+
+    fn response(encrypt_type: u8, key: &[u8; 16], body: &[u8]) -> Bytes {
+        let body = Tea::from_bytes(key).encrypt(body);
+
+        let mut buf = BytesMut::new();
+        buf.put_u8(0x02);
+        buf.put_u16((body.len() + 17) as u16);
+        buf.put_u16(8001);
+        buf.put_u16(0x810);
+        buf.put_u16(1);
+        buf.put_u32(10001);
+        buf.put_u8(0);
+        buf.put_u8(encrypt_type);
+        buf.put_u8(0);
+        buf.put_slice(&body);
+        buf.put_u8(0x03);
+        buf.freeze()
+    }
+
+    #[test]
+    fn encode() {
+        let pkt = OicqPacket {
+            uin: 10001,
+            command: 0x810,
+            body: Bytes::from_static(b"body"),
+        };
+
+        let mut frame = pkt.encode(&ecdh());
+        assert_eq!(frame.len(), frame.slice(1..3).get_u16() as usize);
+        assert_eq!(frame.last(), Some(&0x03));
+        assert_eq!(
+            &frame[..16],
+            &[
+                2,
+                0,
+                frame.len() as u8,
+                0x1f,
+                0x41,
+                8,
+                16,
+                0,
+                1,
+                0,
+                0,
+                39,
+                17,
+                3,
+                0x87,
+                0
+            ]
+        );
+
+        frame.advance(HEADER_LEN);
+        assert_eq!(&frame[..2], &[2, 1]);
+        assert_eq!(&frame[2..18], &RANDOM_KEY);
+        assert_eq!(&frame[18..24], &[1, 0x31, 0, 1, 0, 4]);
+        assert_eq!(&frame[24..28], &PUBLIC_KEY);
+        let body = Tea::from_bytes(&SHARE_KEY)
+            .decrypt(&frame[28..frame.len() - 1])
+            .unwrap();
+        assert_eq!(&body[..], b"body");
+
+        let st = OicqEncrypt::SessionTicket {
+            ticket: &[5; 3],
+            key: &SHARE_KEY,
+        };
+        let frame = pkt.encode(&st);
+        assert_eq!(frame[14], 0x45);
+        assert_eq!(&frame[HEADER_LEN..HEADER_LEN + 5], &[0, 3, 5, 5, 5]);
+    }
+
+    #[test]
+    fn decode() {
+        let mut body = BytesMut::new();
+        body.put_u16(9);
+        body.put_u8(0);
+        body.put_u16(2);
+        body.put_slice(&tlv104(&[1, 2, 3]));
+        body.put_slice(&tlv8(2052));
+
+        for key in [&SHARE_KEY, &RANDOM_KEY] {
+            let rsp = OicqPacket::decode(response(0, key, &body), &ecdh()).unwrap();
+            assert_eq!(rsp.uin, 10001);
+            assert_eq!(rsp.command, 0x810);
+            assert_eq!(rsp.sub_command, 9);
+            assert_eq!(rsp.status, 0);
+            assert_eq!(rsp.tlvs.len(), 2);
+            assert_eq!(rsp.tlvs.get(0x104).unwrap().as_ref(), &[1, 2, 3]);
+        }
+
+        let st = OicqEncrypt::SessionTicket {
+            ticket: &[],
+            key: &SHARE_KEY,
+        };
+        let rsp = OicqPacket::decode(response(3, &SHARE_KEY, &body), &st).unwrap();
+        assert_eq!(rsp.sub_command, 9);
+
+        assert!(matches!(
+            OicqPacket::decode(response(3, &SHARE_KEY, &body), &ecdh()),
+            Err(OicqError::UnknownEncryptType(3))
+        ));
+
+        let mut truncated = BytesMut::from(&response(0, &SHARE_KEY, &body)[..]);
+        truncated.truncate(truncated.len() - 2);
+        assert!(matches!(
+            OicqPacket::decode(truncated.freeze(), &ecdh()),
+            Err(OicqError::Malformed)
+        ));
+    }
+}
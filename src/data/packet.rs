@@ -1,27 +1,208 @@
-use bytes::{BufMut, Bytes, BytesMut};
+use crate::crypto::tea::{DecryptError, Tea};
+use crate::data::protocol::ProtocolInfo;
+use bytes::{Buf, BufMut, Bytes, BytesMut};
+use flate2::read::ZlibDecoder;
+use std::borrow::Cow;
+use std::error::Error;
+use std::fmt::{Debug, Display, Formatter};
+use std::io::Read;
 
 pub struct Packet {
     pub seq: u32,
     pub uin: u64,
     pub packet_detail: PacketDetail,
     pub encrypt: Encrypt,
-    pub command: &'static str,
+    pub command: Cow<'static, str>,
     pub body: Bytes,
     pub message: String,
 }
 
+/// Session state required to frame an outgoing sso packet.
+pub struct SsoContext<'a> {
+    pub protocol: &'a ProtocolInfo,
+    pub imei: &'a str,
+    pub ksid: &'a [u8],
+    pub session_id: &'a [u8; 4],
+    pub tgt: &'a [u8],
+    pub d2: &'a [u8],
+    pub d2_key: &'a [u8; 16],
+}
+
+static EMPTY_KEY: [u8; 16] = [0; 16];
+
+fn put_lv32(buf: &mut BytesMut, bytes: &[u8]) {
+    buf.put_u32(bytes.len() as u32 + 4);
+    buf.put_slice(bytes);
+}
+
+fn get_lv32(buf: &mut Bytes) -> Result<Bytes, PacketError> {
+    if buf.len() < 4 {
+        return Err(PacketError::Malformed);
+    }
+
+    let len = (buf.get_u32() as usize)
+        .checked_sub(4)
+        .ok_or(PacketError::Malformed)?;
+    if buf.len() < len {
+        return Err(PacketError::Malformed);
+    }
+
+    Ok(buf.split_to(len))
+}
+
 impl Packet {
-    pub fn build_sso_packet(&self) -> Bytes {
-        let len = self.command.len() + 20;
+    /// Builds the whole frame, including the leading `u32` length.
+    pub fn build_sso_packet(&self, ctx: &SsoContext) -> Bytes {
+        let (key, d2): (&[u8; 16], &[u8]) = match self.encrypt {
+            Encrypt::UseD2Key => (ctx.d2_key, ctx.d2),
+            Encrypt::EmptyKey => (&EMPTY_KEY, &[]),
+            Encrypt::NoEncrypt => (&EMPTY_KEY, &[]),
+        };
+
+        let mut inner = BytesMut::new();
+        let mut head = BytesMut::new();
+        match self.packet_detail {
+            PacketDetail::Login => {
+                head.put_u32(self.seq);
+                head.put_u32(ctx.protocol.subid);
+                head.put_u32(ctx.protocol.subid);
+                head.put_slice(&[1, 0, 0, 0, 0, 0, 0, 0, 0, 0, 1, 0]);
+                put_lv32(&mut head, ctx.tgt);
+                put_lv32(&mut head, self.command.as_bytes());
+                put_lv32(&mut head, ctx.session_id);
+                put_lv32(&mut head, ctx.imei.as_bytes());
+                head.put_u32(4);
+                head.put_u16(ctx.ksid.len() as u16 + 2);
+                head.put_slice(ctx.ksid);
+                head.put_u32(4);
+            }
+            PacketDetail::Uin => {
+                put_lv32(&mut head, self.command.as_bytes());
+                head.put_u32(8);
+                head.put_slice(ctx.session_id);
+                head.put_u32(4);
+            }
+        }
+        put_lv32(&mut inner, &head);
+        put_lv32(&mut inner, &self.body);
 
-        let mut buf = BytesMut::with_capacity(len + self.body.len() + 4);
+        let uin = self.uin.to_string();
+        let body = match self.encrypt {
+            Encrypt::NoEncrypt => inner.freeze(),
+            _ => Tea::from_bytes(key).encrypt(&inner),
+        };
 
-        let len32 = len as u32;
-        buf.put_u32(len32);
-        buf.put_u32(len32 - 16);
+        let mut buf = BytesMut::with_capacity(body.len() + uin.len() + d2.len() + 22);
+        buf.put_u32(0); // total length
+        match self.packet_detail {
+            PacketDetail::Login => {
+                buf.put_u32(0x0A);
+                buf.put_u8(self.encrypt.flag());
+                put_lv32(&mut buf, d2);
+            }
+            PacketDetail::Uin => {
+                buf.put_u32(0x0B);
+                buf.put_u8(self.encrypt.flag());
+                buf.put_u32(self.seq);
+            }
+        }
+        buf.put_u8(0);
+        put_lv32(&mut buf, uin.as_bytes());
+        buf.put_slice(&body);
 
+        let len = buf.len() as u32;
+        buf[..4].copy_from_slice(&len.to_be_bytes());
         buf.freeze()
     }
+
+    /// Decodes an incoming frame with its leading `u32` length already removed.
+    #[inline]
+    pub fn decode_sso_packet(frame: Bytes, d2_key: &[u8; 16]) -> Result<Self, PacketError> {
+        Self::decode_sso_response(frame, d2_key)?.1
+    }
+
+    /// Like [`Packet::decode_sso_packet`], but keeps the seq of responses the server
+    /// refused, so the failure can reach whoever waits for it.
+    pub fn decode_sso_response(
+        mut frame: Bytes,
+        d2_key: &[u8; 16],
+    ) -> Result<(u32, Result<Self, PacketError>), PacketError> {
+        if frame.len() < 6 {
+            return Err(PacketError::Malformed);
+        }
+
+        let packet_detail = match frame.get_u32() {
+            0x0A => PacketDetail::Login,
+            0x0B => PacketDetail::Uin,
+            _ => return Err(PacketError::Malformed),
+        };
+        let encrypt = match frame.get_u8() {
+            0 => Encrypt::NoEncrypt,
+            1 => Encrypt::UseD2Key,
+            2 => Encrypt::EmptyKey,
+            _ => return Err(PacketError::Malformed),
+        };
+        frame.advance(1);
+
+        let uin = get_lv32(&mut frame)?;
+        let uin = std::str::from_utf8(&uin)
+            .ok()
+            .and_then(|s| s.parse().ok())
+            .unwrap_or(0);
+
+        let mut frame = match encrypt {
+            Encrypt::NoEncrypt => frame,
+            Encrypt::UseD2Key => Tea::from_bytes(d2_key).decrypt(&frame)?,
+            Encrypt::EmptyKey => Tea::from_bytes(&EMPTY_KEY).decrypt(&frame)?,
+        };
+
+        let mut head = get_lv32(&mut frame)?;
+        if head.len() < 8 {
+            return Err(PacketError::Malformed);
+        }
+        let seq = head.get_u32();
+        let ret_code = head.get_i32();
+        let message = String::from_utf8_lossy(&get_lv32(&mut head)?).into_owned();
+        match ret_code {
+            0 => {}
+            -10008 => return Ok((seq, Err(PacketError::SessionExpired))),
+            code => return Ok((seq, Err(PacketError::Server { code, message }))),
+        }
+
+        let command = String::from_utf8_lossy(&get_lv32(&mut head)?).into_owned();
+        get_lv32(&mut head)?; // session id
+        let compress = if head.len() >= 4 { head.get_u32() } else { 0 };
+
+        let body = if frame.len() >= 4 {
+            let len = (frame.get_u32() as usize).saturating_sub(4);
+            frame.split_to(len.min(frame.len()))
+        } else {
+            Bytes::new()
+        };
+        let body = match compress {
+            1 => {
+                let mut out = Vec::new();
+                if let Err(e) = ZlibDecoder::new(&body[..]).read_to_end(&mut out) {
+                    return Ok((seq, Err(PacketError::Decompress(e))));
+                }
+                Bytes::from(out)
+            }
+            8 if body.len() >= 4 => body.slice(4..),
+            8 => Bytes::new(),
+            _ => body,
+        };
+
+        let pkt = Self {
+            seq,
+            uin,
+            packet_detail,
+            encrypt,
+            command: Cow::Owned(command),
+            body,
+            message,
+        };
+        Ok((seq, Ok(pkt)))
+    }
 }
 
 pub enum PacketDetail {
@@ -31,5 +212,210 @@ pub enum PacketDetail {
 
 pub enum Encrypt {
     UseD2Key,
+    EmptyKey,
     NoEncrypt,
 }
+
+impl Encrypt {
+    fn flag(&self) -> u8 {
+        match self {
+            Self::NoEncrypt => 0,
+            Self::UseD2Key => 1,
+            Self::EmptyKey => 2,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum PacketError {
+    Malformed,
+    Decrypt(DecryptError),
+    Decompress(std::io::Error),
+    SessionExpired,
+    Server { code: i32, message: String },
+}
+
+impl Display for PacketError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Malformed => f.write_str("Malformed sso packet"),
+            Self::Decrypt(e) => write!(f, "Sso decrypt error: {}", e),
+            Self::Decompress(e) => write!(f, "Sso decompress error: {}", e),
+            Self::SessionExpired => f.write_str("Session expired"),
+            Self::Server { code, message } => {
+                write!(f, "Server returned {}: {}", code, message)
+            }
+        }
+    }
+}
+
+impl From<DecryptError> for PacketError {
+    fn from(e: DecryptError) -> Self {
+        Self::Decrypt(e)
+    }
+}
+
+impl Error for PacketError {}
+
+#[cfg(test)]
+mod tests {
+    use crate::crypto::tea::Tea;
+    use crate::data::packet::{
+        put_lv32, Encrypt, Packet, PacketDetail, PacketError, SsoContext, EMPTY_KEY,
+    };
+    use crate::data::protocol::ProtocolInfo;
+    use bytes::{Buf, BufMut, Bytes, BytesMut};
+    use flate2::write::ZlibEncoder;
+    use flate2::Compression;
+    use std::borrow::Cow;
+    use std::io::Write;
+
+    const D2_KEY: [u8; 16] = [3; 16];
+
+    fn ctx() -> SsoContext<'static> {
+        SsoContext {
+            protocol: &ProtocolInfo::ANDROID_WATCH,
+            imei: "582827438036112",
+            ksid: b"ksid",
+            session_id: &[1, 2, 3, 4],
+            tgt: b"tgt",
+            d2: b"d2",
+            d2_key: &D2_KEY,
+        }
+    }
+
+    fn packet(detail: PacketDetail, encrypt: Encrypt) -> Packet {
+        Packet {
+            seq: 7,
+            uin: 10001,
+            packet_detail: detail,
+            encrypt,
+            command: Cow::Borrowed("wtlogin.login"),
+            body: Bytes::from_static(b"body"),
+            message: String::new(),
+        }
+    }
+
+    /// Server side frame: `0x0B | flag | 0 | uin | encrypted(head | body)`.
+    fn response(encrypt: u8, key: &[u8; 16], compress: u32, ret_code: i32, body: &[u8]) -> Bytes {
+        let mut head = BytesMut::new();
+        head.put_u32(7);
+        head.put_i32(ret_code);
+        put_lv32(&mut head, b"msg");
+        put_lv32(&mut head, b"OidbSvc.0x88d_0");
+        put_lv32(&mut head, &[1, 2, 3, 4]);
+        head.put_u32(compress);
+
+        let mut inner = BytesMut::new();
+        put_lv32(&mut inner, &head);
+        put_lv32(&mut inner, body);
+
+        let mut buf = BytesMut::new();
+        buf.put_u32(0x0B);
+        buf.put_u8(encrypt);
+        buf.put_u8(0);
+        put_lv32(&mut buf, b"10001");
+        if encrypt == 0 {
+            buf.put_slice(&inner);
+        } else {
+            buf.put_slice(&Tea::from_bytes(key).encrypt(&inner));
+        }
+        buf.freeze()
+    }
+
+    #[test]
+    fn build_login() {
+        let mut frame = packet(PacketDetail::Login, Encrypt::EmptyKey).build_sso_packet(&ctx());
+        assert_eq!(frame.get_u32() as usize, frame.len() + 4);
+        assert_eq!(frame.get_u32(), 0x0A);
+        assert_eq!(frame.get_u8(), 2);
+        assert_eq!(frame.get_u32(), 4);
+        assert_eq!(frame.get_u8(), 0);
+        assert_eq!(frame.get_u32(), 9);
+        assert_eq!(&frame.split_to(5)[..], b"10001");
+
+        let mut inner = Tea::from_bytes(&EMPTY_KEY).decrypt(&frame).unwrap();
+        let head_len = inner.get_u32() as usize - 4;
+        let mut head = inner.split_to(head_len);
+        assert_eq!(head.get_u32(), 7);
+        assert_eq!(head.get_u32(), ProtocolInfo::ANDROID_WATCH.subid);
+        assert_eq!(head.get_u32(), ProtocolInfo::ANDROID_WATCH.subid);
+        head.advance(12);
+        assert_eq!(&head[..7], &[0, 0, 0, 7, b't', b'g', b't']);
+        head.advance(7);
+        assert_eq!(head.get_u32(), 17);
+        assert_eq!(&head.split_to(13)[..], b"wtlogin.login");
+        assert_eq!(&head[..8], &[0, 0, 0, 8, 1, 2, 3, 4]);
+        assert_eq!(
+            &head[head.len() - 10..],
+            &[0, 6, b'k', b's', b'i', b'd', 0, 0, 0, 4]
+        );
+        assert_eq!(&inner[..], &[0, 0, 0, 8, b'b', b'o', b'd', b'y']);
+    }
+
+    #[test]
+    fn build_uin() {
+        let mut frame = packet(PacketDetail::Uin, Encrypt::UseD2Key).build_sso_packet(&ctx());
+        assert_eq!(frame.get_u32() as usize, frame.len() + 4);
+        assert_eq!(frame.get_u32(), 0x0B);
+        assert_eq!(frame.get_u8(), 1);
+        assert_eq!(frame.get_u32(), 7);
+        assert_eq!(frame.get_u8(), 0);
+        frame.advance(9);
+
+        let mut inner = Tea::from_bytes(&D2_KEY).decrypt(&frame).unwrap();
+        let head_len = inner.get_u32() as usize - 4;
+        let head = inner.split_to(head_len);
+        assert_eq!(&head[..4], &[0, 0, 0, 17]);
+        assert_eq!(&head[17..], &[0, 0, 0, 8, 1, 2, 3, 4, 0, 0, 0, 4]);
+        assert_eq!(&inner[4..], b"body");
+
+        let frame = packet(PacketDetail::Uin, Encrypt::NoEncrypt).build_sso_packet(&ctx());
+        assert_eq!(frame[8], 0);
+        assert_eq!(&frame[frame.len() - 4..], b"body");
+    }
+
+    #[test]
+    fn decode() {
+        let pkt = Packet::decode_sso_packet(response(1, &D2_KEY, 0, 0, b"body"), &D2_KEY).unwrap();
+        assert_eq!(pkt.seq, 7);
+        assert_eq!(pkt.uin, 10001);
+        assert!(matches!(pkt.packet_detail, PacketDetail::Uin));
+        assert!(matches!(pkt.encrypt, Encrypt::UseD2Key));
+        assert_eq!(pkt.command, "OidbSvc.0x88d_0");
+        assert_eq!(pkt.message, "msg");
+        assert_eq!(&pkt.body[..], b"body");
+
+        let pkt = Packet::decode_sso_packet(response(2, &EMPTY_KEY, 0, 0, b"b"), &D2_KEY).unwrap();
+        assert_eq!(&pkt.body[..], b"b");
+
+        let pkt = Packet::decode_sso_packet(response(0, &EMPTY_KEY, 8, 0, b"\0\0\0\x05b"), &D2_KEY)
+            .unwrap();
+        assert_eq!(&pkt.body[..], b"b");
+
+        let mut z = ZlibEncoder::new(Vec::new(), Compression::default());
+        z.write_all(b"compressed body").unwrap();
+        let zipped = z.finish().unwrap();
+        let pkt = Packet::decode_sso_packet(response(1, &D2_KEY, 1, 0, &zipped), &D2_KEY).unwrap();
+        assert_eq!(&pkt.body[..], b"compressed body");
+
+        assert!(matches!(
+            Packet::decode_sso_packet(response(1, &D2_KEY, 0, -10008, b""), &D2_KEY),
+            Err(PacketError::SessionExpired)
+        ));
+        assert!(matches!(
+            Packet::decode_sso_packet(response(1, &D2_KEY, 0, -10106, b""), &D2_KEY),
+            Err(PacketError::Server { code: -10106, .. })
+        ));
+        assert!(matches!(
+            Packet::decode_sso_packet(response(1, &EMPTY_KEY, 0, 0, b""), &D2_KEY),
+            Err(PacketError::Decrypt(_))
+        ));
+
+        // the seq is known by the time decompressing fails
+        assert!(matches!(
+            Packet::decode_sso_response(response(1, &D2_KEY, 1, 0, b"not zlib"), &D2_KEY),
+            Ok((7, Err(PacketError::Decompress(_))))
+        ));
+    }
+}
@@ -1,3 +1,4 @@
+#[derive(Clone)]
 pub struct ProtocolInfo {
     pub id: &'static str,
     pub name: &'static str,
@@ -9,6 +10,7 @@ pub struct ProtocolInfo {
     pub subid: u32,
     pub bitmap: u32,
     pub sigmap: u32,
+    pub sub_sigmap: u32,
     pub sso_version: u32,
     pub sdk_version: &'static str,
 }
@@ -27,6 +29,7 @@ impl ProtocolInfo {
         subid: 537064446,
         bitmap: 16252796,
         sigmap: 34869472,
+        sub_sigmap: 66560,
         sso_version: 5,
         sdk_version: "6.0.0.236",
     };
@@ -1,6 +1,14 @@
+use crate::crypto::tea::Tea;
+use crate::data::device::DeviceInfo;
 use crate::data::protocol::ProtocolInfo;
+use crate::proto::device::DeviceReport;
 use bytes::buf::UninitSlice;
-use bytes::{BufMut, Bytes, BytesMut};
+use bytes::{Buf, BufMut, Bytes, BytesMut};
+use digest::Digest;
+use prost::Message;
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt::{Debug, Display, Formatter};
 
 pub struct TlvWriter {
     buf: BytesMut,
@@ -30,6 +38,12 @@ impl TlvWriter {
         self.write_bytes(b.as_ref())
     }
 
+    /// Writes a length-prefixed slice, truncated to at most `limit` bytes.
+    pub fn write_limited(&mut self, bytes: &[u8], limit: usize) {
+        let bytes = &bytes[..bytes.len().min(limit)];
+        self.write_bytes(bytes)
+    }
+
     pub fn into_bytes_mut(mut self) -> BytesMut {
         let len = self.buf.len() - 4;
         self.buf[2..4].copy_from_slice(&(len as u16).to_be_bytes());
@@ -37,12 +51,12 @@ impl TlvWriter {
     }
 
     #[inline]
-    pub fn into_bytes(mut self) -> Bytes {
+    pub fn into_bytes(self) -> Bytes {
         self.into_bytes_mut().freeze()
     }
 
     #[inline]
-    pub fn complete(mut self) -> Bytes {
+    pub fn complete(self) -> Bytes {
         self.into_bytes()
     }
 }
@@ -64,6 +78,192 @@ unsafe impl BufMut for TlvWriter {
     }
 }
 
+/// Walks a buffer of `u16 tag | u16 len | payload` entries without copying.
+pub struct TlvReader {
+    buf: Bytes,
+}
+
+impl TlvReader {
+    #[inline]
+    pub fn new(buf: Bytes) -> Self {
+        Self { buf }
+    }
+
+    /// Remaining bytes that have not been read yet.
+    #[inline]
+    pub fn remaining(&self) -> usize {
+        self.buf.len()
+    }
+
+    pub fn read_tlv(&mut self) -> Result<Option<(u16, Bytes)>, TlvError> {
+        if self.buf.is_empty() {
+            return Ok(None);
+        }
+
+        if self.buf.len() < 4 {
+            return Err(TlvError::Truncated {
+                tag: None,
+                expected: 4,
+                remaining: self.buf.len(),
+            });
+        }
+
+        let tag = self.buf.get_u16();
+        let len = self.buf.get_u16() as usize;
+        if self.buf.len() < len {
+            return Err(TlvError::Truncated {
+                tag: Some(tag),
+                expected: len,
+                remaining: self.buf.len(),
+            });
+        }
+
+        Ok(Some((tag, self.buf.split_to(len))))
+    }
+}
+
+impl Iterator for TlvReader {
+    type Item = Result<(u16, Bytes), TlvError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self.read_tlv() {
+            Ok(Some(tlv)) => Some(Ok(tlv)),
+            Ok(None) => None,
+            Err(e) => {
+                self.buf.clear();
+                Some(Err(e))
+            }
+        }
+    }
+}
+
+#[derive(Debug, Default, Clone)]
+pub struct TlvMap {
+    map: HashMap<u16, Bytes>,
+}
+
+impl TlvMap {
+    pub fn decode(buf: Bytes) -> Result<Self, TlvError> {
+        let mut map = HashMap::new();
+        for tlv in TlvReader::new(buf) {
+            let (tag, value) = tlv?;
+            if map.insert(tag, value).is_some() {
+                return Err(TlvError::Duplicate(tag));
+            }
+        }
+
+        Ok(Self { map })
+    }
+
+    /// Decodes a tlv list prefixed with its `u16` entry count,
+    /// as found in wtlogin responses and the decrypted `tlv119`.
+    pub fn decode_counted(mut buf: Bytes) -> Result<Self, TlvError> {
+        if buf.len() < 2 {
+            return Err(TlvError::Truncated {
+                tag: None,
+                expected: 2,
+                remaining: buf.len(),
+            });
+        }
+
+        let count = buf.get_u16() as usize;
+        let mut map = HashMap::with_capacity(count);
+        let mut reader = TlvReader::new(buf);
+        for _ in 0..count {
+            let (tag, value) = reader.read_tlv()?.ok_or(TlvError::Truncated {
+                tag: None,
+                expected: 4,
+                remaining: 0,
+            })?;
+
+            if map.insert(tag, value).is_some() {
+                return Err(TlvError::Duplicate(tag));
+            }
+        }
+
+        Ok(Self { map })
+    }
+
+    #[inline]
+    pub fn get(&self, tag: u16) -> Option<&Bytes> {
+        self.map.get(&tag)
+    }
+
+    pub fn require(&self, tag: u16) -> Result<&Bytes, TlvError> {
+        self.get(tag).ok_or(TlvError::Missing(tag))
+    }
+
+    #[inline]
+    pub fn contains(&self, tag: u16) -> bool {
+        self.map.contains_key(&tag)
+    }
+
+    #[inline]
+    pub fn remove(&mut self, tag: u16) -> Option<Bytes> {
+        self.map.remove(&tag)
+    }
+
+    #[inline]
+    pub fn insert(&mut self, tag: u16, value: Bytes) -> Option<Bytes> {
+        self.map.insert(tag, value)
+    }
+
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.map.len()
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.map.is_empty()
+    }
+
+    #[inline]
+    pub fn iter(&self) -> impl Iterator<Item = (u16, &Bytes)> {
+        self.map.iter().map(|(&tag, value)| (tag, value))
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum TlvError {
+    Truncated {
+        tag: Option<u16>,
+        expected: usize,
+        remaining: usize,
+    },
+    Duplicate(u16),
+    Missing(u16),
+}
+
+impl Display for TlvError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Truncated {
+                tag: Some(tag),
+                expected,
+                remaining,
+            } => write!(
+                f,
+                "Truncated tlv {:#x}: expected {} bytes, {} remaining",
+                tag, expected, remaining
+            ),
+            Self::Truncated {
+                tag: None,
+                expected,
+                remaining,
+            } => write!(
+                f,
+                "Truncated tlv header: expected {} bytes, {} remaining",
+                expected, remaining
+            ),
+            Self::Duplicate(tag) => write!(f, "Duplicate tlv {:#x}", tag),
+            Self::Missing(tag) => write!(f, "Missing tlv {:#x}", tag),
+        }
+    }
+}
+
+impl Error for TlvError {}
+
 macro_rules! tlv_write {
     ($writer:expr; $($op:ident $arg:expr),* $(,)?) => {
         let w = &mut $writer;
@@ -106,3 +306,811 @@ pub fn tlv1b() -> Bytes {
 
     w.into_bytes()
 }
+
+pub const GUID_FLAG: u32 = 1 << 24;
+
+pub static DOMAINS: &[&str] = &[
+    "tenpay.com",
+    "openmobile.qq.com",
+    "docs.qq.com",
+    "connect.qq.com",
+    "qzone.qq.com",
+    "vip.qq.com",
+    "gamecenter.qq.com",
+    "qun.qq.com",
+    "game.qq.com",
+    "qqweb.qq.com",
+    "office.qq.com",
+    "ti.qq.com",
+    "mail.qq.com",
+    "mma.qq.com",
+];
+
+fn md5(data: &[u8]) -> [u8; 16] {
+    let mut arr = [0; 16];
+    arr.copy_from_slice(&md5::Md5::digest(data));
+    arr
+}
+
+pub fn tlv1(device: &DeviceInfo, uin: u32, rand: u32, time: u32) -> Bytes {
+    let mut w = TlvWriter::with_capacity(0x1, 20);
+
+    tlv_write!(
+        w;
+        put_u16 1, // ip version
+        put_u32 rand,
+        put_u32 uin,
+        put_u32 time,
+        put_slice &device.ip_address,
+        put_u16 0,
+    );
+
+    w.into_bytes()
+}
+
+pub fn tlv8(local_id: u32) -> Bytes {
+    let mut w = TlvWriter::with_capacity(0x8, 8);
+
+    tlv_write!(
+        w;
+        put_u16 0,
+        put_u32 local_id,
+        put_u16 0,
+    );
+
+    w.into_bytes()
+}
+
+pub fn tlv18(protocol: &ProtocolInfo, uin: u32) -> Bytes {
+    let mut w = TlvWriter::with_capacity(0x18, 22);
+
+    tlv_write!(
+        w;
+        put_u16 1, // ping version
+        put_u32 1536, // sso version
+        put_u32 protocol.appid,
+        put_u32 0, // app client version
+        put_u32 uin,
+        put_u16 0,
+        put_u16 0,
+    );
+
+    w.into_bytes()
+}
+
+pub fn tlv1d(protocol: &ProtocolInfo) -> Bytes {
+    let mut w = TlvWriter::with_capacity(0x1d, 14);
+
+    tlv_write!(
+        w;
+        put_u8 1,
+        put_u32 protocol.bitmap,
+        put_u32 0,
+        put_u8 0,
+        put_u32 0,
+    );
+
+    w.into_bytes()
+}
+
+pub fn tlv1f(device: &DeviceInfo) -> Bytes {
+    let mut w = TlvWriter::new(0x1f);
+
+    w.put_u8(0); // root
+    w.write(device.os_type.as_str());
+    w.write(&device.version.release);
+    w.put_u16(device.apn.network_type());
+    w.write(&device.sim);
+    w.write([]);
+    w.write(device.apn.as_str());
+
+    w.into_bytes()
+}
+
+pub fn tlv33(guid: &[u8; 16]) -> Bytes {
+    let mut w = TlvWriter::with_capacity(0x33, 16);
+    w.put_slice(guid);
+    w.into_bytes()
+}
+
+pub fn tlv35(product_type: u32) -> Bytes {
+    let mut w = TlvWriter::with_capacity(0x35, 4);
+    w.put_u32(product_type);
+    w.into_bytes()
+}
+
+pub fn tlv100(protocol: &ProtocolInfo) -> Bytes {
+    let mut w = TlvWriter::with_capacity(0x100, 22);
+
+    tlv_write!(
+        w;
+        put_u16 1, // db buf version
+        put_u32 protocol.sso_version,
+        put_u32 protocol.appid,
+        put_u32 protocol.subid,
+        put_u32 0, // app client version
+        put_u32 protocol.sigmap,
+    );
+
+    w.into_bytes()
+}
+
+pub fn tlv104(sig: &[u8]) -> Bytes {
+    let mut w = TlvWriter::with_capacity(0x104, sig.len());
+    w.put_slice(sig);
+    w.into_bytes()
+}
+
+/// The encrypted A1 block, keyed by `md5(password_md5 | 0u32 | salt or uin)`.
+#[allow(clippy::too_many_arguments)]
+pub fn tlv106(
+    protocol: &ProtocolInfo,
+    uin: u32,
+    salt: u32,
+    password_md5: &[u8; 16],
+    guid: &[u8; 16],
+    tgtgt_key: &[u8; 16],
+    rand: u32,
+    time: u32,
+) -> Bytes {
+    let uin_or_salt = if salt == 0 { uin } else { salt };
+
+    let mut body = BytesMut::with_capacity(98);
+    tlv_write!(
+        body;
+        put_u16 4, // tgtgt version
+        put_u32 rand,
+        put_u32 protocol.sso_version,
+        put_u32 protocol.appid,
+        put_u32 0, // app client version
+        put_u64 uin_or_salt as u64,
+        put_u32 time,
+        put_slice &[0; 4], // ip
+        put_u8 1, // save password
+        put_slice password_md5,
+        put_slice tgtgt_key,
+        put_u32 0,
+        put_u8 1, // guid available
+        put_slice guid,
+        put_u32 protocol.subid,
+        put_u32 1, // password login
+    );
+    let uin_str = uin.to_string();
+    body.put_u16(uin_str.len() as u16);
+    body.put_slice(uin_str.as_bytes());
+    body.put_u16(0);
+
+    let mut key = [0; 24];
+    key[..16].copy_from_slice(password_md5);
+    key[20..].copy_from_slice(&uin_or_salt.to_be_bytes());
+
+    let mut w = TlvWriter::new(0x106);
+    w.put_slice(&Tea::from_bytes(&md5(&key)).encrypt(&body));
+    w.into_bytes()
+}
+
+pub fn tlv107(pic_type: u16) -> Bytes {
+    let mut w = TlvWriter::with_capacity(0x107, 6);
+
+    tlv_write!(
+        w;
+        put_u16 pic_type,
+        put_u8 0, // captcha type
+        put_u16 0, // pic size
+        put_u8 1, // ret type
+    );
+
+    w.into_bytes()
+}
+
+pub fn tlv108(ksid: &[u8]) -> Bytes {
+    let mut w = TlvWriter::with_capacity(0x108, ksid.len());
+    w.put_slice(ksid);
+    w.into_bytes()
+}
+
+pub fn tlv109(device: &DeviceInfo) -> Bytes {
+    let mut w = TlvWriter::with_capacity(0x109, 16);
+    w.put_slice(&md5(device.android_id.as_bytes()));
+    w.into_bytes()
+}
+
+pub fn tlv10a(tgt: &[u8]) -> Bytes {
+    let mut w = TlvWriter::with_capacity(0x10a, tgt.len());
+    w.put_slice(tgt);
+    w.into_bytes()
+}
+
+pub fn tlv116(protocol: &ProtocolInfo) -> Bytes {
+    let mut w = TlvWriter::with_capacity(0x116, 14);
+
+    tlv_write!(
+        w;
+        put_u8 0, // ver
+        put_u32 protocol.bitmap,
+        put_u32 protocol.sub_sigmap,
+        put_u8 1, // app id list size
+        put_u32 1600000226,
+    );
+
+    w.into_bytes()
+}
+
+pub fn tlv124(device: &DeviceInfo) -> Bytes {
+    let mut w = TlvWriter::new(0x124);
+
+    w.write_limited(device.os_type.as_str().as_bytes(), 16);
+    w.write_limited(device.version.release.as_bytes(), 16);
+    w.put_u16(device.apn.network_type());
+    w.write_limited(device.sim.as_bytes(), 16);
+    w.write_limited(&[], 32); // address
+    w.write_limited(device.apn.as_str().as_bytes(), 16);
+
+    w.into_bytes()
+}
+
+pub fn tlv128(device: &DeviceInfo, guid: &[u8; 16]) -> Bytes {
+    let mut w = TlvWriter::new(0x128);
+
+    tlv_write!(
+        w;
+        put_u16 0,
+        put_u8 0, // guid from file null
+        put_u8 1, // guid available
+        put_u8 0, // guid changed
+        put_u32 GUID_FLAG,
+    );
+    w.write_limited(device.model.as_bytes(), 32);
+    w.write_limited(guid, 16);
+    w.write_limited(device.brand.as_bytes(), 16);
+
+    w.into_bytes()
+}
+
+pub fn tlv141(device: &DeviceInfo) -> Bytes {
+    let mut w = TlvWriter::new(0x141);
+
+    w.put_u16(1); // version
+    w.write(&device.sim);
+    w.put_u16(device.apn.network_type());
+    w.write(device.apn.as_str());
+
+    w.into_bytes()
+}
+
+pub fn tlv142(protocol: &ProtocolInfo) -> Bytes {
+    let mut w = TlvWriter::new(0x142);
+
+    w.put_u16(0);
+    w.write_limited(protocol.id.as_bytes(), 32);
+
+    w.into_bytes()
+}
+
+pub fn tlv143(d2: &[u8]) -> Bytes {
+    let mut w = TlvWriter::with_capacity(0x143, d2.len());
+    w.put_slice(d2);
+    w.into_bytes()
+}
+
+/// Device information encrypted with the tgtgt key.
+pub fn tlv144(device: &DeviceInfo, guid: &[u8; 16], tgtgt_key: &[u8; 16]) -> Bytes {
+    let mut body = BytesMut::new();
+    body.put_u16(5);
+    body.put_slice(&tlv109(device));
+    body.put_slice(&tlv52d(device));
+    body.put_slice(&tlv124(device));
+    body.put_slice(&tlv128(device, guid));
+    body.put_slice(&tlv16e(device));
+
+    let mut w = TlvWriter::new(0x144);
+    w.put_slice(&Tea::from_bytes(tgtgt_key).encrypt(&body));
+    w.into_bytes()
+}
+
+pub fn tlv145(guid: &[u8; 16]) -> Bytes {
+    let mut w = TlvWriter::with_capacity(0x145, 16);
+    w.put_slice(guid);
+    w.into_bytes()
+}
+
+pub fn tlv147(protocol: &ProtocolInfo) -> Bytes {
+    let mut w = TlvWriter::new(0x147);
+
+    w.put_u32(protocol.appid);
+    w.write_limited(protocol.version.as_bytes(), 32);
+    w.write_limited(protocol.sign, 32);
+
+    w.into_bytes()
+}
+
+pub fn tlv154(seq: u32) -> Bytes {
+    let mut w = TlvWriter::with_capacity(0x154, 4);
+    w.put_u32(seq);
+    w.into_bytes()
+}
+
+pub fn tlv16e(device: &DeviceInfo) -> Bytes {
+    let mut w = TlvWriter::new(0x16e);
+    w.put_slice(device.model.as_bytes());
+    w.into_bytes()
+}
+
+pub fn tlv16a(no_pic_sig: &[u8]) -> Bytes {
+    let mut w = TlvWriter::with_capacity(0x16a, no_pic_sig.len());
+    w.put_slice(no_pic_sig);
+    w.into_bytes()
+}
+
+pub fn tlv174(sig: &[u8]) -> Bytes {
+    let mut w = TlvWriter::with_capacity(0x174, sig.len());
+    w.put_slice(sig);
+    w.into_bytes()
+}
+
+pub fn tlv17a(value: u32) -> Bytes {
+    let mut w = TlvWriter::with_capacity(0x17a, 4);
+    w.put_u32(value);
+    w.into_bytes()
+}
+
+pub fn tlv17c(code: &str) -> Bytes {
+    let mut w = TlvWriter::with_capacity(0x17c, code.len() + 2);
+    w.write(code);
+    w.into_bytes()
+}
+
+pub fn tlv177(protocol: &ProtocolInfo) -> Bytes {
+    let mut w = TlvWriter::new(0x177);
+
+    w.put_u8(1);
+    w.put_u32(protocol.build_time);
+    w.write(protocol.sdk_version);
+
+    w.into_bytes()
+}
+
+pub fn tlv187(device: &DeviceInfo) -> Bytes {
+    let mut w = TlvWriter::with_capacity(0x187, 16);
+    w.put_slice(&md5(device.mac_address.as_bytes()));
+    w.into_bytes()
+}
+
+pub fn tlv188(device: &DeviceInfo) -> Bytes {
+    let mut w = TlvWriter::with_capacity(0x188, 16);
+    w.put_slice(&md5(device.android_id.as_bytes()));
+    w.into_bytes()
+}
+
+pub fn tlv191(k: u8) -> Bytes {
+    let mut w = TlvWriter::with_capacity(0x191, 1);
+    w.put_u8(k);
+    w.into_bytes()
+}
+
+pub fn tlv193(ticket: &str) -> Bytes {
+    let mut w = TlvWriter::with_capacity(0x193, ticket.len());
+    w.put_slice(ticket.as_bytes());
+    w.into_bytes()
+}
+
+pub fn tlv194(device: &DeviceInfo) -> Bytes {
+    let mut w = TlvWriter::with_capacity(0x194, 16);
+    w.put_slice(&device.imsi);
+    w.into_bytes()
+}
+
+pub fn tlv197() -> Bytes {
+    let mut w = TlvWriter::with_capacity(0x197, 1);
+    w.put_u8(0);
+    w.into_bytes()
+}
+
+pub fn tlv198() -> Bytes {
+    let mut w = TlvWriter::with_capacity(0x198, 1);
+    w.put_u8(0);
+    w.into_bytes()
+}
+
+pub fn tlv202(device: &DeviceInfo) -> Bytes {
+    let mut w = TlvWriter::new(0x202);
+
+    w.write_limited(&md5(device.wifi_bssid.as_bytes()), 16);
+    w.write_limited(device.wifi_ssid.as_bytes(), 32);
+
+    w.into_bytes()
+}
+
+/// The `tgt_qr` handed out once a qrcode is confirmed.
+pub fn tlv318(tgt_qr: &[u8]) -> Bytes {
+    let mut w = TlvWriter::with_capacity(0x318, tgt_qr.len());
+    w.put_slice(tgt_qr);
+    w.into_bytes()
+}
+
+pub fn tlv401(g: &[u8; 16]) -> Bytes {
+    let mut w = TlvWriter::with_capacity(0x401, 16);
+    w.put_slice(g);
+    w.into_bytes()
+}
+
+/// Domains to request pskeys for, optionally prefixed with `(flags)`.
+pub fn tlv511(domains: &[&str]) -> Bytes {
+    let mut w = TlvWriter::new(0x511);
+
+    let domains: Vec<&str> = domains.iter().copied().filter(|d| !d.is_empty()).collect();
+    w.put_u16(domains.len() as u16);
+    for domain in domains {
+        let flagged = domain
+            .strip_prefix('(')
+            .and_then(|d| d.split_once(')'))
+            .and_then(|(flags, d)| flags.parse::<u32>().ok().map(|flags| (flags, d)));
+
+        match flagged {
+            Some((flags, domain)) => {
+                let mut b = 0;
+                if flags & 0x100000 != 0 {
+                    b |= 1;
+                }
+                if flags & 0x8000000 != 0 {
+                    b |= 2;
+                }
+                w.put_u8(b);
+                w.write(domain);
+            }
+            None => {
+                w.put_u8(1);
+                w.write(domain);
+            }
+        }
+    }
+
+    w.into_bytes()
+}
+
+pub fn tlv516() -> Bytes {
+    let mut w = TlvWriter::with_capacity(0x516, 4);
+    w.put_u32(0); // source type
+    w.into_bytes()
+}
+
+pub fn tlv521(product_type: u32) -> Bytes {
+    let mut w = TlvWriter::with_capacity(0x521, 6);
+
+    tlv_write!(
+        w;
+        put_u32 product_type,
+        put_u16 0,
+    );
+
+    w.into_bytes()
+}
+
+pub fn tlv525(t536: &[u8]) -> Bytes {
+    let mut w = TlvWriter::new(0x525);
+
+    w.put_u16(1);
+    w.put_slice(t536);
+
+    w.into_bytes()
+}
+
+pub fn tlv52d(device: &DeviceInfo) -> Bytes {
+    let report = DeviceReport {
+        bootloader: device.bootloader.as_bytes().to_vec(),
+        proc_version: device.proc_version.as_bytes().to_vec(),
+        codename: device.version.codename.as_bytes().to_vec(),
+        incremental: device.version.incremental.to_string().into_bytes(),
+        fingerprint: device.fingerprint().into_bytes(),
+        boot_id: device.boot_id.as_bytes().to_vec(),
+        android_id: device.android_id.as_bytes().to_vec(),
+        base_band: device.base_band.as_bytes().to_vec(),
+        inner_version: device.version.incremental.to_string().into_bytes(),
+    };
+
+    let mut w = TlvWriter::with_capacity(0x52d, report.encoded_len());
+    report.encode(&mut w).expect("TlvWriter grows on demand");
+    w.into_bytes()
+}
+
+pub fn tlv536(login_extra_data: &[u8]) -> Bytes {
+    let mut w = TlvWriter::with_capacity(0x536, login_extra_data.len());
+    w.put_slice(login_extra_data);
+    w.into_bytes()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::crypto::tea::Tea;
+    use crate::data::device::sample;
+    use crate::data::protocol::ProtocolInfo;
+    use crate::data::tlv::*;
+    use crate::proto::device::DeviceReport;
+    use bytes::{Buf, BufMut, Bytes, BytesMut};
+    use prost::Message;
+
+    fn hex(s: &str) -> Vec<u8> {
+        let s: String = s.split_whitespace().collect();
+        (0..s.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(&s[i..i + 2], 16).unwrap())
+            .collect()
+    }
+
+    fn tlv(tag: u16, value: &[u8]) -> Bytes {
+        let mut w = TlvWriter::new(tag);
+        w.put_slice(value);
+        w.into_bytes()
+    }
+
+    #[test]
+    fn read() {
+        let mut buf = BytesMut::new();
+        buf.put_slice(&tlv(0x106, &[1, 2, 3]));
+        buf.put_slice(&tlv(0x119, &[]));
+        buf.put_slice(&tlv1b());
+
+        let buf = buf.freeze();
+        let tlvs: Vec<_> = TlvReader::new(buf.clone())
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert_eq!(tlvs.len(), 3);
+        assert_eq!(tlvs[0], (0x106, Bytes::from_static(&[1, 2, 3])));
+        assert_eq!(tlvs[1], (0x119, Bytes::new()));
+        assert_eq!(tlvs[2].0, 0x1b);
+        assert_eq!(tlvs[2].1.len(), 30);
+
+        let map = TlvMap::decode(buf).unwrap();
+        assert_eq!(map.len(), 3);
+        assert_eq!(map.get(0x106).unwrap().as_ref(), &[1, 2, 3]);
+        assert!(map.require(0x119).unwrap().is_empty());
+        assert_eq!(map.require(0x16), Err(TlvError::Missing(0x16)));
+    }
+
+    #[test]
+    fn counted() {
+        let mut buf = BytesMut::new();
+        buf.put_u16(2);
+        buf.put_slice(&tlv(0x1, &[1]));
+        buf.put_slice(&tlv(0x2, &[2, 2]));
+
+        let map = TlvMap::decode_counted(buf.freeze()).unwrap();
+        assert_eq!(map.get(0x2).unwrap().as_ref(), &[2, 2]);
+
+        let mut buf = BytesMut::new();
+        buf.put_u16(2);
+        buf.put_slice(&tlv(0x1, &[1]));
+        assert!(matches!(
+            TlvMap::decode_counted(buf.freeze()),
+            Err(TlvError::Truncated { tag: None, .. })
+        ));
+    }
+
+    #[test]
+    fn malformed() {
+        let mut buf = BytesMut::new();
+        buf.put_slice(&tlv(0x104, &[0; 4]));
+        buf.put_u16(0x105);
+        buf.put_u16(8);
+        buf.put_slice(&[0; 3]);
+
+        let mut reader = TlvReader::new(buf.freeze());
+        assert!(reader.next().unwrap().is_ok());
+        assert_eq!(
+            reader.next().unwrap(),
+            Err(TlvError::Truncated {
+                tag: Some(0x105),
+                expected: 8,
+                remaining: 3,
+            })
+        );
+        assert!(reader.next().is_none());
+
+        let mut buf = BytesMut::new();
+        buf.put_slice(&tlv(0x104, &[0; 4]));
+        buf.put_slice(&tlv(0x104, &[1; 4]));
+        assert_eq!(
+            TlvMap::decode(buf.freeze()).unwrap_err(),
+            TlvError::Duplicate(0x104)
+        );
+
+        assert!(matches!(
+            TlvMap::decode(Bytes::from_static(&[0, 1, 0])),
+            Err(TlvError::Truncated { tag: None, .. })
+        ));
+    }
+
+    #[test]
+    fn golden() {
+        let protocol = ProtocolInfo::ANDROID_WATCH;
+        let device = sample();
+        let guid = device.guid();
+
+        assert_eq!(
+            &*tlv1(&device, 10001, 0x01020304, 0x5f5e1000),
+            hex("0001 0014 0001 01020304 00002711 5f5e1000 0a000103 0000")
+        );
+        assert_eq!(&*tlv8(2052), hex("0008 0008 0000 00000804 0000"));
+        assert_eq!(
+            &*tlv1d(&protocol),
+            hex("001d 000e 01 00f7ff7c 00000000 00 00000000")
+        );
+        assert_eq!(
+            &*tlv1f(&device),
+            hex("001f 0022 00 0007 616e64726f6964 0002 3130 0002 0008 542d4d6f62696c65 0000 0004 77696669")
+        );
+        assert_eq!(&tlv33(&guid)[4..], &guid);
+        assert_eq!(&*tlv35(8), hex("0035 0004 00000008"));
+        assert_eq!(&*tlv16a(b"sig"), hex("016a 0003 736967"));
+        assert_eq!(&*tlv318(b"qr"), hex("0318 0002 7172"));
+        assert_eq!(&*tlv108(b"ksid"), hex("0108 0004 6b736964"));
+        assert_eq!(&*tlv10a(b"a2"), hex("010a 0002 6132"));
+        assert_eq!(&*tlv143(b"d2"), hex("0143 0002 6432"));
+        assert_eq!(&*tlv17a(9), hex("017a 0004 00000009"));
+        assert_eq!(&*tlv17c("1234"), hex("017c 0006 0004 31323334"));
+        assert_eq!(&*tlv193("t"), hex("0193 0001 74"));
+        assert_eq!(&*tlv197(), hex("0197 0001 00"));
+        assert_eq!(&tlv401(&[1; 16])[..4], hex("0401 0010"));
+        assert_eq!(
+            &*tlv18(&protocol, 10001),
+            hex("0018 0016 0001 00000600 00000010 00000000 00002711 0000 0000")
+        );
+        assert_eq!(
+            &*tlv100(&protocol),
+            hex("0100 0016 0001 00000005 00000010 2002f3fe 00000000 021410e0")
+        );
+        assert_eq!(&*tlv104(&[1, 2]), hex("0104 0002 0102"));
+        assert_eq!(&*tlv107(0), hex("0107 0006 0000 00 0000 01"));
+        assert_eq!(
+            &*tlv109(&device),
+            hex("0109 0010 bd97364d86c99309e7bffe81df1a9e07")
+        );
+        assert_eq!(
+            &*tlv116(&protocol),
+            hex("0116 000e 00 00f7ff7c 00010400 01 5f5e10e2")
+        );
+        assert_eq!(
+            &*tlv124(&device),
+            hex(
+                "0124 0021 0007 616e64726f6964 0002 3130 0002 0008 542d4d6f62696c65 0000 0004 77696669"
+            )
+        );
+        assert_eq!(
+            &*tlv141(&device),
+            hex("0141 0014 0001 0008 542d4d6f62696c65 0002 0004 77696669")
+        );
+        assert_eq!(
+            &*tlv142(&protocol),
+            hex("0142 0016 0000 0012 636f6d2e74656e63656e742e71716c697465")
+        );
+        assert_eq!(
+            &*tlv128(&device, &guid),
+            hex("0128 0029 0000 00 01 00 01000000 0004 4d492036
+                0010 64a267ccf6abbbb928449ff56c445821 0006 5869616f6d69")
+        );
+        assert_eq!(&tlv145(&guid)[4..], &guid);
+        assert_eq!(
+            &*tlv147(&protocol),
+            hex("0147 001d 00000010 0005 322e302e35 0010 a6b745bf24a2c277527716f6f36eb68d")
+        );
+        assert_eq!(&*tlv154(16), hex("0154 0004 00000010"));
+        assert_eq!(&*tlv16e(&device), hex("016e 0004 4d492036"));
+        assert_eq!(
+            &*tlv177(&protocol),
+            hex("0177 0010 01 5cf511bb 0009 362e302e302e323336")
+        );
+        assert_eq!(
+            &*tlv187(&device),
+            hex("0187 0010 10d2717e7d06a80d409fbb21516ebec0")
+        );
+        assert_eq!(
+            &*tlv188(&device),
+            hex("0188 0010 bd97364d86c99309e7bffe81df1a9e07")
+        );
+        assert_eq!(&*tlv191(0x82), hex("0191 0001 82"));
+        assert_eq!(&tlv194(&device)[4..], &device.imsi);
+        assert_eq!(
+            &*tlv202(&device),
+            hex("0202 001a 0010 10d2717e7d06a80d409fbb21516ebec0 0006 4d6957696669")
+        );
+        assert_eq!(
+            &*tlv511(&["a.com", "(1048576)b.com", ""]),
+            hex("0511 0012 0002 01 0005 612e636f6d 01 0005 622e636f6d")
+        );
+        assert_eq!(&*tlv516(), hex("0516 0004 00000000"));
+        assert_eq!(&*tlv521(0), hex("0521 0006 00000000 0000"));
+        assert_eq!(
+            &*tlv525(&tlv536(&[1, 0])),
+            hex("0525 0008 0001 0536 0002 0100")
+        );
+        assert_eq!(
+            &*tlv52d(&device),
+            hex("052d 00d5
+                0a06552d626f6f74123a4c696e757820352e342e302d35342d67656e65726963
+                2d5158703053444d772028616e64726f69642d6275696c6440676f6f676c652e
+                636f6d291a0352454c2207353839313933382a405869616f6d692f696172696d
+                2f73616769743a31302f643766633730613039663463633466352f3538393139
+                33383a757365722f72656c656173652d6b657973322462313566366662652d35
+                6532392d376439622d396537332d3661343030666131396630383a1064376663
+                3730613039663463633466354a0735383931393338")
+        );
+    }
+
+    #[test]
+    fn encrypted() {
+        let protocol = ProtocolInfo::ANDROID_WATCH;
+        let device = sample();
+        let guid = device.guid();
+        let password_md5 = [7; 16];
+        let tgtgt_key = [9; 16];
+
+        let t106 = tlv106(
+            &protocol,
+            10001,
+            0,
+            &password_md5,
+            &guid,
+            &tgtgt_key,
+            0x01020304,
+            0x5f5e1000,
+        );
+        assert_eq!(
+            &*t106,
+            hex("0106 0078
+                c3f34dc7dd404fad392b53ed8070dbc264773e6673973963c75badb4bbc9ccc0
+                00bad1811b0e81c6870e4381d2c15d228a53b5b9d9c67779bc45f2705bdee492
+                09fd44de4273f25f76cfb948c68ed430052d806d5c9d4697ca1951fbcb792f48
+                b5f21738fa4e95eb51e80cc8f904418315a1043ec20ba4e9")
+        );
+
+        let mut key = [0; 24];
+        key[..16].copy_from_slice(&password_md5);
+        key[20..].copy_from_slice(&10001u32.to_be_bytes());
+        let mut body = Tea::from_bytes(&md5(&key)).decrypt(&t106[4..]).unwrap();
+        assert_eq!(body.get_u16(), 4);
+        assert_eq!(body.get_u32(), 0x01020304);
+        assert_eq!(body.get_u32(), protocol.sso_version);
+        assert_eq!(body.get_u32(), protocol.appid);
+        assert_eq!(body.get_u32(), 0);
+        assert_eq!(body.get_u64(), 10001);
+        assert_eq!(body.get_u32(), 0x5f5e1000);
+        body.advance(5);
+        assert_eq!(&body.split_to(16)[..], &password_md5);
+        assert_eq!(&body.split_to(16)[..], &tgtgt_key);
+        body.advance(5);
+        assert_eq!(&body.split_to(16)[..], &guid);
+        assert_eq!(body.get_u32(), protocol.subid);
+        assert_eq!(body.get_u32(), 1);
+        assert_eq!(&body[..], &hex("0005 3130303031 0000")[..]);
+
+        let t144 = tlv144(&device, &guid, &tgtgt_key);
+        assert_eq!(
+            &*t144,
+            hex("0144 0158
+                ca1ed03c8f43c9096fc292a385e09ef2136c6acb5f89eb3e14377f55eb0a8af6
+                9319946f27a73be282e29a7c8d6a4109bd3018b940fce1b1ccf271a5ce1f0916
+                3fe4d4c3237e76a992bc49f226ba36b2d40623ece2c667fbd6a7a96d0e6b4c33
+                7a008168588d4b18f1fabb2a898a1e927c10f0e98155903bfa635cc470b791d4
+                9f3884f4e902e921576c783c7aafe0da011b391c264e59273a67eff1b9e49150
+                53d0abf6100d26876c63ed8539b25fde57316a7fcae4473e1834e4ded459dbb7
+                84ca2abb5609370017b6229e164d8eb331eda8657f4eed6f650ff49d35e24852
+                9fe75659ab06e71612e78719bb43d13ec06f6cb359588b1fdb21eaac9c080517
+                3350cc71bb146bc35b500251ed9d5c62d610a0037b61e5c012ef23cb9b0bb82a
+                e7a5a473c4017499e80cb4921651dae2c91ada49b2e757fd23a3728c8abd596e
+                3631c881e1b95515ffe2998f5fc7dd8a72b1bf05c2e885a8")
+        );
+        let body = Tea::from_bytes(&tgtgt_key).decrypt(&t144[4..]).unwrap();
+        let map = TlvMap::decode_counted(body).unwrap();
+        assert_eq!(map.len(), 5);
+        assert_eq!(map.get(0x109).unwrap(), &tlv109(&device)[4..]);
+        assert_eq!(map.get(0x124).unwrap(), &tlv124(&device)[4..]);
+        assert_eq!(map.get(0x128).unwrap(), &tlv128(&device, &guid)[4..]);
+        assert_eq!(map.get(0x16e).unwrap(), &b"MI 6"[..]);
+
+        let report = DeviceReport::decode(map.require(0x52d).unwrap().clone()).unwrap();
+        assert_eq!(report.bootloader, b"U-boot");
+        assert_eq!(report.codename, b"REL");
+        assert_eq!(report.incremental, b"5891938");
+        assert_eq!(report.fingerprint, device.fingerprint().as_bytes());
+    }
+}
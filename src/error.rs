@@ -1,3 +1,6 @@
+use crate::data::oicq::OicqError;
+use crate::data::packet::PacketError;
+use crate::jce::JceError;
 use std::error::Error;
 use std::fmt::{Debug, Display, Formatter};
 
@@ -5,7 +8,20 @@ use std::fmt::{Debug, Display, Formatter};
 pub enum ClientError {
     NotInitialized,
     TokenExpired,
+    /// No response arrived in time.
+    Timeout,
+    /// A uin with no room in the protocol, such as the target of an at above `u32::MAX`.
+    InvalidUin(u64),
+    /// The server answered, but turned the request down.
+    Refused {
+        code: i64,
+        message: String,
+    },
     IO(std::io::Error),
+    Packet(PacketError),
+    Oicq(OicqError),
+    Jce(JceError),
+    Proto(prost::DecodeError),
 }
 
 impl Display for ClientError {
@@ -13,7 +29,16 @@ impl Display for ClientError {
         match self {
             Self::NotInitialized => write!(f, "Client is not initialized"),
             Self::TokenExpired => write!(f, "Token expired"),
+            Self::Timeout => write!(f, "Request timed out"),
+            Self::InvalidUin(uin) => write!(f, "Invalid uin: {}", uin),
+            Self::Refused { code, message } => {
+                write!(f, "Refused by server ({}): {}", code, message)
+            }
             Self::IO(e) => write!(f, "IO Error: {}", e),
+            Self::Packet(e) => write!(f, "Packet Error: {}", e),
+            Self::Oicq(e) => write!(f, "Oicq Error: {}", e),
+            Self::Jce(e) => write!(f, "Jce Error: {}", e),
+            Self::Proto(e) => write!(f, "Protobuf Error: {}", e),
         }
     }
 }
@@ -24,4 +49,31 @@ impl From<std::io::Error> for ClientError {
     }
 }
 
+impl From<PacketError> for ClientError {
+    fn from(e: PacketError) -> Self {
+        match e {
+            PacketError::SessionExpired => Self::TokenExpired,
+            e => Self::Packet(e),
+        }
+    }
+}
+
+impl From<OicqError> for ClientError {
+    fn from(e: OicqError) -> Self {
+        Self::Oicq(e)
+    }
+}
+
+impl From<JceError> for ClientError {
+    fn from(e: JceError) -> Self {
+        Self::Jce(e)
+    }
+}
+
+impl From<prost::DecodeError> for ClientError {
+    fn from(e: prost::DecodeError) -> Self {
+        Self::Proto(e)
+    }
+}
+
 impl Error for ClientError {}
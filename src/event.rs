@@ -1,3 +1,215 @@
-pub enum ClientEvent {
-    Test,
+use crate::client::Client;
+use crate::message::MessageChain;
+use bytes::Bytes;
+use std::error::Error;
+use std::fmt;
+use std::net::SocketAddr;
+use std::time::Duration;
+
+/// Something that happened to a session, handed to the handler of
+/// [`ClientBuilder::with_handler`](crate::client::ClientBuilder::with_handler).
+#[derive(Clone, Debug)]
+pub struct ClientEvent {
+    /// The session it happened to, for acting on it.
+    pub client: Client,
+    pub kind: EventKind,
 }
+
+#[derive(Clone, Debug)]
+pub enum EventKind {
+    GroupMessage(GroupMessage),
+    FriendMessage(FriendMessage),
+    /// A message from a stranger, through a group both are in.
+    TempMessage(TempMessage),
+    MessageRecall(MessageRecall),
+    MemberJoin(MemberJoin),
+    MemberLeave(MemberLeave),
+    MemberMute(MemberMute),
+    GroupNameChange(GroupNameChange),
+    FriendAdd(FriendAdd),
+    FriendDelete(FriendDelete),
+    FriendRequest(FriendRequest),
+    GroupJoinRequest(GroupJoinRequest),
+    Poke(Poke),
+    /// `StatSvc.register` went through, the account is online.
+    Online,
+    /// `StatSvc.ReqMSFOffline`, the server is taking this session offline.
+    Offline(Offline),
+    /// `MessageSvc.PushForceOffline`, the account logged in on another device.
+    Kicked(Kicked),
+    /// An `OnlinePush.*` packet not made into one of the events above.
+    OnlinePush {
+        command: String,
+        body: Bytes,
+    },
+    /// `MessageSvc.PushNotify`, new messages wait to be fetched.
+    MessageNotify {
+        body: Bytes,
+    },
+    /// `ConfigPushSvc.PushReq`, carrying server lists and other configuration.
+    ConfigPush {
+        body: Bytes,
+    },
+    /// The connection broke or stopped answering heartbeats, ending the session.
+    ///
+    /// Not sent for sessions ended by [`Client::stop`].
+    /// A session that [reconnects](crate::client::ClientBuilder::with_reconnect) sends
+    /// it once no server took it back.
+    Disconnected,
+    /// The connection broke and the session is looking for another one.
+    ///
+    /// Requests made meanwhile are sent once it is back.
+    Reconnecting,
+    /// The session is back on `server`, registered again if it was before.
+    Reconnected {
+        server: SocketAddr,
+    },
+    /// The server refused to exchange the tickets in the background, so the session
+    /// ends right after. Only a fresh login brings the account back.
+    TokenExpired,
+}
+
+/// Where a message sits in its conversation, as needed to recall or quote it.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct MessageId {
+    pub seq: i32,
+    pub rand: i32,
+    /// Seconds since the unix epoch.
+    pub time: i32,
+}
+
+#[derive(Clone, Debug)]
+pub struct GroupMessage {
+    pub id: MessageId,
+    pub group: u64,
+    pub group_name: String,
+    pub sender: u64,
+    /// The name of the sender in the group, empty if they set none.
+    pub sender_card: String,
+    pub chain: MessageChain,
+}
+
+#[derive(Clone, Debug)]
+pub struct FriendMessage {
+    pub id: MessageId,
+    pub sender: u64,
+    /// Who it was sent to, another device of this account for messages sent from there.
+    pub target: u64,
+    pub chain: MessageChain,
+}
+
+#[derive(Clone, Debug)]
+pub struct TempMessage {
+    pub id: MessageId,
+    pub group: u64,
+    pub sender: u64,
+    pub chain: MessageChain,
+}
+
+#[derive(Clone, Debug)]
+pub struct MessageRecall {
+    pub id: MessageId,
+    /// The group of the message, `None` for a friend message.
+    pub group: Option<u64>,
+    pub sender: u64,
+    /// Who recalled it, an admin or the sender.
+    pub operator: u64,
+}
+
+#[derive(Clone, Debug)]
+pub struct MemberJoin {
+    pub group: u64,
+    pub member: u64,
+}
+
+#[derive(Clone, Debug)]
+pub struct MemberLeave {
+    pub group: u64,
+    pub member: u64,
+    /// Who kicked them, `None` if they left.
+    pub operator: Option<u64>,
+}
+
+#[derive(Clone, Debug)]
+pub struct MemberMute {
+    pub group: u64,
+    pub operator: u64,
+    /// The member muted, `None` if the whole group is.
+    pub target: Option<u64>,
+    /// How long for, zero to unmute.
+    pub duration: Duration,
+}
+
+#[derive(Clone, Debug)]
+pub struct GroupNameChange {
+    pub group: u64,
+    pub operator: u64,
+    pub name: String,
+}
+
+#[derive(Clone, Debug)]
+pub struct FriendAdd {
+    pub uin: u64,
+    pub nickname: String,
+}
+
+#[derive(Clone, Debug)]
+pub struct FriendDelete {
+    pub uin: u64,
+}
+
+#[derive(Clone, Debug)]
+pub struct FriendRequest {
+    /// Identifies the request when answering it.
+    pub seq: i64,
+    pub uin: u64,
+    pub nickname: String,
+    pub message: String,
+}
+
+#[derive(Clone, Debug)]
+pub struct GroupJoinRequest {
+    /// Identifies the request when answering it.
+    pub seq: i64,
+    pub group: u64,
+    pub uin: u64,
+    pub nickname: String,
+    pub message: String,
+    /// The member who invited them, `None` if they asked to join themselves.
+    pub invitor: Option<u64>,
+}
+
+#[derive(Clone, Debug)]
+pub struct Poke {
+    /// The group it happened in, `None` between friends.
+    pub group: Option<u64>,
+    pub sender: u64,
+    pub target: u64,
+}
+
+#[derive(Clone, Debug)]
+pub struct Offline {
+    pub title: String,
+    pub message: String,
+}
+
+#[derive(Clone, Debug)]
+pub struct Kicked {
+    pub title: String,
+    pub message: String,
+    /// The other login came from this same device.
+    pub same_device: bool,
+}
+
+/// A [subscriber](crate::client::Client::subscribe) fell behind by this many events,
+/// which were dropped to keep the buffer bounded.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Lagged(pub u64);
+
+impl fmt::Display for Lagged {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Lagged behind by {} events", self.0)
+    }
+}
+
+impl Error for Lagged {}
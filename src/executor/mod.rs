@@ -1,55 +1,299 @@
 pub mod runtime;
+pub mod timer;
 
+use futures::channel::oneshot;
+use futures::future::{AbortHandle, Abortable, Aborted};
+use futures::Stream;
 use std::future::Future;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use std::time::{Duration, Instant};
 
 pub trait Executor {
-    fn spawn<F>(&self, fu: F)
+    fn spawn<F>(&self, fu: F) -> JoinHandle<F::Output>
     where
         F: Future + Send,
         F: 'static,
         F::Output: Send + 'static;
 }
 
-impl<E: Executor> Executor for std::rc::Rc<E> {
-    fn spawn<F>(&self, fu: F)
+macro_rules! delegate_executor {
+    ($($t:ty),*) => {
+        $(
+            impl<E: Executor + ?Sized> Executor for $t {
+                #[inline]
+                fn spawn<F>(&self, fu: F) -> JoinHandle<F::Output>
+                where
+                    F: Future + Send,
+                    F: 'static,
+                    F::Output: Send + 'static,
+                {
+                    (**self).spawn(fu)
+                }
+            }
+        )*
+    };
+}
+
+delegate_executor!(std::rc::Rc<E>, std::sync::Arc<E>, &E, Box<E>);
+
+/// A spawned task, the same for every runtime.
+///
+/// Awaiting it gives the output of the task, or [`Aborted`] if the task was aborted
+/// or never finished, such as when it panicked or its runtime shut down.
+/// Dropping it aborts the task, unless it was [detached](JoinHandle::detach).
+#[must_use = "dropping a JoinHandle aborts its task, detach it to let the task run"]
+pub struct JoinHandle<T> {
+    output: oneshot::Receiver<T>,
+    abort: AbortHandle,
+    detached: bool,
+}
+
+impl<T: Send + 'static> JoinHandle<T> {
+    /// Wraps `fu` into the task a runtime runs, and the handle to it.
+    ///
+    /// For implementing [`Executor`], the task is what gets handed to the runtime.
+    pub fn pair<F>(fu: F) -> (impl Future<Output = ()> + Send + 'static, Self)
     where
-        F: Future + Send,
-        F: 'static,
-        F::Output: Send + 'static,
+        F: Future<Output = T> + Send + 'static,
     {
-        (**self).spawn(fu)
+        let (abort, reg) = AbortHandle::new_pair();
+        let (tx, output) = oneshot::channel();
+        let task = async move {
+            if let Ok(out) = Abortable::new(fu, reg).await {
+                let _ = tx.send(out);
+            }
+        };
+
+        let handle = Self {
+            output,
+            abort,
+            detached: false,
+        };
+        (task, handle)
     }
 }
 
-impl<E: Executor> Executor for std::sync::Arc<E> {
-    fn spawn<F>(&self, fu: F)
-    where
-        F: Future + Send,
-        F: 'static,
-        F::Output: Send + 'static,
-    {
-        (**self).spawn(fu)
+impl<T> JoinHandle<T> {
+    /// Stops the task the next time it would be polled.
+    pub fn abort(&self) {
+        self.abort.abort();
+    }
+
+    /// Lets the task run to completion with nobody waiting for it.
+    pub fn detach(mut self) {
+        self.detached = true;
+    }
+
+    /// A handle that aborts the task, and can be kept apart from this one.
+    pub fn abort_handle(&self) -> AbortHandle {
+        self.abort.clone()
     }
 }
 
-impl<E: Executor> Executor for &E {
-    fn spawn<F>(&self, fu: F)
-    where
-        F: Future + Send,
-        F: 'static,
-        F::Output: Send + 'static,
-    {
-        (**self).spawn(fu)
+impl<T> Future for JoinHandle<T> {
+    type Output = Result<T, Aborted>;
+
+    #[inline]
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        Pin::new(&mut self.output).poll(cx).map_err(|_| Aborted)
     }
 }
 
-impl<E: Executor> Executor for Box<E> {
-    fn spawn<F>(&self, fu: F)
+impl<T> Drop for JoinHandle<T> {
+    fn drop(&mut self) {
+        if !self.detached {
+            self.abort.abort();
+        }
+    }
+}
+
+/// Sleeping on the timer of a runtime.
+///
+/// Implemented for every runtime in [`runtime`], those without a timer of their own
+/// fall back to the one in [`timer`].
+pub trait Timer {
+    type Sleep: Future<Output = ()> + Send + 'static;
+
+    fn sleep(&self, duration: Duration) -> Self::Sleep;
+
+    /// Runs `fu`, giving up with `None` once `duration` has passed.
+    #[inline]
+    fn timeout<F: Future>(&self, duration: Duration, fu: F) -> Timeout<F, Self::Sleep> {
+        Timeout::new(fu, self.sleep(duration))
+    }
+
+    /// Ticks every `period`, the first tick one period from now.
+    ///
+    /// Ticks missed by a slow consumer are skipped rather than delivered in a burst.
+    fn interval(&self, period: Duration) -> Interval<&Self>
     where
-        F: Future + Send,
-        F: 'static,
-        F::Output: Send + 'static,
+        Self: Sized,
     {
-        (**self).spawn(fu)
+        Interval {
+            timer: self,
+            period,
+            next: Instant::now() + period,
+            sleep: None,
+        }
+    }
+}
+
+macro_rules! delegate_timer {
+    ($($t:ty),*) => {
+        $(
+            impl<T: Timer + ?Sized> Timer for $t {
+                type Sleep = T::Sleep;
+
+                #[inline]
+                fn sleep(&self, duration: Duration) -> Self::Sleep {
+                    (**self).sleep(duration)
+                }
+            }
+        )*
+    };
+}
+
+delegate_timer!(std::rc::Rc<T>, std::sync::Arc<T>, &T, Box<T>);
+
+/// Future of [`Timer::timeout`].
+pub struct Timeout<F, S> {
+    fu: F,
+    sleep: S,
+}
+
+impl<F, S> Timeout<F, S> {
+    /// Races `fu` against `sleep`.
+    pub fn new(fu: F, sleep: S) -> Self {
+        Self { fu, sleep }
+    }
+}
+
+impl<F, S> Future for Timeout<F, S>
+where
+    F: Future,
+    S: Future<Output = ()>,
+{
+    type Output = Option<F::Output>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        // SAFETY: the fields are only ever used pinned, and never moved out
+        let this = unsafe { self.get_unchecked_mut() };
+        let fu = unsafe { Pin::new_unchecked(&mut this.fu) };
+        if let Poll::Ready(output) = fu.poll(cx) {
+            return Poll::Ready(Some(output));
+        }
+
+        let sleep = unsafe { Pin::new_unchecked(&mut this.sleep) };
+        sleep.poll(cx).map(|()| None)
+    }
+}
+
+/// Stream of [`Timer::interval`].
+pub struct Interval<T: Timer> {
+    timer: T,
+    period: Duration,
+    next: Instant,
+    sleep: Option<Pin<Box<T::Sleep>>>,
+}
+
+// the sleep is boxed, and the timer never pinned
+impl<T: Timer> Unpin for Interval<T> {}
+
+impl<T: Timer> Stream for Interval<T> {
+    type Item = Instant;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Instant>> {
+        let this = &mut *self;
+        let sleep = match &mut this.sleep {
+            Some(sleep) => sleep,
+            None => {
+                let wait = this.next.saturating_duration_since(Instant::now());
+                this.sleep.insert(Box::pin(this.timer.sleep(wait)))
+            }
+        };
+
+        if sleep.as_mut().poll(cx).is_pending() {
+            return Poll::Pending;
+        }
+        this.sleep = None;
+
+        let tick = this.next;
+        let now = Instant::now();
+        this.next += this.period;
+        if this.next <= now {
+            let behind = (now - this.next).as_nanos() / this.period.as_nanos().max(1);
+            this.next += this.period * (behind as u32 + 1);
+        }
+
+        Poll::Ready(Some(tick))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::executor::timer::ThreadTimer;
+    use crate::executor::{JoinHandle, Timer};
+    use futures::future::Aborted;
+    use futures::StreamExt;
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    #[test]
+    fn join_handle() {
+        let (task, handle) = JoinHandle::pair(async { 1 });
+        futures::executor::block_on(task);
+        assert_eq!(futures::executor::block_on(handle), Ok(1));
+
+        let (task, handle) = JoinHandle::pair(futures::future::pending::<()>());
+        handle.abort();
+        futures::executor::block_on(task);
+        assert_eq!(futures::executor::block_on(handle), Err(Aborted));
+
+        // dropping the handle stops the task, detaching it does not
+        let ran = Arc::new(AtomicBool::new(false));
+        let flag = ran.clone();
+        let (task, handle) = JoinHandle::pair(async move { flag.store(true, Ordering::Relaxed) });
+        drop(handle);
+        futures::executor::block_on(task);
+        assert!(!ran.load(Ordering::Relaxed));
+
+        let flag = ran.clone();
+        let (task, handle) = JoinHandle::pair(async move { flag.store(true, Ordering::Relaxed) });
+        handle.detach();
+        futures::executor::block_on(task);
+        assert!(ran.load(Ordering::Relaxed));
+    }
+
+    #[test]
+    fn timeout() {
+        futures::executor::block_on(async {
+            let rsp = ThreadTimer
+                .timeout(Duration::from_secs(5), async { 1 })
+                .await;
+            assert_eq!(rsp, Some(1));
+
+            let pending = futures::future::pending::<()>();
+            let rsp = ThreadTimer
+                .timeout(Duration::from_millis(10), pending)
+                .await;
+            assert_eq!(rsp, None);
+        });
+    }
+
+    #[test]
+    fn interval() {
+        futures::executor::block_on(async {
+            let mut ticks = ThreadTimer.interval(Duration::from_millis(10));
+            let first = ticks.next().await.unwrap();
+            std::thread::sleep(Duration::from_millis(35));
+
+            // the late tick comes at once, the one after it is back on the schedule
+            let late = ticks.next().await.unwrap();
+            assert_eq!(late - first, Duration::from_millis(10));
+            let next = ticks.next().await.unwrap();
+            assert_eq!(next - first, Duration::from_millis(40));
+        });
     }
 }
@@ -1,109 +1,189 @@
 #[cfg(feature = "tokio")]
 pub mod tokio {
-    use crate::executor::Executor;
+    use crate::executor::{Executor, JoinHandle, Timer};
     use std::future::Future;
+    use std::time::Duration;
     pub use tokio::runtime::Runtime;
+    use tokio::time::Sleep;
 
     impl Executor for Runtime {
-        fn spawn<F>(&self, fu: F)
+        fn spawn<F>(&self, fu: F) -> JoinHandle<F::Output>
         where
             F: Future + Send,
             F: 'static,
             F::Output: Send + 'static,
         {
-            (*self).spawn(fu);
+            let (task, handle) = JoinHandle::pair(fu);
+            (*self).spawn(task);
+            handle
+        }
+    }
+
+    /// Needs the runtime built with its time driver enabled.
+    impl Timer for Runtime {
+        type Sleep = Sleep;
+
+        fn sleep(&self, duration: Duration) -> Sleep {
+            let _guard = self.enter();
+            tokio::time::sleep(duration)
         }
     }
 }
 
 #[cfg(feature = "async-std")]
 pub mod async_std {
-    use crate::executor::Executor;
+    use crate::executor::{Executor, JoinHandle, Timer};
+    use futures::future::BoxFuture;
+    use futures::FutureExt;
     use std::future::Future;
+    use std::time::Duration;
 
     pub struct Runtime;
 
     impl Executor for Runtime {
-        fn spawn<F>(&self, fu: F)
+        fn spawn<F>(&self, fu: F) -> JoinHandle<F::Output>
         where
             F: Future + Send,
             F: 'static,
             F::Output: Send + 'static,
         {
-            async_std::task::spawn(fu);
+            let (task, handle) = JoinHandle::pair(fu);
+            async_std::task::spawn(task);
+            handle
+        }
+    }
+
+    impl Timer for Runtime {
+        type Sleep = BoxFuture<'static, ()>;
+
+        fn sleep(&self, duration: Duration) -> Self::Sleep {
+            async_std::task::sleep(duration).boxed()
         }
     }
 }
 
 #[cfg(feature = "smol")]
 pub mod smol {
-    use crate::executor::Executor;
+    use crate::executor::{Executor, JoinHandle, Timer};
+    use futures::future::Map;
+    use futures::FutureExt;
+    use smol::Timer as SmolTimer;
     use std::future::Future;
+    use std::time::{Duration, Instant};
+
+    type Sleep = Map<SmolTimer, fn(Instant)>;
+
+    fn sleep(duration: Duration) -> Sleep {
+        SmolTimer::after(duration).map(drop as fn(Instant))
+    }
 
     pub struct Runtime;
 
     impl Executor for Runtime {
-        fn spawn<F>(&self, fu: F)
+        fn spawn<F>(&self, fu: F) -> JoinHandle<F::Output>
         where
             F: Future + Send,
             F: 'static,
             F::Output: Send + 'static,
         {
-            smol::spawn(fu).detach();
+            let (task, handle) = JoinHandle::pair(fu);
+            smol::spawn(task).detach();
+            handle
+        }
+    }
+
+    impl Timer for Runtime {
+        type Sleep = Sleep;
+
+        #[inline]
+        fn sleep(&self, duration: Duration) -> Sleep {
+            sleep(duration)
         }
     }
 
     impl Executor for smol::Executor<'_> {
-        fn spawn<F>(&self, fu: F)
+        fn spawn<F>(&self, fu: F) -> JoinHandle<F::Output>
         where
             F: Future + Send,
             F: 'static,
             F::Output: Send + 'static,
         {
-            (*self).spawn(fu).detach();
+            let (task, handle) = JoinHandle::pair(fu);
+            (*self).spawn(task).detach();
+            handle
+        }
+    }
+
+    impl Timer for smol::Executor<'_> {
+        type Sleep = Sleep;
+
+        #[inline]
+        fn sleep(&self, duration: Duration) -> Sleep {
+            sleep(duration)
         }
     }
 }
 
 #[cfg(feature = "blocking")]
 pub mod blocking {
-    use crate::executor::Executor;
+    use crate::executor::timer::{self, Delay};
+    use crate::executor::{Executor, JoinHandle, Timer};
     use std::future::Future;
     use std::thread;
+    use std::time::Duration;
 
     pub struct Runtime;
 
     impl Executor for Runtime {
-        fn spawn<F>(&self, fu: F)
+        fn spawn<F>(&self, fu: F) -> JoinHandle<F::Output>
         where
             F: Future + Send,
             F: 'static,
             F::Output: Send + 'static,
         {
-            thread::spawn(move || {
-                futures::executor::block_on(fu);
-            });
+            let (task, handle) = JoinHandle::pair(fu);
+            thread::spawn(move || futures::executor::block_on(task));
+            handle
+        }
+    }
+
+    impl Timer for Runtime {
+        type Sleep = Delay;
+
+        #[inline]
+        fn sleep(&self, duration: Duration) -> Delay {
+            timer::sleep(duration)
         }
     }
 }
 
 #[cfg(feature = "thread-pool")]
 pub mod thread_pool {
-    use crate::executor::Executor;
+    use crate::executor::timer::{self, Delay};
+    use crate::executor::{Executor, JoinHandle, Timer};
     use futures::executor::ThreadPool;
     use std::future::Future;
+    use std::time::Duration;
 
     impl Executor for ThreadPool {
-        fn spawn<F>(&self, fu: F)
+        fn spawn<F>(&self, fu: F) -> JoinHandle<F::Output>
         where
             F: Future + Send,
             F: 'static,
             F::Output: Send + 'static,
         {
-            let fu = async {
-                fu.await;
-            };
-            (*self).spawn_ok(fu);
+            let (task, handle) = JoinHandle::pair(fu);
+            (*self).spawn_ok(task);
+            handle
+        }
+    }
+
+    impl Timer for ThreadPool {
+        type Sleep = Delay;
+
+        #[inline]
+        fn sleep(&self, duration: Duration) -> Delay {
+            timer::sleep(duration)
         }
     }
 }
@@ -0,0 +1,193 @@
+//! A timer independent of any runtime, driven by a single background thread.
+
+use crate::executor::Timer;
+use std::cmp::Ordering;
+use std::collections::BinaryHeap;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicBool, Ordering as AtomicOrdering};
+use std::sync::{Arc, Condvar, Mutex, OnceLock};
+use std::task::{Context, Poll, Waker};
+use std::thread;
+use std::time::{Duration, Instant};
+
+#[derive(Default)]
+struct State {
+    fired: AtomicBool,
+    waker: Mutex<Option<Waker>>,
+}
+
+impl State {
+    fn fire(&self) {
+        self.fired.store(true, AtomicOrdering::Release);
+        if let Some(waker) = lock(&self.waker).take() {
+            waker.wake();
+        }
+    }
+}
+
+struct Entry {
+    deadline: Instant,
+    state: Arc<State>,
+}
+
+impl PartialEq for Entry {
+    fn eq(&self, other: &Self) -> bool {
+        self.deadline == other.deadline
+    }
+}
+
+impl Eq for Entry {}
+
+impl PartialOrd for Entry {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Entry {
+    // reversed, so the heap pops the earliest deadline first
+    fn cmp(&self, other: &Self) -> Ordering {
+        other.deadline.cmp(&self.deadline)
+    }
+}
+
+#[derive(Default)]
+struct Wheel {
+    entries: Mutex<BinaryHeap<Entry>>,
+    cond: Condvar,
+}
+
+fn lock<T>(m: &Mutex<T>) -> std::sync::MutexGuard<'_, T> {
+    m.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+fn wheel() -> &'static Wheel {
+    static WHEEL: OnceLock<&'static Wheel> = OnceLock::new();
+
+    WHEEL.get_or_init(|| {
+        let wheel: &'static Wheel = Box::leak(Box::default());
+        thread::Builder::new()
+            .name("atri-timer".into())
+            .spawn(move || run(wheel))
+            .expect("failed to spawn the timer thread");
+        wheel
+    })
+}
+
+fn run(wheel: &Wheel) {
+    let mut entries = lock(&wheel.entries);
+    loop {
+        let now = Instant::now();
+        while entries.peek().is_some_and(|e| e.deadline <= now) {
+            if let Some(e) = entries.pop() {
+                e.state.fire();
+            }
+        }
+
+        entries = match entries.peek() {
+            Some(e) => {
+                let wait = e.deadline - now;
+                wheel
+                    .cond
+                    .wait_timeout(entries, wait)
+                    .unwrap_or_else(|e| e.into_inner())
+                    .0
+            }
+            None => wheel.cond.wait(entries).unwrap_or_else(|e| e.into_inner()),
+        };
+    }
+}
+
+/// Completes once its deadline has passed.
+pub struct Delay {
+    state: Arc<State>,
+}
+
+impl Future for Delay {
+    type Output = ();
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        if self.state.fired.load(AtomicOrdering::Acquire) {
+            return Poll::Ready(());
+        }
+
+        *lock(&self.state.waker) = Some(cx.waker().clone());
+
+        // it may have fired before the waker was in place
+        if self.state.fired.load(AtomicOrdering::Acquire) {
+            Poll::Ready(())
+        } else {
+            Poll::Pending
+        }
+    }
+}
+
+impl Drop for Delay {
+    fn drop(&mut self) {
+        // a cancelled delay leaves the heap now rather than at its deadline
+        if !self.state.fired.load(AtomicOrdering::Acquire) {
+            lock(&wheel().entries).retain(|e| !Arc::ptr_eq(&e.state, &self.state));
+        }
+    }
+}
+
+pub fn sleep(duration: Duration) -> Delay {
+    let state = Arc::new(State::default());
+    let wheel = wheel();
+
+    lock(&wheel.entries).push(Entry {
+        deadline: Instant::now() + duration,
+        state: state.clone(),
+    });
+    wheel.cond.notify_one();
+
+    Delay { state }
+}
+
+/// The timer of runtimes that have none, such as [`blocking`](crate::executor::runtime::blocking).
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ThreadTimer;
+
+impl Timer for ThreadTimer {
+    type Sleep = Delay;
+
+    #[inline]
+    fn sleep(&self, duration: Duration) -> Delay {
+        sleep(duration)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::executor::timer::{lock, sleep, wheel};
+    use std::sync::Arc;
+    use std::time::{Duration, Instant};
+
+    #[test]
+    fn delay() {
+        let start = Instant::now();
+        futures::executor::block_on(async {
+            futures::join!(
+                sleep(Duration::from_millis(30)),
+                sleep(Duration::from_millis(10))
+            );
+        });
+        assert!(start.elapsed() >= Duration::from_millis(30));
+    }
+
+    #[test]
+    fn cancel() {
+        let delay = sleep(Duration::from_secs(60));
+        let state = delay.state.clone();
+        let queued = || {
+            lock(&wheel().entries)
+                .iter()
+                .any(|e| Arc::ptr_eq(&e.state, &state))
+        };
+        assert!(queued());
+
+        drop(delay);
+        assert!(!queued());
+    }
+}
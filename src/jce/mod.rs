@@ -0,0 +1,474 @@
+//! JCE (Tars), the tagged encoding spoken by the older services such as `StatSvc.register`.
+//!
+//! Every field starts with a head holding its tag and type, `tag << 4 | type`,
+//! with tags above 14 moved to a second byte. Structs are defined with [`jce_struct!`](crate::jce_struct),
+//! much like the prost messages of the protobuf services:
+//!
+//! ```
+//! use atri_core::jce::JceStruct;
+//!
+//! atri_core::jce_struct! {
+//!     #[derive(Debug, Default, PartialEq)]
+//!     pub struct SsoServerInfo {
+//!         pub server: String = 1,
+//!         pub port: i32 = 2,
+//!     }
+//! }
+//!
+//! let info = SsoServerInfo { server: "10.0.0.1".into(), port: 8080 };
+//! assert_eq!(SsoServerInfo::from_bytes(info.to_bytes()).unwrap(), info);
+//! ```
+
+mod packet;
+mod reader;
+mod writer;
+
+pub use packet::{RequestPacket, UniPacket};
+pub use reader::JceReader;
+pub use writer::JceWriter;
+
+use bytes::Bytes;
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt::{Debug, Display, Formatter};
+use std::hash::Hash;
+
+const INT8: u8 = 0;
+const INT16: u8 = 1;
+const INT32: u8 = 2;
+const INT64: u8 = 3;
+const FLOAT: u8 = 4;
+const DOUBLE: u8 = 5;
+const STRING1: u8 = 6;
+const STRING4: u8 = 7;
+const MAP: u8 = 8;
+const LIST: u8 = 9;
+const STRUCT_BEGIN: u8 = 10;
+const STRUCT_END: u8 = 11;
+const ZERO: u8 = 12;
+const SIMPLE_LIST: u8 = 13;
+
+/// A value that can be a field of a JCE struct.
+///
+/// Integers take any width on the wire as long as the value fits, [`Bytes`] is a simple
+/// list, [`Vec`] a list and [`HashMap`] a map. A missing field reads as the default.
+pub trait JceType: Default {
+    /// Writes the value as the field `tag`.
+    fn write(&self, w: &mut JceWriter, tag: u8);
+
+    /// Reads the value of a field of type `ty`, whose head was just read.
+    fn read(r: &mut JceReader, ty: u8) -> Result<Self, JceError>;
+}
+
+/// A struct of tagged fields, usually defined with [`jce_struct!`](crate::jce_struct).
+pub trait JceStruct: Sized {
+    fn write_fields(&self, w: &mut JceWriter);
+
+    fn read_fields(r: &mut JceReader) -> Result<Self, JceError>;
+
+    /// Encodes the fields, without a struct head around them.
+    fn to_bytes(&self) -> Bytes {
+        let mut w = JceWriter::new();
+        self.write_fields(&mut w);
+        w.into_bytes()
+    }
+
+    fn from_bytes(buf: Bytes) -> Result<Self, JceError> {
+        Self::read_fields(&mut JceReader::new(buf))
+    }
+}
+
+/// Defines a struct of JCE fields, each followed by its tag.
+///
+/// Fields have to be listed in ascending tag order, and the struct has to implement
+/// [`Default`] for it to be a field itself.
+#[macro_export]
+macro_rules! jce_struct {
+    (
+        $(#[$meta:meta])*
+        $vis:vis struct $name:ident {
+            $(
+                $(#[$field_meta:meta])*
+                $field_vis:vis $field:ident: $ty:ty = $tag:literal
+            ),* $(,)?
+        }
+    ) => {
+        $(#[$meta])*
+        $vis struct $name {
+            $(
+                $(#[$field_meta])*
+                $field_vis $field: $ty,
+            )*
+        }
+
+        impl $crate::jce::JceStruct for $name {
+            fn write_fields(&self, w: &mut $crate::jce::JceWriter) {
+                $( w.put($tag, &self.$field); )*
+            }
+
+            fn read_fields(
+                r: &mut $crate::jce::JceReader,
+            ) -> ::std::result::Result<Self, $crate::jce::JceError> {
+                ::std::result::Result::Ok(Self {
+                    $( $field: r.get($tag)?, )*
+                })
+            }
+        }
+
+        impl $crate::jce::JceType for $name {
+            fn write(&self, w: &mut $crate::jce::JceWriter, tag: u8) {
+                w.put_struct(tag, |w| $crate::jce::JceStruct::write_fields(self, w));
+            }
+
+            fn read(
+                r: &mut $crate::jce::JceReader,
+                ty: u8,
+            ) -> ::std::result::Result<Self, $crate::jce::JceError> {
+                let mut fields = <$crate::jce::JceReader as $crate::jce::JceType>::read(r, ty)?;
+                $crate::jce::JceStruct::read_fields(&mut fields)
+            }
+        }
+    };
+}
+
+macro_rules! impl_int {
+    ($($t:ty),*) => {
+        $(
+            impl JceType for $t {
+                #[inline]
+                fn write(&self, w: &mut JceWriter, tag: u8) {
+                    w.put_int(tag, *self as i64);
+                }
+
+                fn read(r: &mut JceReader, ty: u8) -> Result<Self, JceError> {
+                    let value = r.read_int_of(ty)?;
+                    <$t>::try_from(value).map_err(|_| JceError::TypeMismatch(ty))
+                }
+            }
+        )*
+    };
+}
+
+// unsigned types are written one width up, as Tars has no unsigned integers
+impl_int!(i8, i16, i32, i64, u8, u16, u32);
+
+/// Written as `int64`, as uins are.
+impl JceType for u64 {
+    #[inline]
+    fn write(&self, w: &mut JceWriter, tag: u8) {
+        w.put_int(tag, *self as i64);
+    }
+
+    #[inline]
+    fn read(r: &mut JceReader, ty: u8) -> Result<Self, JceError> {
+        Ok(r.read_int_of(ty)? as u64)
+    }
+}
+
+impl JceType for bool {
+    #[inline]
+    fn write(&self, w: &mut JceWriter, tag: u8) {
+        w.put_int(tag, *self as i64);
+    }
+
+    #[inline]
+    fn read(r: &mut JceReader, ty: u8) -> Result<Self, JceError> {
+        Ok(r.read_int_of(ty)? != 0)
+    }
+}
+
+impl JceType for f32 {
+    #[inline]
+    fn write(&self, w: &mut JceWriter, tag: u8) {
+        w.put_float(tag, *self);
+    }
+
+    #[inline]
+    fn read(r: &mut JceReader, ty: u8) -> Result<Self, JceError> {
+        Ok(r.read_double_of(ty)? as f32)
+    }
+}
+
+impl JceType for f64 {
+    #[inline]
+    fn write(&self, w: &mut JceWriter, tag: u8) {
+        w.put_double(tag, *self);
+    }
+
+    #[inline]
+    fn read(r: &mut JceReader, ty: u8) -> Result<Self, JceError> {
+        r.read_double_of(ty)
+    }
+}
+
+impl JceType for String {
+    #[inline]
+    fn write(&self, w: &mut JceWriter, tag: u8) {
+        w.put_string(tag, self);
+    }
+
+    #[inline]
+    fn read(r: &mut JceReader, ty: u8) -> Result<Self, JceError> {
+        r.read_string_of(ty)
+    }
+}
+
+impl JceType for Bytes {
+    #[inline]
+    fn write(&self, w: &mut JceWriter, tag: u8) {
+        w.put_bytes(tag, self);
+    }
+
+    #[inline]
+    fn read(r: &mut JceReader, ty: u8) -> Result<Self, JceError> {
+        r.read_bytes_of(ty)
+    }
+}
+
+/// A struct read as is, its fields left to read by tag.
+impl JceType for JceReader {
+    #[inline]
+    fn write(&self, w: &mut JceWriter, tag: u8) {
+        w.put_struct(tag, |w| w.put_raw(self.remaining()));
+    }
+
+    #[inline]
+    fn read(r: &mut JceReader, ty: u8) -> Result<Self, JceError> {
+        r.read_struct_of(ty)
+    }
+}
+
+/// Left out when `None`.
+impl<T: JceType> JceType for Option<T> {
+    #[inline]
+    fn write(&self, w: &mut JceWriter, tag: u8) {
+        if let Some(value) = self {
+            value.write(w, tag);
+        }
+    }
+
+    #[inline]
+    fn read(r: &mut JceReader, ty: u8) -> Result<Self, JceError> {
+        T::read(r, ty).map(Some)
+    }
+}
+
+impl<T: JceType> JceType for Vec<T> {
+    fn write(&self, w: &mut JceWriter, tag: u8) {
+        w.put_list(tag, self, |w, item| item.write(w, 0));
+    }
+
+    fn read(r: &mut JceReader, ty: u8) -> Result<Self, JceError> {
+        let len = r.read_len_of(ty, LIST)?;
+        let mut list = Vec::with_capacity(len.min(64));
+        for _ in 0..len {
+            let (_, ty) = r.read_head()?;
+            list.push(T::read(r, ty)?);
+        }
+        Ok(list)
+    }
+}
+
+impl<K, V> JceType for HashMap<K, V>
+where
+    K: JceType + Eq + Hash,
+    V: JceType,
+{
+    fn write(&self, w: &mut JceWriter, tag: u8) {
+        w.put_map(tag, self, |w, (key, value)| {
+            key.write(w, 0);
+            value.write(w, 1);
+        });
+    }
+
+    fn read(r: &mut JceReader, ty: u8) -> Result<Self, JceError> {
+        let len = r.read_len_of(ty, MAP)?;
+        let mut map = HashMap::with_capacity(len.min(64));
+        for _ in 0..len {
+            let (_, ty) = r.read_head()?;
+            let key = K::read(r, ty)?;
+            let (_, ty) = r.read_head()?;
+            map.insert(key, V::read(r, ty)?);
+        }
+        Ok(map)
+    }
+}
+
+#[derive(Debug)]
+pub enum JceError {
+    Truncated,
+    UnknownType(u8),
+    /// The field holds a type other than the one asked for, or a number too large for it.
+    TypeMismatch(u8),
+    /// A `RequestPacket` without the struct asked for.
+    Missing(String),
+}
+
+impl Display for JceError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Truncated => write!(f, "Jce data is truncated"),
+            Self::UnknownType(ty) => write!(f, "Unknown jce type: {}", ty),
+            Self::TypeMismatch(ty) => write!(f, "Unexpected jce type: {}", ty),
+            Self::Missing(name) => write!(f, "Missing jce struct: {}", name),
+        }
+    }
+}
+
+impl Error for JceError {}
+
+#[cfg(test)]
+mod tests {
+    use crate::jce::{JceError, JceReader, JceStruct, JceWriter};
+    use bytes::Bytes;
+    use std::collections::HashMap;
+
+    #[test]
+    fn encode() {
+        let mut w = JceWriter::new();
+        w.put_int(0, 0);
+        w.put_int(1, 1);
+        w.put_int(2, 300);
+        w.put_int(3, 1 << 20);
+        w.put_int(15, 1 << 40);
+        w.put_string(4, "ab");
+        w.put_bytes(5, &[9, 9]);
+        w.put_struct(6, |w| w.put_int(0, -1));
+        w.put_float(7, 1.5);
+        w.put_list(8, [1u8, 2], |w, i| w.put_int(0, i as i64));
+
+        assert_eq!(
+            &w.into_bytes()[..],
+            [
+                0x0C, // 0: zero
+                0x10, 1, // 1: int8
+                0x21, 0x01, 0x2C, // 2: int16
+                0x32, 0, 0x10, 0, 0, // 3: int32
+                0xF3, 15, 0, 0, 1, 0, 0, 0, 0, 0, // 15: int64
+                0x46, 2, b'a', b'b', // 4: string1
+                0x5D, 0x00, 0x00, 2, 9, 9, // 5: simple list
+                0x6A, 0x00, 0xFF, 0x0B, // 6: struct
+                0x74, 0x3F, 0xC0, 0, 0, // 7: float
+                0x89, 0x00, 2, 0x00, 1, 0x00, 2, // 8: list
+            ]
+        );
+    }
+
+    #[test]
+    fn decode() {
+        let mut w = JceWriter::new();
+        w.put_int(0, 7);
+        w.put_string(1, "skipped");
+        w.put_struct(2, |w| {
+            w.put_bytes_map(0, [("k", &b"v"[..])]);
+            w.put_int(1, 1 << 40);
+        });
+        w.put_string(3, &"x".repeat(300));
+        w.put_int(20, -5);
+
+        let mut r = JceReader::new(w.into_bytes());
+        assert_eq!(r.get_int(0).unwrap(), 7);
+
+        let mut inner = r.get_struct(2).unwrap();
+        let map = inner.get_bytes_map(0).unwrap();
+        assert_eq!(&map["k"][..], b"v");
+        assert_eq!(inner.get_int(1).unwrap(), 1 << 40);
+        assert_eq!(inner.get_int(2).unwrap(), 0);
+
+        assert_eq!(r.get_string(3).unwrap().len(), 300);
+        assert_eq!(r.get_bytes(10).unwrap(), Bytes::new());
+        assert_eq!(r.get_int(20).unwrap(), -5);
+
+        let mut w = JceWriter::new();
+        w.put_struct_list(1, &[1, 2], |w, &i| w.put_int(0, i));
+        w.put_int(2, 9);
+        let mut r = JceReader::new(w.into_bytes());
+        let list = r.get_struct_list(1).unwrap();
+        let items: Vec<_> = list
+            .into_iter()
+            .map(|mut r| r.get_int(0).unwrap())
+            .collect();
+        assert_eq!(items, [1, 2]);
+        assert_eq!(r.get_int(2).unwrap(), 9);
+        assert!(r.get_struct_list(3).unwrap().is_empty());
+
+        let mut r = JceReader::new(Bytes::from_static(&[0x16, 3, b'a']));
+        assert!(matches!(r.get_int(1), Err(JceError::TypeMismatch(6))));
+        let mut r = JceReader::new(Bytes::from_static(&[0x16, 3, b'a']));
+        assert!(matches!(r.get_string(1), Err(JceError::Truncated)));
+        let mut r = JceReader::new(Bytes::from_static(&[0x11, 0x01, 0x2C]));
+        assert!(matches!(r.get::<i8>(1), Err(JceError::TypeMismatch(1))));
+    }
+
+    crate::jce_struct! {
+        #[derive(Clone, Debug, Default, PartialEq)]
+        struct Inner {
+            id: u64 = 0,
+            name: String = 1,
+        }
+    }
+
+    crate::jce_struct! {
+        /// Every kind of field.
+        #[derive(Debug, Default, PartialEq)]
+        struct Everything {
+            byte: i8 = 0,
+            short: i16 = 1,
+            int: i32 = 2,
+            long: i64 = 3,
+            float: f32 = 4,
+            double: f64 = 5,
+            flag: bool = 6,
+            string: String = 7,
+            bytes: Bytes = 8,
+            inner: Inner = 9,
+            list: Vec<Inner> = 10,
+            map: HashMap<String, Vec<i32>> = 11,
+            absent: Option<u32> = 12,
+            present: Option<u32> = 13,
+            unsigned: u16 = 14,
+            far: u8 = 200,
+        }
+    }
+
+    #[test]
+    fn structs() {
+        let inner = Inner {
+            id: 10003,
+            name: "a".into(),
+        };
+        let value = Everything {
+            byte: -1,
+            short: 300,
+            int: 1 << 20,
+            long: -(1 << 40),
+            float: 0.5,
+            double: -2.25,
+            flag: true,
+            string: "s".repeat(256),
+            bytes: Bytes::from_static(b"raw"),
+            inner: inner.clone(),
+            list: vec![inner.clone(), Inner::default()],
+            map: HashMap::from([("k".into(), vec![1, -1])]),
+            absent: None,
+            present: Some(u32::MAX),
+            unsigned: u16::MAX,
+            far: 255,
+        };
+
+        let buf = value.to_bytes();
+        assert_eq!(Everything::from_bytes(buf.clone()).unwrap(), value);
+
+        // fields are read by tag, whatever struct wrote them
+        let mut r = JceReader::new(buf);
+        assert_eq!(r.get::<i64>(2).unwrap(), 1 << 20);
+        assert_eq!(r.get::<Inner>(9).unwrap(), inner);
+        assert_eq!(r.get::<u32>(12).unwrap(), 0);
+        assert_eq!(r.get::<i64>(13).unwrap(), u32::MAX as i64);
+
+        assert_eq!(
+            Everything::from_bytes(Bytes::new()).unwrap(),
+            Everything::default()
+        );
+    }
+}
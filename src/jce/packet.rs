@@ -0,0 +1,167 @@
+use crate::jce::{JceError, JceReader, JceStruct, JceType, JceWriter};
+use bytes::{Buf, BufMut, Bytes, BytesMut};
+use std::collections::HashMap;
+
+crate::jce_struct! {
+    /// The envelope of every call to a JCE service, and of its response.
+    #[derive(Clone, Debug, Default, PartialEq)]
+    pub struct RequestPacket {
+        pub version: i16 = 1,
+        pub packet_type: i8 = 2,
+        pub message_type: i32 = 3,
+        pub request_id: i32 = 4,
+        pub servant: String = 5,
+        pub func: String = 6,
+        pub buffer: Bytes = 7,
+        pub timeout: i32 = 8,
+        pub context: HashMap<String, String> = 9,
+        pub status: HashMap<String, String> = 10,
+    }
+}
+
+/// A [`RequestPacket`] whose buffer carries structs by name, as the services expect.
+///
+/// Encoded as version 3. Version 2, which files every struct under its type name as
+/// well, can be decoded too.
+#[derive(Clone, Debug, Default)]
+pub struct UniPacket {
+    pub packet: RequestPacket,
+    data: HashMap<String, Bytes>,
+}
+
+impl UniPacket {
+    /// A call to `func` of `servant`.
+    pub fn new(servant: &str, func: &str) -> Self {
+        Self {
+            packet: RequestPacket {
+                version: 3,
+                servant: servant.into(),
+                func: func.into(),
+                ..RequestPacket::default()
+            },
+            data: HashMap::new(),
+        }
+    }
+
+    /// Adds `value` under `name`.
+    pub fn put<T: JceType>(&mut self, name: &str, value: &T) {
+        let mut w = JceWriter::new();
+        value.write(&mut w, 0);
+        self.data.insert(name.into(), w.into_bytes());
+    }
+
+    /// Reads the value under `name`.
+    pub fn get<T: JceType>(&self, name: &str) -> Result<T, JceError> {
+        let data = self
+            .data
+            .get(name)
+            .ok_or_else(|| JceError::Missing(name.into()))?;
+        JceReader::new(data.clone()).get(0)
+    }
+
+    /// Encodes the packet with the `u32` length the services expect in front of it.
+    pub fn encode(&self) -> Bytes {
+        let mut buffer = JceWriter::new();
+        buffer.put(0, &self.data);
+        let packet = RequestPacket {
+            buffer: buffer.into_bytes(),
+            ..self.packet.clone()
+        };
+        let packet = packet.to_bytes();
+
+        let mut buf = BytesMut::with_capacity(packet.len() + 4);
+        buf.put_u32(packet.len() as u32 + 4);
+        buf.put_slice(&packet);
+        buf.freeze()
+    }
+
+    /// Decodes a packet, with its `u32` length in front.
+    pub fn decode(mut buf: Bytes) -> Result<Self, JceError> {
+        if buf.len() < 4 {
+            return Err(JceError::Truncated);
+        }
+        buf.advance(4);
+
+        let packet = RequestPacket::from_bytes(buf)?;
+        let mut buffer = JceReader::new(packet.buffer.clone());
+        let data = match packet.version {
+            2 => buffer
+                .get::<HashMap<String, HashMap<String, Bytes>>>(0)?
+                .into_iter()
+                .filter_map(|(name, types)| Some((name, types.into_values().next()?)))
+                .collect(),
+            _ => buffer.get(0)?,
+        };
+
+        Ok(Self { packet, data })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::jce::{JceError, JceReader, JceWriter, UniPacket, MAP};
+    use std::collections::HashMap;
+
+    #[test]
+    fn uni() {
+        let mut body = JceWriter::new();
+        body.put_int(0, 10003);
+        body.put_string(1, "ok");
+
+        let mut pkt = UniPacket::new("PushService", "SvcReqRegister");
+        pkt.put("SvcReqRegister", &JceReader::new(body.into_bytes()));
+        pkt.put("Extra", &vec![1, 2, 3]);
+        let buf = pkt.encode();
+        assert_eq!(&buf[..4], (buf.len() as u32).to_be_bytes());
+
+        let mut r = JceReader::new(buf.slice(4..));
+        assert_eq!(r.get_int(1).unwrap(), 3);
+        assert_eq!(r.get_string(5).unwrap(), "PushService");
+        assert_eq!(r.get_string(6).unwrap(), "SvcReqRegister");
+
+        let pkt = UniPacket::decode(buf).unwrap();
+        assert_eq!(pkt.packet.func, "SvcReqRegister");
+        let mut r: JceReader = pkt.get("SvcReqRegister").unwrap();
+        assert_eq!(r.get_int(0).unwrap(), 10003);
+        assert_eq!(r.get_string(1).unwrap(), "ok");
+        assert_eq!(pkt.get::<Vec<i32>>("Extra").unwrap(), [1, 2, 3]);
+
+        assert!(matches!(
+            pkt.get::<JceReader>("Other"),
+            Err(JceError::Missing(_))
+        ));
+        assert!(matches!(
+            UniPacket::decode(Default::default()),
+            Err(JceError::Truncated)
+        ));
+    }
+
+    #[test]
+    fn uni_v2() {
+        let mut data = JceWriter::new();
+        data.put_struct(0, |w| w.put_int(1, 1));
+        let data = data.into_bytes();
+
+        let mut map = JceWriter::new();
+        map.put_head(0, MAP);
+        map.put_int(0, 1);
+        map.put_string(0, "PushReq");
+        map.put_bytes_map(1, [("ConfigPush.PushReq", &data[..])]);
+        let map = map.into_bytes();
+
+        let mut w = JceWriter::new();
+        w.put_int(1, 2);
+        w.put_string(5, "QQService.ConfigPushSvc.MainServant");
+        w.put_string(6, "PushReq");
+        w.put_bytes(7, &map);
+        let body = w.into_bytes();
+        let mut buf = (body.len() as u32 + 4).to_be_bytes().to_vec();
+        buf.extend_from_slice(&body);
+
+        let pkt = UniPacket::decode(buf.into()).unwrap();
+        assert_eq!(pkt.packet.version, 2);
+        let mut r: JceReader = pkt.get("PushReq").unwrap();
+        assert_eq!(r.get_int(1).unwrap(), 1);
+        assert!(pkt.get::<HashMap<String, i32>>("Other").is_err());
+    }
+}
@@ -0,0 +1,221 @@
+use crate::jce::{
+    JceError, JceType, DOUBLE, FLOAT, INT16, INT32, INT64, INT8, LIST, MAP, SIMPLE_LIST, STRING1,
+    STRING4, STRUCT_BEGIN, STRUCT_END, ZERO,
+};
+use bytes::{Buf, Bytes};
+use std::collections::HashMap;
+
+/// Reads the fields of a struct by tag, without copying.
+///
+/// Fields are looked up in ascending tag order, skipping whatever lies in between.
+/// A missing field reads as its default value, as JCE leaves those out.
+#[derive(Clone, Debug, Default)]
+pub struct JceReader {
+    buf: Bytes,
+}
+
+impl JceReader {
+    #[inline]
+    pub fn new(buf: Bytes) -> Self {
+        Self { buf }
+    }
+
+    /// The fields not read yet.
+    #[inline]
+    pub fn remaining(&self) -> &Bytes {
+        &self.buf
+    }
+
+    fn take(&mut self, len: usize) -> Result<Bytes, JceError> {
+        if self.buf.len() < len {
+            return Err(JceError::Truncated);
+        }
+        Ok(self.buf.split_to(len))
+    }
+
+    fn peek_head(&self) -> Result<Option<(u8, u8)>, JceError> {
+        let Some(&first) = self.buf.first() else {
+            return Ok(None);
+        };
+
+        let ty = first & 0x0F;
+        match first >> 4 {
+            15 => match self.buf.get(1) {
+                Some(&tag) => Ok(Some((tag, ty))),
+                None => Err(JceError::Truncated),
+            },
+            tag => Ok(Some((tag, ty))),
+        }
+    }
+
+    pub(crate) fn read_head(&mut self) -> Result<(u8, u8), JceError> {
+        let (tag, ty) = self.peek_head()?.ok_or(JceError::Truncated)?;
+        self.buf.advance(if tag < 15 { 1 } else { 2 });
+        Ok((tag, ty))
+    }
+
+    /// Moves to the field with `tag`, returning its type, or `None` if the struct has none.
+    fn seek(&mut self, tag: u8) -> Result<Option<u8>, JceError> {
+        loop {
+            match self.peek_head()? {
+                None | Some((_, STRUCT_END)) => return Ok(None),
+                Some((t, _)) if t > tag => return Ok(None),
+                Some((t, _)) => {
+                    let (_, ty) = self.read_head()?;
+                    if t == tag {
+                        return Ok(Some(ty));
+                    }
+                    self.skip(ty)?;
+                }
+            }
+        }
+    }
+
+    fn skip(&mut self, ty: u8) -> Result<(), JceError> {
+        let len = match ty {
+            INT8 => 1,
+            INT16 => 2,
+            INT32 | FLOAT => 4,
+            INT64 | DOUBLE => 8,
+            STRING1 => self.take(1)?.get_u8() as usize,
+            STRING4 => self.take(4)?.get_u32() as usize,
+            MAP | LIST => {
+                let fields = self.read_len()? * if ty == MAP { 2 } else { 1 };
+                for _ in 0..fields {
+                    let (_, ty) = self.read_head()?;
+                    self.skip(ty)?;
+                }
+                0
+            }
+            STRUCT_BEGIN => {
+                self.struct_body()?;
+                0
+            }
+            STRUCT_END | ZERO => 0,
+            SIMPLE_LIST => {
+                self.read_head()?;
+                self.read_len()?
+            }
+            ty => return Err(JceError::UnknownType(ty)),
+        };
+        self.take(len)?;
+        Ok(())
+    }
+
+    pub(crate) fn read_int_of(&mut self, ty: u8) -> Result<i64, JceError> {
+        Ok(match ty {
+            ZERO => 0,
+            INT8 => self.take(1)?.get_i8() as i64,
+            INT16 => self.take(2)?.get_i16() as i64,
+            INT32 => self.take(4)?.get_i32() as i64,
+            INT64 => self.take(8)?.get_i64(),
+            ty => return Err(JceError::TypeMismatch(ty)),
+        })
+    }
+
+    pub(crate) fn read_double_of(&mut self, ty: u8) -> Result<f64, JceError> {
+        Ok(match ty {
+            ZERO => 0.0,
+            FLOAT => self.take(4)?.get_f32() as f64,
+            DOUBLE => self.take(8)?.get_f64(),
+            ty => return Err(JceError::TypeMismatch(ty)),
+        })
+    }
+
+    pub(crate) fn read_string_of(&mut self, ty: u8) -> Result<String, JceError> {
+        let len = match ty {
+            STRING1 => self.take(1)?.get_u8() as usize,
+            STRING4 => self.take(4)?.get_u32() as usize,
+            ty => return Err(JceError::TypeMismatch(ty)),
+        };
+        Ok(String::from_utf8_lossy(&self.take(len)?).into_owned())
+    }
+
+    pub(crate) fn read_bytes_of(&mut self, ty: u8) -> Result<Bytes, JceError> {
+        match ty {
+            SIMPLE_LIST => {
+                self.read_head()?;
+                let len = self.read_len()?;
+                self.take(len)
+            }
+            ty => Err(JceError::TypeMismatch(ty)),
+        }
+    }
+
+    /// Splits off a struct whose begin head was just read, as a reader of its own.
+    pub(crate) fn read_struct_of(&mut self, ty: u8) -> Result<JceReader, JceError> {
+        match ty {
+            STRUCT_BEGIN => Ok(JceReader::new(self.struct_body()?)),
+            ty => Err(JceError::TypeMismatch(ty)),
+        }
+    }
+
+    /// Reads the length of a list or map whose head was just read.
+    pub(crate) fn read_len_of(&mut self, ty: u8, expected: u8) -> Result<usize, JceError> {
+        if ty != expected {
+            return Err(JceError::TypeMismatch(ty));
+        }
+        self.read_len()
+    }
+
+    /// Reads the untagged length of a list, map or simple list.
+    fn read_len(&mut self) -> Result<usize, JceError> {
+        let (_, ty) = self.read_head()?;
+        let len = self.read_int_of(ty)?;
+        usize::try_from(len).map_err(|_| JceError::Truncated)
+    }
+
+    /// Splits off the fields of a struct whose begin head was just read.
+    fn struct_body(&mut self) -> Result<Bytes, JceError> {
+        let start = self.buf.clone();
+        loop {
+            let remaining = self.buf.len();
+            let (_, ty) = self.read_head()?;
+            if ty == STRUCT_END {
+                return Ok(start.slice(..start.len() - remaining));
+            }
+            self.skip(ty)?;
+        }
+    }
+
+    /// Reads any [`JceType`], its default if the field is missing.
+    pub fn get<T: JceType>(&mut self, tag: u8) -> Result<T, JceError> {
+        match self.seek(tag)? {
+            Some(ty) => T::read(self, ty),
+            None => Ok(T::default()),
+        }
+    }
+
+    #[inline]
+    pub fn get_int(&mut self, tag: u8) -> Result<i64, JceError> {
+        self.get(tag)
+    }
+
+    #[inline]
+    pub fn get_string(&mut self, tag: u8) -> Result<String, JceError> {
+        self.get(tag)
+    }
+
+    #[inline]
+    pub fn get_bytes(&mut self, tag: u8) -> Result<Bytes, JceError> {
+        self.get(tag)
+    }
+
+    /// Reads a nested struct, empty if there is none.
+    #[inline]
+    pub fn get_struct(&mut self, tag: u8) -> Result<JceReader, JceError> {
+        self.get(tag)
+    }
+
+    /// Reads a list of structs, empty if there is none.
+    #[inline]
+    pub fn get_struct_list(&mut self, tag: u8) -> Result<Vec<JceReader>, JceError> {
+        self.get(tag)
+    }
+
+    /// Reads a `map<string, bytes>`.
+    #[inline]
+    pub fn get_bytes_map(&mut self, tag: u8) -> Result<HashMap<String, Bytes>, JceError> {
+        self.get(tag)
+    }
+}
@@ -0,0 +1,160 @@
+use crate::jce::{
+    JceType, DOUBLE, FLOAT, INT16, INT32, INT64, INT8, LIST, MAP, SIMPLE_LIST, STRING1, STRING4,
+    STRUCT_BEGIN, STRUCT_END, ZERO,
+};
+use bytes::{BufMut, Bytes, BytesMut};
+
+#[derive(Default)]
+pub struct JceWriter {
+    buf: BytesMut,
+}
+
+impl JceWriter {
+    #[inline]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub(crate) fn put_head(&mut self, tag: u8, ty: u8) {
+        if tag < 15 {
+            self.buf.put_u8(tag << 4 | ty);
+        } else {
+            self.buf.put_u8(0xF0 | ty);
+            self.buf.put_u8(tag);
+        }
+    }
+
+    /// Writes any [`JceType`] as the field `tag`.
+    #[inline]
+    pub fn put<T: JceType>(&mut self, tag: u8, value: &T) {
+        value.write(self, tag);
+    }
+
+    /// Writes an integer of any width, in as few bytes as it fits.
+    pub fn put_int(&mut self, tag: u8, value: i64) {
+        if value == 0 {
+            self.put_head(tag, ZERO);
+        } else if let Ok(v) = i8::try_from(value) {
+            self.put_head(tag, INT8);
+            self.buf.put_i8(v);
+        } else if let Ok(v) = i16::try_from(value) {
+            self.put_head(tag, INT16);
+            self.buf.put_i16(v);
+        } else if let Ok(v) = i32::try_from(value) {
+            self.put_head(tag, INT32);
+            self.buf.put_i32(v);
+        } else {
+            self.put_head(tag, INT64);
+            self.buf.put_i64(value);
+        }
+    }
+
+    pub fn put_float(&mut self, tag: u8, value: f32) {
+        self.put_head(tag, FLOAT);
+        self.buf.put_f32(value);
+    }
+
+    pub fn put_double(&mut self, tag: u8, value: f64) {
+        self.put_head(tag, DOUBLE);
+        self.buf.put_f64(value);
+    }
+
+    pub fn put_string(&mut self, tag: u8, s: &str) {
+        match u8::try_from(s.len()) {
+            Ok(len) => {
+                self.put_head(tag, STRING1);
+                self.buf.put_u8(len);
+            }
+            Err(_) => {
+                self.put_head(tag, STRING4);
+                self.buf.put_u32(s.len() as u32);
+            }
+        }
+        self.buf.put_slice(s.as_bytes());
+    }
+
+    /// Writes a byte array, a simple list of `int8`.
+    pub fn put_bytes(&mut self, tag: u8, bytes: &[u8]) {
+        self.put_head(tag, SIMPLE_LIST);
+        self.put_head(0, INT8);
+        self.put_int(0, bytes.len() as i64);
+        self.buf.put_slice(bytes);
+    }
+
+    /// Writes a nested struct, its fields written by `f`.
+    pub fn put_struct<F: FnOnce(&mut Self)>(&mut self, tag: u8, f: F) {
+        self.put_head(tag, STRUCT_BEGIN);
+        f(self);
+        self.put_head(0, STRUCT_END);
+    }
+
+    /// Writes a list, each item written by `f` as the field `0`.
+    pub fn put_list<I, F>(&mut self, tag: u8, items: I, mut f: F)
+    where
+        I: IntoIterator,
+        I::IntoIter: ExactSizeIterator,
+        F: FnMut(&mut Self, I::Item),
+    {
+        let items = items.into_iter();
+        self.put_head(tag, LIST);
+        self.put_int(0, items.len() as i64);
+        for item in items {
+            f(self, item);
+        }
+    }
+
+    /// Writes a list of structs, the fields of each written by `f`.
+    pub fn put_struct_list<T, F>(&mut self, tag: u8, items: &[T], mut f: F)
+    where
+        F: FnMut(&mut Self, &T),
+    {
+        self.put_list(tag, items, |w, item| w.put_struct(0, |w| f(w, item)));
+    }
+
+    /// Writes a map, each entry written by `f` as the fields `0` and `1`.
+    pub fn put_map<I, F>(&mut self, tag: u8, entries: I, mut f: F)
+    where
+        I: IntoIterator,
+        I::IntoIter: ExactSizeIterator,
+        F: FnMut(&mut Self, I::Item),
+    {
+        let entries = entries.into_iter();
+        self.put_head(tag, MAP);
+        self.put_int(0, entries.len() as i64);
+        for entry in entries {
+            f(self, entry);
+        }
+    }
+
+    /// Writes a `map<string, bytes>`.
+    pub fn put_bytes_map<'a, I>(&mut self, tag: u8, entries: I)
+    where
+        I: IntoIterator<Item = (&'a str, &'a [u8])>,
+        I::IntoIter: ExactSizeIterator,
+    {
+        self.put_map(tag, entries, |w, (key, value)| {
+            w.put_string(0, key);
+            w.put_bytes(1, value);
+        });
+    }
+
+    /// Appends already encoded fields.
+    pub(crate) fn put_raw(&mut self, fields: &[u8]) {
+        self.buf.put_slice(fields);
+    }
+
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.buf.len()
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.buf.is_empty()
+    }
+
+    #[inline]
+    pub fn into_bytes(self) -> Bytes {
+        self.buf.freeze()
+    }
+}
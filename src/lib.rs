@@ -1,10 +1,12 @@
 pub mod client;
+pub mod crypto;
 pub mod data;
 pub mod error;
 pub mod event;
 pub mod executor;
+pub mod jce;
+pub mod message;
 pub mod net;
 
-mod crypto;
 mod proto;
 mod sync;
@@ -0,0 +1,858 @@
+//! Messages as chains of elements, such as text, ats and images.
+//!
+//! A chain is carried as the `Elem` protobufs of `msg.proto`, a voice next to them.
+//! Elements not modelled here are kept as [`MessageElement::Unknown`] and encoded again as
+//! they came. Of the modelled ones, images, voices, files and market faces keep every field
+//! of their protobuf, as sending them on needs what the server filled in. Texts, ats and
+//! replies are rebuilt from what is modelled of them.
+
+use crate::error::ClientError;
+use crate::proto::message::{
+    CommonElem, CustomFace, Elem, Face as FaceElem, GroupFile, LightAppElem,
+    MarketFace as MarketFaceElem, MsgElemInfoServtype3, NotOnlineImage, Ptt, RichMsg, RichText,
+    SourceMsg, Text,
+};
+use bytes::{Buf, BufMut, Bytes, BytesMut};
+use flate2::read::ZlibDecoder;
+use flate2::write::ZlibEncoder;
+use flate2::Compression;
+use prost::Message;
+use std::fmt::Write as _;
+use std::io::{Read, Write};
+
+/// The text a flash image is followed by, for clients that cannot show it.
+const FLASH_FALLBACK: &str = "[闪照]请使用新版手机QQ查看闪照。";
+
+/// The `service_type` of a [`CommonElem`] carrying a flash image.
+const FLASH_SERVICE: i32 = 3;
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct MessageChain {
+    elements: Vec<MessageElement>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum MessageElement {
+    Text(String),
+    At(At),
+    AtAll,
+    Face(Face),
+    Image(Image),
+    /// An image shown once, for a few seconds.
+    FlashImage(Image),
+    /// Quotes another message, placed first in a chain.
+    Reply(Reply),
+    /// Sent alone, nothing else goes with a voice.
+    Voice(Voice),
+    File(File),
+    /// A light app, such as a shared mini program.
+    Json(String),
+    /// A rich message, such as a shared link.
+    Xml {
+        service_id: i32,
+        xml: String,
+    },
+    MarketFace(MarketFace),
+    /// An encoded `Elem` not modelled, kept as it came.
+    Unknown(Bytes),
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct At {
+    pub target: u64,
+    /// What the at shows, such as `@name`.
+    pub display: String,
+}
+
+/// One of the built-in small faces.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Face {
+    pub index: i32,
+}
+
+/// The `buf` every client sends a [`Face`] with.
+const FACE_BUF: &[u8] = &[0x00, 0x01, 0x00, 0x04, 0x52, 0xcc, 0xf5, 0xd0];
+
+impl Face {
+    /// The id older clients know the face by, sent along with the index.
+    fn old(&self) -> [u8; 2] {
+        ((0x1445 - 4 + self.index) as u16).to_be_bytes()
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Reply {
+    /// The seq of the message quoted.
+    pub seq: i32,
+    pub sender: u64,
+    /// When the quoted message was sent, in seconds since the unix epoch.
+    pub time: i32,
+    /// What is quoted of it.
+    pub chain: MessageChain,
+}
+
+/// An image already uploaded, as received.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Image(ImageInner);
+
+#[derive(Clone, Debug, PartialEq)]
+enum ImageInner {
+    Group(CustomFace),
+    Friend(NotOnlineImage),
+}
+
+impl Image {
+    /// Whether it was sent in a group, friend images go to friends and temp chats.
+    pub fn is_group(&self) -> bool {
+        matches!(self.0, ImageInner::Group(_))
+    }
+
+    pub fn md5(&self) -> &[u8] {
+        match &self.0 {
+            ImageInner::Group(face) => &face.md5,
+            ImageInner::Friend(image) => &image.pic_md5,
+        }
+    }
+
+    pub fn size(&self) -> u32 {
+        match &self.0 {
+            ImageInner::Group(face) => face.size,
+            ImageInner::Friend(image) => image.file_len,
+        }
+    }
+
+    /// The width and height in pixels.
+    pub fn dimensions(&self) -> (u32, u32) {
+        match &self.0 {
+            ImageInner::Group(face) => (face.width as u32, face.height as u32),
+            ImageInner::Friend(image) => (image.pic_width, image.pic_height),
+        }
+    }
+
+    /// Where to download the original.
+    pub fn url(&self) -> String {
+        match &self.0 {
+            ImageInner::Group(face) if !face.orig_url.is_empty() => {
+                format!("https://gchat.qpic.cn{}", face.orig_url)
+            }
+            ImageInner::Group(face) => format!(
+                "https://gchat.qpic.cn/gchatpic_new/0/0-0-{}/0?term=2",
+                hex(&face.md5)
+            ),
+            ImageInner::Friend(image) if !image.orig_url.is_empty() => {
+                format!("https://c2cpicdw.qpic.cn{}", image.orig_url)
+            }
+            ImageInner::Friend(image) => format!(
+                "https://c2cpicdw.qpic.cn/offpic_new/0/{}/0?term=2",
+                image.res_id
+            ),
+        }
+    }
+}
+
+/// A voice already uploaded, as received.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Voice(Ptt);
+
+impl Voice {
+    pub fn md5(&self) -> &[u8] {
+        &self.0.file_md5
+    }
+
+    pub fn size(&self) -> u32 {
+        self.0.file_size as u32
+    }
+
+    pub fn name(&self) -> String {
+        String::from_utf8_lossy(&self.0.file_name).into_owned()
+    }
+
+    /// Where to download it, empty if the server gave none.
+    pub fn url(&self) -> String {
+        String::from_utf8_lossy(&self.0.down_para).into_owned()
+    }
+}
+
+/// A group file, as received.
+#[derive(Clone, Debug, PartialEq)]
+pub struct File(GroupFile);
+
+impl File {
+    pub fn name(&self) -> String {
+        String::from_utf8_lossy(&self.0.filename).into_owned()
+    }
+
+    pub fn size(&self) -> u64 {
+        self.0.file_size as u64
+    }
+
+    /// Identifies the file when downloading it.
+    pub fn id(&self) -> String {
+        String::from_utf8_lossy(&self.0.file_id).into_owned()
+    }
+}
+
+/// A face from the face store, as received.
+#[derive(Clone, Debug, PartialEq)]
+pub struct MarketFace(MarketFaceElem);
+
+impl MarketFace {
+    /// The name, such as `[开心]`.
+    pub fn name(&self) -> String {
+        String::from_utf8_lossy(&self.0.face_name).into_owned()
+    }
+
+    pub fn face_id(&self) -> &[u8] {
+        &self.0.face_id
+    }
+
+    /// The face pack it belongs to.
+    pub fn tab_id(&self) -> u32 {
+        self.0.tab_id
+    }
+}
+
+/// Where a sent message landed, for recalling or quoting it later.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct MessageReceipt {
+    /// One for each packet the message went out in.
+    ///
+    /// Empty for a group message whose echo never arrived, the message was delivered
+    /// but where it landed is unknown.
+    pub seqs: Vec<i32>,
+    pub rands: Vec<i32>,
+    /// Seconds since the unix epoch.
+    pub time: i32,
+}
+
+impl MessageChain {
+    #[inline]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    #[inline]
+    pub fn push<T: Into<MessageElement>>(&mut self, element: T) {
+        self.elements.push(element.into());
+    }
+
+    /// Adds `element`, for building a chain in one expression.
+    #[inline]
+    pub fn with<T: Into<MessageElement>>(mut self, element: T) -> Self {
+        self.push(element);
+        self
+    }
+
+    #[inline]
+    pub fn elements(&self) -> &[MessageElement] {
+        &self.elements
+    }
+
+    #[inline]
+    pub fn iter(&self) -> std::slice::Iter<'_, MessageElement> {
+        self.elements.iter()
+    }
+
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.elements.len()
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.elements.is_empty()
+    }
+
+    /// The text of every text element, and the display of every at.
+    pub fn text(&self) -> String {
+        let mut text = String::new();
+        for element in &self.elements {
+            match element {
+                MessageElement::Text(s) => text.push_str(s),
+                MessageElement::At(at) => text.push_str(&at.display),
+                MessageElement::AtAll => text.push_str(AT_ALL),
+                _ => {}
+            }
+        }
+        text
+    }
+
+    /// Reads a chain out of the rich text of a message.
+    pub(crate) fn from_rich_text(rich_text: RichText) -> Self {
+        let mut chain = Self::from_elems(&rich_text.elems);
+        if let Some(ptt) = rich_text.ptt {
+            chain.push(MessageElement::Voice(Voice(ptt)));
+        }
+        chain
+    }
+
+    /// The rich text carrying the chain, without its attributes.
+    ///
+    /// Fails with [`ClientError::InvalidUin`] if an at names a uin above `u32::MAX`.
+    pub(crate) fn to_rich_text(&self) -> Result<RichText, ClientError> {
+        let mut ptt = None;
+        let mut elems = vec![];
+        for element in &self.elements {
+            match element {
+                MessageElement::Voice(voice) => ptt = Some(voice.0.clone()),
+                element => element.encode(&mut elems)?,
+            }
+        }
+
+        Ok(RichText {
+            attr: None,
+            elems,
+            ptt,
+        })
+    }
+
+    fn from_elems(elems: &[Bytes]) -> Self {
+        let mut chain = Self::new();
+        let mut elems = elems.iter();
+        while let Some(raw) = elems.next() {
+            let element = MessageElement::decode(raw);
+            let flash = matches!(element, MessageElement::FlashImage(_));
+            chain.push(element);
+
+            // the fallback text is part of the flash image
+            if flash && elems.as_slice().first().is_some_and(is_flash_fallback) {
+                elems.next();
+            }
+        }
+        chain
+    }
+}
+
+impl From<Vec<MessageElement>> for MessageChain {
+    #[inline]
+    fn from(elements: Vec<MessageElement>) -> Self {
+        Self { elements }
+    }
+}
+
+impl<T: Into<MessageElement>> FromIterator<T> for MessageChain {
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
+        Self {
+            elements: iter.into_iter().map(Into::into).collect(),
+        }
+    }
+}
+
+impl IntoIterator for MessageChain {
+    type Item = MessageElement;
+    type IntoIter = std::vec::IntoIter<MessageElement>;
+
+    #[inline]
+    fn into_iter(self) -> Self::IntoIter {
+        self.elements.into_iter()
+    }
+}
+
+impl<'a> IntoIterator for &'a MessageChain {
+    type Item = &'a MessageElement;
+    type IntoIter = std::slice::Iter<'a, MessageElement>;
+
+    #[inline]
+    fn into_iter(self) -> Self::IntoIter {
+        self.elements.iter()
+    }
+}
+
+impl From<&str> for MessageElement {
+    #[inline]
+    fn from(text: &str) -> Self {
+        Self::Text(text.into())
+    }
+}
+
+impl From<String> for MessageElement {
+    #[inline]
+    fn from(text: String) -> Self {
+        Self::Text(text)
+    }
+}
+
+impl From<At> for MessageElement {
+    #[inline]
+    fn from(at: At) -> Self {
+        Self::At(at)
+    }
+}
+
+impl From<Face> for MessageElement {
+    #[inline]
+    fn from(face: Face) -> Self {
+        Self::Face(face)
+    }
+}
+
+impl From<Image> for MessageElement {
+    #[inline]
+    fn from(image: Image) -> Self {
+        Self::Image(image)
+    }
+}
+
+impl From<Reply> for MessageElement {
+    #[inline]
+    fn from(reply: Reply) -> Self {
+        Self::Reply(reply)
+    }
+}
+
+const AT_ALL: &str = "@全体成员";
+
+impl MessageElement {
+    /// Reads an encoded `Elem`, as [`MessageElement::Unknown`] if it is not modelled.
+    fn decode(raw: &Bytes) -> Self {
+        let unknown = || Self::Unknown(raw.clone());
+        let Ok(elem) = Elem::decode(raw.clone()) else {
+            return unknown();
+        };
+
+        if let Some(text) = elem.text {
+            return decode_text(text);
+        }
+        if let Some(face) = elem.face {
+            return Self::Face(Face { index: face.index });
+        }
+        if let Some(face) = elem.custom_face {
+            return Self::Image(Image(ImageInner::Group(face)));
+        }
+        if let Some(image) = elem.not_online_image {
+            return Self::Image(Image(ImageInner::Friend(image)));
+        }
+        if let Some(face) = elem.market_face {
+            return Self::MarketFace(MarketFace(face));
+        }
+        if let Some(file) = elem.group_file {
+            return Self::File(File(file));
+        }
+        if let Some(src) = elem.src_msg {
+            return Self::Reply(Reply {
+                seq: src.orig_seqs.first().copied().unwrap_or_default(),
+                sender: src.sender_uin as u64,
+                time: src.time,
+                chain: MessageChain::from_elems(&src.elems),
+            });
+        }
+        if let Some(rich) = elem.rich_msg {
+            return match inflate(&rich.template1) {
+                Some(xml) => Self::Xml {
+                    service_id: rich.service_id,
+                    xml,
+                },
+                None => unknown(),
+            };
+        }
+        if let Some(app) = elem.light_app {
+            return inflate(&app.data).map_or_else(unknown, Self::Json);
+        }
+        if let Some(common) = elem.common_elem {
+            if common.service_type == FLASH_SERVICE {
+                if let Ok(flash) = MsgElemInfoServtype3::decode(common.pb_elem) {
+                    match (flash.flash_troop_pic, flash.flash_c2c_pic) {
+                        (Some(face), _) => return Self::FlashImage(Image(ImageInner::Group(face))),
+                        (_, Some(image)) => {
+                            return Self::FlashImage(Image(ImageInner::Friend(image)))
+                        }
+                        _ => {}
+                    }
+                }
+            }
+        }
+
+        unknown()
+    }
+
+    /// Appends the encoded `Elem`s of the element, voices excepted.
+    fn encode(&self, elems: &mut Vec<Bytes>) -> Result<(), ClientError> {
+        let elem = match self {
+            Self::Text(s) => Elem {
+                text: Some(text(s.clone(), Bytes::new())),
+                ..Default::default()
+            },
+            Self::At(at) => Elem {
+                text: Some(text(at.display.clone(), at_attr(at.target, &at.display)?)),
+                ..Default::default()
+            },
+            Self::AtAll => Elem {
+                text: Some(text(AT_ALL.into(), at_attr(0, AT_ALL)?)),
+                ..Default::default()
+            },
+            Self::Face(face) => Elem {
+                face: Some(FaceElem {
+                    index: face.index,
+                    old: Bytes::copy_from_slice(&face.old()),
+                    buf: Bytes::from_static(FACE_BUF),
+                }),
+                ..Default::default()
+            },
+            Self::Image(Image(ImageInner::Group(face))) => Elem {
+                custom_face: Some(face.clone()),
+                ..Default::default()
+            },
+            Self::Image(Image(ImageInner::Friend(image))) => Elem {
+                not_online_image: Some(image.clone()),
+                ..Default::default()
+            },
+            Self::FlashImage(image) => {
+                let flash = match &image.0 {
+                    ImageInner::Group(face) => MsgElemInfoServtype3 {
+                        flash_troop_pic: Some(face.clone()),
+                        flash_c2c_pic: None,
+                    },
+                    ImageInner::Friend(image) => MsgElemInfoServtype3 {
+                        flash_troop_pic: None,
+                        flash_c2c_pic: Some(image.clone()),
+                    },
+                };
+                let elem = Elem {
+                    common_elem: Some(CommonElem {
+                        service_type: FLASH_SERVICE,
+                        pb_elem: flash.encode_to_vec().into(),
+                        business_type: 0,
+                    }),
+                    ..Default::default()
+                };
+                elems.push(elem.encode_to_vec().into());
+                Elem {
+                    text: Some(text(FLASH_FALLBACK.into(), Bytes::new())),
+                    ..Default::default()
+                }
+            }
+            Self::Reply(reply) => Elem {
+                src_msg: Some(SourceMsg {
+                    orig_seqs: vec![reply.seq],
+                    sender_uin: reply.sender as i64,
+                    time: reply.time,
+                    flag: 1,
+                    elems: reply.chain.to_rich_text()?.elems,
+                    ..Default::default()
+                }),
+                ..Default::default()
+            },
+            // carried next to the elems
+            Self::Voice(_) => return Ok(()),
+            Self::File(file) => Elem {
+                group_file: Some(file.0.clone()),
+                ..Default::default()
+            },
+            Self::Json(json) => Elem {
+                light_app: Some(LightAppElem {
+                    data: deflate(json),
+                    msg_resid: Bytes::new(),
+                }),
+                ..Default::default()
+            },
+            Self::Xml { service_id, xml } => Elem {
+                rich_msg: Some(RichMsg {
+                    template1: deflate(xml),
+                    service_id: *service_id,
+                    ..Default::default()
+                }),
+                ..Default::default()
+            },
+            Self::MarketFace(face) => Elem {
+                market_face: Some(face.0.clone()),
+                ..Default::default()
+            },
+            Self::Unknown(raw) => {
+                elems.push(raw.clone());
+                return Ok(());
+            }
+        };
+
+        elems.push(elem.encode_to_vec().into());
+        Ok(())
+    }
+}
+
+fn text(str: String, attr6_buf: Bytes) -> Text {
+    Text {
+        str,
+        attr6_buf,
+        ..Default::default()
+    }
+}
+
+/// An at is a text whose `attr6_buf` names the target, `0` for everyone.
+///
+/// The target has 32 bits, a larger uin is refused rather than cut to another one.
+fn at_attr(target: u64, display: &str) -> Result<Bytes, ClientError> {
+    let uin = u32::try_from(target).map_err(|_| ClientError::InvalidUin(target))?;
+    let mut buf = BytesMut::with_capacity(13);
+    buf.put_u16(1);
+    buf.put_u16(0);
+    buf.put_u16(display.chars().count() as u16);
+    buf.put_u8(u8::from(target == 0));
+    buf.put_u32(uin);
+    buf.put_u16(0);
+    Ok(buf.freeze())
+}
+
+fn decode_text(text: Text) -> MessageElement {
+    if text.attr6_buf.len() < 11 {
+        return MessageElement::Text(text.str);
+    }
+
+    let mut attr = text.attr6_buf.slice(7..11);
+    match attr.get_u32() {
+        0 => MessageElement::AtAll,
+        target => MessageElement::At(At {
+            target: target as u64,
+            display: text.str,
+        }),
+    }
+}
+
+fn is_flash_fallback(raw: &Bytes) -> bool {
+    matches!(
+        Elem::decode(raw.clone()),
+        Ok(Elem { text: Some(text), .. }) if text.str == FLASH_FALLBACK
+    )
+}
+
+/// Compresses a json or xml message behind the flag byte `1`.
+fn deflate(s: &str) -> Bytes {
+    let mut z = ZlibEncoder::new(vec![1], Compression::default());
+    // writing to a vec cannot fail
+    let _ = z.write_all(s.as_bytes());
+    z.finish().unwrap_or_default().into()
+}
+
+/// Undoes [`deflate`], or takes the rest as is if the flag byte is `0`.
+fn inflate(buf: &[u8]) -> Option<String> {
+    let (&flag, data) = buf.split_first()?;
+    let mut s = String::new();
+    match flag {
+        0 => s.push_str(std::str::from_utf8(data).ok()?),
+        _ => {
+            ZlibDecoder::new(data).read_to_string(&mut s).ok()?;
+        }
+    }
+    Some(s)
+}
+
+fn hex(bytes: &[u8]) -> String {
+    let mut s = String::with_capacity(bytes.len() * 2);
+    for b in bytes {
+        let _ = write!(s, "{:02X}", b);
+    }
+    s
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::error::ClientError;
+    use crate::message::{
+        At, Face, File, Image, ImageInner, MarketFace, MessageChain, MessageElement, Reply, Voice,
+        FLASH_FALLBACK,
+    };
+    use crate::proto::message::{
+        CustomFace, Elem, GroupFile, MarketFace as MarketFaceElem, NotOnlineImage, Ptt, RichText,
+        Text,
+    };
+    use bytes::Bytes;
+    use prost::Message;
+
+    fn group_image() -> Image {
+        Image(ImageInner::Group(CustomFace {
+            file_path: "{0A0B0C0D}.jpg".into(),
+            file_id: 123,
+            server_ip: 0x7f000001,
+            md5: Bytes::from_static(&[0x0a; 16]),
+            orig_url: "/gchatpic_new/1/2-3-0A0A/0".into(),
+            width: 640,
+            height: 480,
+            size: 1024,
+            ..Default::default()
+        }))
+    }
+
+    fn friend_image() -> Image {
+        Image(ImageInner::Friend(NotOnlineImage {
+            res_id: "/1-2-abc".into(),
+            file_len: 2048,
+            pic_md5: Bytes::from_static(&[0x0b; 16]),
+            pic_width: 100,
+            pic_height: 200,
+            ..Default::default()
+        }))
+    }
+
+    /// Every modelled element.
+    fn chain() -> MessageChain {
+        let quoted = MessageChain::new().with("quoted");
+        MessageChain::new()
+            .with(Reply {
+                seq: 42,
+                sender: 10001,
+                time: 1_600_000_000,
+                chain: quoted,
+            })
+            .with("hello ")
+            .with(At {
+                target: 10002,
+                display: "@someone".into(),
+            })
+            .with(MessageElement::AtAll)
+            .with(Face { index: 14 })
+            .with(group_image())
+            .with(MessageElement::FlashImage(friend_image()))
+            .with(MessageElement::File(File(GroupFile {
+                filename: Bytes::from_static(b"a.txt"),
+                file_size: 3,
+                file_id: Bytes::from_static(b"/abc"),
+                ..Default::default()
+            })))
+            .with(MessageElement::Json(
+                r#"{"app":"com.tencent.miniapp"}"#.into(),
+            ))
+            .with(MessageElement::Xml {
+                service_id: 1,
+                xml: "<msg/>".into(),
+            })
+            .with(MessageElement::MarketFace(MarketFace(MarketFaceElem {
+                face_name: Bytes::from_static("[开心]".as_bytes()),
+                face_id: Bytes::from_static(&[1; 16]),
+                tab_id: 7,
+                ..Default::default()
+            })))
+            .with(MessageElement::Unknown(Bytes::from_static(b"\xba\x02\x00")))
+    }
+
+    #[test]
+    fn roundtrip() {
+        let chain = chain();
+        let rich_text = chain.to_rich_text().unwrap();
+        // the flash image comes with its fallback text
+        assert_eq!(rich_text.elems.len(), chain.len() + 1);
+        assert!(rich_text.ptt.is_none());
+
+        let decoded = MessageChain::from_rich_text(rich_text);
+        assert_eq!(decoded, chain);
+        assert_eq!(
+            decoded.to_rich_text().unwrap().elems,
+            chain.to_rich_text().unwrap().elems
+        );
+        assert_eq!(decoded.text(), "hello @someone@全体成员");
+
+        let voice = MessageChain::new().with(MessageElement::Voice(Voice(Ptt {
+            file_md5: Bytes::from_static(&[2; 16]),
+            file_name: Bytes::from_static(b"voice.amr"),
+            file_size: 512,
+            ..Default::default()
+        })));
+        let rich_text = voice.to_rich_text().unwrap();
+        assert!(rich_text.elems.is_empty());
+        assert_eq!(rich_text.ptt.as_ref().unwrap().file_size, 512);
+        assert_eq!(MessageChain::from_rich_text(rich_text), voice);
+    }
+
+    #[test]
+    fn face() {
+        // as clients send the face at index 14
+        let raw = Bytes::from_static(
+            b"\x12\x10\x08\x0e\x12\x02\x14\x4f\x5a\x08\x00\x01\x00\x04\x52\xcc\xf5\xd0",
+        );
+        let chain = MessageChain::from_elems(std::slice::from_ref(&raw));
+        assert_eq!(chain.elements(), [MessageElement::Face(Face { index: 14 })]);
+        assert_eq!(chain.to_rich_text().unwrap().elems, [raw]);
+    }
+
+    #[test]
+    fn decode() {
+        let elem = |text: Text| -> Bytes {
+            Elem {
+                text: Some(text),
+                ..Default::default()
+            }
+            .encode_to_vec()
+            .into()
+        };
+
+        // as another client sends them
+        let at_all = Text {
+            str: "@全体成员".into(),
+            attr6_buf: Bytes::from_static(&[0, 1, 0, 0, 0, 5, 1, 0, 0, 0, 0, 0, 0]),
+            ..Default::default()
+        };
+        let at = Text {
+            str: "@x".into(),
+            attr6_buf: Bytes::from_static(&[0, 1, 0, 0, 0, 2, 0, 0, 0, 0x27, 0x12, 0, 0]),
+            ..Default::default()
+        };
+        let chain = MessageChain::from_rich_text(RichText {
+            attr: None,
+            elems: vec![
+                elem(at_all),
+                elem(at),
+                elem(Text {
+                    str: FLASH_FALLBACK.into(),
+                    ..Default::default()
+                }),
+                Bytes::from_static(b"junk"),
+            ],
+            ptt: None,
+        });
+
+        assert_eq!(
+            chain.elements(),
+            [
+                MessageElement::AtAll,
+                MessageElement::At(At {
+                    target: 10002,
+                    display: "@x".into()
+                }),
+                // without a flash image before it, the fallback is just text
+                MessageElement::Text(FLASH_FALLBACK.into()),
+                MessageElement::Unknown(Bytes::from_static(b"junk")),
+            ]
+        );
+    }
+
+    #[test]
+    fn large_uin() {
+        let at = At {
+            target: u32::MAX as u64 + 10002,
+            display: "@x".into(),
+        };
+        let chain = MessageChain::new().with(at.clone());
+        assert!(matches!(
+            chain.to_rich_text(),
+            Err(ClientError::InvalidUin(uin)) if uin == at.target
+        ));
+
+        let quoted = MessageChain::new().with(Reply {
+            seq: 42,
+            sender: 10001,
+            time: 1_600_000_000,
+            chain,
+        });
+        assert!(matches!(
+            quoted.to_rich_text(),
+            Err(ClientError::InvalidUin(_))
+        ));
+    }
+
+    #[test]
+    fn image() {
+        let image = group_image();
+        assert!(image.is_group());
+        assert_eq!(image.size(), 1024);
+        assert_eq!(image.dimensions(), (640, 480));
+        assert_eq!(
+            image.url(),
+            "https://gchat.qpic.cn/gchatpic_new/1/2-3-0A0A/0"
+        );
+
+        let image = friend_image();
+        assert!(!image.is_group());
+        assert_eq!(image.md5(), [0x0b; 16]);
+        assert_eq!(
+            image.url(),
+            "https://c2cpicdw.qpic.cn/offpic_new/0//1-2-abc/0?term=2"
+        );
+    }
+}
@@ -1,5 +1,6 @@
-use std::future::poll_fn;
+use std::future::{poll_fn, Future};
 use std::io;
+use std::net::SocketAddr;
 use std::task::{Context, Poll};
 
 pub trait Connector
@@ -11,6 +12,31 @@ where
     fn poll_send(&mut self, cx: &mut Context<'_>, buf: &[u8]) -> Poll<io::Result<usize>>;
 }
 
+/// Opens new connections, for a session that reconnects once its connection breaks.
+///
+/// Implemented for closures, such as `|addr| tokio::net::TcpStream::connect(addr)`.
+pub trait ConnectorFactory {
+    type Connector: Connector;
+    type Future: Future<Output = io::Result<Self::Connector>>;
+
+    fn connect(&self, addr: SocketAddr) -> Self::Future;
+}
+
+impl<F, Fu, C> ConnectorFactory for F
+where
+    F: Fn(SocketAddr) -> Fu,
+    Fu: Future<Output = io::Result<C>>,
+    C: Connector,
+{
+    type Connector = C;
+    type Future = Fu;
+
+    #[inline]
+    fn connect(&self, addr: SocketAddr) -> Fu {
+        self(addr)
+    }
+}
+
 #[cfg(feature = "net-tokio")]
 mod net_tokio {
     use crate::net::connector::Connector;
@@ -24,7 +50,7 @@ mod net_tokio {
             let pin = Pin::new(self);
             let mut buffer = ReadBuf::new(buf);
             ready!(pin.poll_read(cx, &mut buffer))?;
-            Poll::Ready(Ok(buf.len()))
+            Poll::Ready(Ok(buffer.filled().len()))
         }
 
         fn poll_send(&mut self, cx: &mut Context<'_>, buf: &[u8]) -> Poll<io::Result<usize>> {
@@ -41,9 +67,22 @@ mod net_std {
     use std::io::{Read, Write};
     use std::task::{Context, Poll};
 
+    /// Reads block the calling thread. Give the stream a read timeout to share it
+    /// between a reader and a writer, a timed out read is reported as pending.
     impl Connector for std::net::TcpStream {
-        fn poll_recv(&mut self, _: &mut Context<'_>, buf: &mut [u8]) -> Poll<io::Result<usize>> {
-            Poll::Ready(self.read(buf))
+        fn poll_recv(&mut self, cx: &mut Context<'_>, buf: &mut [u8]) -> Poll<io::Result<usize>> {
+            match self.read(buf) {
+                Err(e)
+                    if matches!(
+                        e.kind(),
+                        io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut
+                    ) =>
+                {
+                    cx.waker().wake_by_ref();
+                    Poll::Pending
+                }
+                r => Poll::Ready(r),
+            }
         }
 
         fn poll_send(&mut self, _: &mut Context<'_>, buf: &[u8]) -> Poll<io::Result<usize>> {
@@ -101,24 +140,30 @@ pub async fn send<C: Connector>(connector: &mut C, buf: &[u8]) -> io::Result<usi
     poll_fn(|cx| connector.poll_send(cx, buf)).await
 }
 
-pub async fn send_all<C: Connector>(connector: &mut C, buf: &[u8]) -> io::Result<()> {
-    let size = send(connector, buf).await?;
-    if size == buf.len() {
-        Ok(())
-    } else {
-        Err(io::ErrorKind::WriteZero.into())
+pub async fn send_all<C: Connector>(connector: &mut C, mut buf: &[u8]) -> io::Result<()> {
+    while !buf.is_empty() {
+        let size = send(connector, buf).await?;
+        if size == 0 {
+            return Err(io::ErrorKind::WriteZero.into());
+        }
+        buf = &buf[size..];
     }
+
+    Ok(())
 }
 
 pub async fn recv<C: Connector>(connector: &mut C, buf: &mut [u8]) -> io::Result<usize> {
     poll_fn(|cx| connector.poll_recv(cx, buf)).await
 }
 
-pub async fn recv_all<C: Connector>(connector: &mut C, buf: &mut [u8]) -> io::Result<()> {
-    let size = recv(connector, buf).await?;
-    if size == buf.len() {
-        Ok(())
-    } else {
-        Err(io::ErrorKind::UnexpectedEof.into())
+pub async fn recv_all<C: Connector>(connector: &mut C, mut buf: &mut [u8]) -> io::Result<()> {
+    while !buf.is_empty() {
+        let size = recv(connector, buf).await?;
+        if size == 0 {
+            return Err(io::ErrorKind::UnexpectedEof.into());
+        }
+        buf = &mut buf[size..];
     }
+
+    Ok(())
 }
@@ -0,0 +1,281 @@
+use crate::net::connector::Connector;
+use bytes::{Buf, Bytes, BytesMut};
+use futures::{Sink, Stream};
+use std::io;
+use std::pin::Pin;
+use std::task::{ready, Context, Poll};
+
+const READ_CHUNK: usize = 4096;
+const MAX_FRAME_LEN: usize = 16 * 1024 * 1024;
+
+/// Splits sso frames (`u32` big-endian length, counting itself, followed by the payload)
+/// out of a [`Connector`].
+///
+/// As a [`Stream`] it yields each frame without the length prefix. As a [`Sink`] it
+/// writes already framed packets, such as those built by `Packet::build_sso_packet`,
+/// retrying on short writes.
+pub struct FramedConnector<C> {
+    inner: C,
+    /// Zeroed once as it grows, only the first `filled` bytes hold data read.
+    read_buf: BytesMut,
+    filled: usize,
+    write_buf: BytesMut,
+    /// Set once a bad length header is read, after which the stream can't find the
+    /// next frame and ends.
+    terminated: bool,
+}
+
+pub type PacketStream<C> = FramedConnector<C>;
+
+impl<C: Connector> FramedConnector<C> {
+    pub fn new(inner: C) -> Self {
+        Self {
+            inner,
+            read_buf: BytesMut::with_capacity(READ_CHUNK),
+            filled: 0,
+            write_buf: BytesMut::new(),
+            terminated: false,
+        }
+    }
+
+    #[inline]
+    pub fn get_ref(&self) -> &C {
+        &self.inner
+    }
+
+    #[inline]
+    pub fn get_mut(&mut self) -> &mut C {
+        &mut self.inner
+    }
+
+    #[inline]
+    pub fn into_inner(self) -> C {
+        self.inner
+    }
+
+    fn decode_frame(&mut self) -> io::Result<Option<Bytes>> {
+        if self.filled < 4 {
+            return Ok(None);
+        }
+
+        let mut len = [0; 4];
+        len.copy_from_slice(&self.read_buf[..4]);
+        let len = u32::from_be_bytes(len) as usize;
+        if !(4..=MAX_FRAME_LEN).contains(&len) {
+            self.clear();
+            self.terminated = true;
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("invalid frame length: {}", len),
+            ));
+        }
+
+        if self.filled < len {
+            self.grow_read_buf(len - self.filled);
+            return Ok(None);
+        }
+
+        let mut frame = self.read_buf.split_to(len);
+        self.filled -= len;
+        frame.advance(4);
+        Ok(Some(frame.freeze()))
+    }
+
+    /// Makes room for at least `additional` more bytes past the data read, zeroing only
+    /// the part of `read_buf` not zeroed before.
+    fn grow_read_buf(&mut self, additional: usize) {
+        let len = self.filled + additional;
+        if self.read_buf.len() < len {
+            self.read_buf.resize(len, 0);
+        }
+    }
+
+    fn clear(&mut self) {
+        self.read_buf.clear();
+        self.filled = 0;
+    }
+}
+
+impl<C: Connector> Stream for FramedConnector<C> {
+    type Item = io::Result<Bytes>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+        if this.terminated {
+            return Poll::Ready(None);
+        }
+
+        loop {
+            match this.decode_frame() {
+                Ok(Some(frame)) => return Poll::Ready(Some(Ok(frame))),
+                Ok(None) => {}
+                Err(e) => return Poll::Ready(Some(Err(e))),
+            }
+
+            this.grow_read_buf(READ_CHUNK);
+            let n = ready!(this.inner.poll_recv(cx, &mut this.read_buf[this.filled..]))?;
+            this.filled += n;
+
+            if n == 0 {
+                return if this.filled == 0 {
+                    Poll::Ready(None)
+                } else {
+                    this.clear();
+                    Poll::Ready(Some(Err(io::ErrorKind::UnexpectedEof.into())))
+                };
+            }
+        }
+    }
+}
+
+impl<C: Connector> Sink<Bytes> for FramedConnector<C> {
+    type Error = io::Error;
+
+    #[inline]
+    fn poll_ready(self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn start_send(self: Pin<&mut Self>, item: Bytes) -> Result<(), Self::Error> {
+        self.get_mut().write_buf.extend_from_slice(&item);
+        Ok(())
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        let this = self.get_mut();
+
+        while !this.write_buf.is_empty() {
+            let n = ready!(this.inner.poll_send(cx, &this.write_buf))?;
+            if n == 0 {
+                return Poll::Ready(Err(io::ErrorKind::WriteZero.into()));
+            }
+            this.write_buf.advance(n);
+        }
+
+        Poll::Ready(Ok(()))
+    }
+
+    #[inline]
+    fn poll_close(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.poll_flush(cx)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::net::connector::Connector;
+    use crate::net::framed::FramedConnector;
+    use bytes::Bytes;
+    use futures::{SinkExt, StreamExt};
+    use std::io;
+    use std::task::{Context, Poll};
+
+    /// Hands out at most `step` bytes per call and returns `Pending` every other call.
+    struct Trickle {
+        input: Vec<u8>,
+        output: Vec<u8>,
+        step: usize,
+        pending: bool,
+    }
+
+    impl Connector for Trickle {
+        fn poll_recv(&mut self, cx: &mut Context<'_>, buf: &mut [u8]) -> Poll<io::Result<usize>> {
+            self.pending = !self.pending;
+            if self.pending {
+                cx.waker().wake_by_ref();
+                return Poll::Pending;
+            }
+
+            let n = self.step.min(buf.len()).min(self.input.len());
+            buf[..n].copy_from_slice(&self.input[..n]);
+            self.input.drain(..n);
+            Poll::Ready(Ok(n))
+        }
+
+        fn poll_send(&mut self, _: &mut Context<'_>, buf: &[u8]) -> Poll<io::Result<usize>> {
+            let n = self.step.min(buf.len());
+            self.output.extend_from_slice(&buf[..n]);
+            Poll::Ready(Ok(n))
+        }
+    }
+
+    fn frame(payload: &[u8]) -> Vec<u8> {
+        let mut v = ((payload.len() + 4) as u32).to_be_bytes().to_vec();
+        v.extend_from_slice(payload);
+        v
+    }
+
+    #[test]
+    fn partial_reads() {
+        let mut input = frame(b"hello");
+        input.extend(frame(b""));
+        input.extend(frame(&[7; 10000]));
+
+        let mut framed = FramedConnector::new(Trickle {
+            input,
+            output: vec![],
+            step: 3,
+            pending: false,
+        });
+
+        futures::executor::block_on(async {
+            assert_eq!(framed.next().await.unwrap().unwrap(), &b"hello"[..]);
+            assert!(framed.next().await.unwrap().unwrap().is_empty());
+            assert_eq!(framed.next().await.unwrap().unwrap(), &[7; 10000][..]);
+            assert!(framed.next().await.is_none());
+        });
+    }
+
+    #[test]
+    fn truncated() {
+        let mut input = frame(b"hello");
+        input.pop();
+
+        let mut framed = FramedConnector::new(Trickle {
+            input,
+            output: vec![],
+            step: 64,
+            pending: false,
+        });
+
+        futures::executor::block_on(async {
+            let e = framed.next().await.unwrap().unwrap_err();
+            assert_eq!(e.kind(), io::ErrorKind::UnexpectedEof);
+        });
+
+        let mut input = vec![0, 0, 0, 1];
+        input.extend(frame(b"hello"));
+
+        let mut framed = FramedConnector::new(Trickle {
+            input,
+            output: vec![],
+            step: 64,
+            pending: false,
+        });
+
+        futures::executor::block_on(async {
+            let e = framed.next().await.unwrap().unwrap_err();
+            assert_eq!(e.kind(), io::ErrorKind::InvalidData);
+            assert!(framed.next().await.is_none());
+        });
+    }
+
+    #[test]
+    fn short_writes() {
+        let mut framed = FramedConnector::new(Trickle {
+            input: vec![],
+            output: vec![],
+            step: 2,
+            pending: false,
+        });
+
+        futures::executor::block_on(async {
+            framed.send(Bytes::from(frame(b"abc"))).await.unwrap();
+            framed.send(Bytes::from(frame(b"defg"))).await.unwrap();
+        });
+
+        let mut expected = frame(b"abc");
+        expected.extend(frame(b"defg"));
+        assert_eq!(framed.into_inner().output, expected);
+    }
+}
@@ -1 +1,3 @@
 pub mod connector;
+pub mod framed;
+pub mod server;
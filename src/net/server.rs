@@ -0,0 +1,265 @@
+//! Candidate sso servers, ranked by how fast they answer.
+
+use crate::executor::Timer;
+use crate::net::connector::ConnectorFactory;
+use crate::sync::Mutex;
+use futures::stream::FuturesUnordered;
+use futures::StreamExt;
+use std::future::Future;
+use std::io;
+use std::net::SocketAddr;
+use std::time::{Duration, Instant};
+
+/// How long connecting to a server may take before it counts as unreachable.
+pub const CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Server {
+    pub addr: SocketAddr,
+    /// How long the last connection took, `None` until one was made.
+    pub latency: Option<Duration>,
+    /// The last attempt to connect failed.
+    pub unreachable: bool,
+}
+
+/// Candidate servers, fastest first.
+///
+/// Servers not probed yet rank after those that were reached, and unreachable ones last.
+/// The server lists of `ConfigPushSvc.PushReq` replace the candidates, unless the list
+/// is [fixed](ServerList::fixed).
+#[derive(Clone, Debug, Default)]
+pub struct ServerList {
+    servers: Vec<Server>,
+    fixed: bool,
+}
+
+impl ServerList {
+    pub fn new<I: IntoIterator<Item = SocketAddr>>(addrs: I) -> Self {
+        let mut list = Self::default();
+        list.replace(addrs);
+        list
+    }
+
+    /// A list of just `addrs`, ignoring the servers the server pushes, such as for tests.
+    pub fn fixed<I: IntoIterator<Item = SocketAddr>>(addrs: I) -> Self {
+        Self {
+            fixed: true,
+            ..Self::new(addrs)
+        }
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.servers.is_empty()
+    }
+
+    #[inline]
+    pub fn servers(&self) -> &[Server] {
+        &self.servers
+    }
+
+    /// Every candidate, best first.
+    pub fn addrs(&self) -> Vec<SocketAddr> {
+        self.servers.iter().map(|s| s.addr).collect()
+    }
+
+    /// The best candidate not known to be unreachable.
+    pub fn fastest(&self) -> Option<SocketAddr> {
+        self.servers.iter().find(|s| !s.unreachable).map(|s| s.addr)
+    }
+
+    /// Takes the candidates of a server push, keeping what is known about those already listed.
+    ///
+    /// Does nothing to a [fixed](ServerList::fixed) list.
+    pub fn update<I: IntoIterator<Item = SocketAddr>>(&mut self, addrs: I) {
+        if !self.fixed {
+            self.replace(addrs);
+        }
+    }
+
+    fn replace<I: IntoIterator<Item = SocketAddr>>(&mut self, addrs: I) {
+        let mut servers: Vec<Server> = vec![];
+        for addr in addrs {
+            if servers.iter().any(|s| s.addr == addr) {
+                continue;
+            }
+
+            let known = self.servers.iter().find(|s| s.addr == addr);
+            servers.push(known.cloned().unwrap_or(Server {
+                addr,
+                latency: None,
+                unreachable: false,
+            }));
+        }
+
+        self.servers = servers;
+        self.rank();
+    }
+
+    /// Records how connecting to `addr` went, `None` for a failure.
+    pub fn record(&mut self, addr: SocketAddr, latency: Option<Duration>) {
+        let Some(server) = self.servers.iter_mut().find(|s| s.addr == addr) else {
+            return;
+        };
+
+        server.unreachable = latency.is_none();
+        if latency.is_some() {
+            server.latency = latency;
+        }
+        self.rank();
+    }
+
+    fn rank(&mut self) {
+        // stable, so servers that tie keep their order
+        self.servers
+            .sort_by_key(|s| (s.unreachable, s.latency.is_none(), s.latency));
+    }
+
+    /// Connects to every candidate at once and ranks them by the time it took.
+    pub async fn probe<R, T>(&mut self, factory: &R, timer: &T)
+    where
+        R: ConnectorFactory,
+        T: Timer,
+    {
+        let mut probes: FuturesUnordered<_> = self
+            .addrs()
+            .into_iter()
+            .map(|addr| timed(addr, factory.connect(addr), timer))
+            .collect();
+
+        while let Some((addr, rsp)) = probes.next().await {
+            self.record(addr, rsp.map(|(latency, _)| latency));
+        }
+    }
+}
+
+impl From<Vec<SocketAddr>> for ServerList {
+    #[inline]
+    fn from(addrs: Vec<SocketAddr>) -> Self {
+        Self::new(addrs)
+    }
+}
+
+/// Connects to every candidate of `servers` at once, keeping the first connection made.
+///
+/// Every attempt that ends before it is recorded in the list, those still running are
+/// dropped. Gives `None` if none of them connects.
+pub(crate) async fn connect_fastest<F, Fu, C, T>(
+    servers: &Mutex<ServerList>,
+    connect: F,
+    timer: &T,
+) -> Option<(SocketAddr, C)>
+where
+    F: Fn(SocketAddr) -> Fu,
+    Fu: Future<Output = io::Result<C>>,
+    T: Timer,
+{
+    let addrs = servers.lock().addrs();
+    let mut probes: FuturesUnordered<_> = addrs
+        .into_iter()
+        .map(|addr| timed(addr, connect(addr), timer))
+        .collect();
+
+    while let Some((addr, rsp)) = probes.next().await {
+        match rsp {
+            Some((latency, connector)) => {
+                servers.lock().record(addr, Some(latency));
+                return Some((addr, connector));
+            }
+            None => servers.lock().record(addr, None),
+        }
+    }
+
+    None
+}
+
+/// Runs a connection attempt, giving how long it took if it went through in time.
+async fn timed<Fu, C, T>(
+    addr: SocketAddr,
+    connect: Fu,
+    timer: &T,
+) -> (SocketAddr, Option<(Duration, C)>)
+where
+    Fu: Future<Output = io::Result<C>>,
+    T: Timer,
+{
+    let start = Instant::now();
+    match timer.timeout(CONNECT_TIMEOUT, connect).await {
+        Some(Ok(connector)) => (addr, Some((start.elapsed(), connector))),
+        _ => (addr, None),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::executor::timer::ThreadTimer;
+    use crate::net::server::{connect_fastest, ServerList};
+    use crate::sync::Mutex;
+    use futures::future;
+    use std::io;
+    use std::net::{SocketAddr, TcpListener, TcpStream};
+    use std::time::Duration;
+
+    fn addr(port: u16) -> SocketAddr {
+        SocketAddr::from(([127, 0, 0, 1], port))
+    }
+
+    #[test]
+    fn rank() {
+        let mut list = ServerList::new([addr(1), addr(2), addr(3), addr(1)]);
+        assert_eq!(list.addrs(), [addr(1), addr(2), addr(3)]);
+        assert_eq!(list.fastest(), Some(addr(1)));
+
+        list.record(addr(3), Some(Duration::from_millis(5)));
+        list.record(addr(2), Some(Duration::from_millis(10)));
+        list.record(addr(1), None);
+        assert_eq!(list.addrs(), [addr(3), addr(2), addr(1)]);
+
+        // a failure keeps the latency of the last success, but ranks after those reached
+        list.record(addr(3), None);
+        assert_eq!(list.addrs(), [addr(2), addr(3), addr(1)]);
+        assert_eq!(list.servers()[1].latency, Some(Duration::from_millis(5)));
+        assert_eq!(list.fastest(), Some(addr(2)));
+
+        // pushed servers replace the candidates, keeping what is known of the rest
+        list.update([addr(4), addr(2)]);
+        assert_eq!(list.addrs(), [addr(2), addr(4)]);
+        list.record(addr(9), None);
+        assert_eq!(list.addrs(), [addr(2), addr(4)]);
+
+        let mut fixed = ServerList::fixed([addr(1)]);
+        fixed.update([addr(2)]);
+        assert_eq!(fixed.addrs(), [addr(1)]);
+    }
+
+    #[test]
+    fn probe() {
+        let live = TcpListener::bind("127.0.0.1:0").unwrap();
+        let dead = TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap();
+        let live = live.local_addr().unwrap();
+
+        let mut list = ServerList::new([dead, live]);
+        let connect = |addr| future::ready(TcpStream::connect(addr));
+        futures::executor::block_on(list.probe(&connect, &ThreadTimer));
+        assert_eq!(list.addrs(), [live, dead]);
+        assert!(list.servers()[0].latency.is_some());
+        assert!(list.servers()[1].unreachable);
+
+        let list = Mutex::new(ServerList::new([dead, live]));
+        let (addr, _) =
+            futures::executor::block_on(connect_fastest(&list, connect, &ThreadTimer)).unwrap();
+        assert_eq!(addr, live);
+
+        let refused = |_| {
+            future::ready(Err::<TcpStream, _>(io::Error::from(
+                io::ErrorKind::ConnectionRefused,
+            )))
+        };
+        let rsp = futures::executor::block_on(connect_fastest(&list, refused, &ThreadTimer));
+        assert!(rsp.is_none());
+        assert_eq!(list.lock().fastest(), None);
+    }
+}
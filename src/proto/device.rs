@@ -0,0 +1,23 @@
+use prost::Message;
+
+#[derive(Message)]
+pub struct DeviceReport {
+    #[prost(bytes)]
+    pub bootloader: Vec<u8>,
+    #[prost(bytes)]
+    pub proc_version: Vec<u8>,
+    #[prost(bytes)]
+    pub codename: Vec<u8>,
+    #[prost(bytes)]
+    pub incremental: Vec<u8>,
+    #[prost(bytes)]
+    pub fingerprint: Vec<u8>,
+    #[prost(bytes)]
+    pub boot_id: Vec<u8>,
+    #[prost(bytes)]
+    pub android_id: Vec<u8>,
+    #[prost(bytes)]
+    pub base_band: Vec<u8>,
+    #[prost(bytes)]
+    pub inner_version: Vec<u8>,
+}
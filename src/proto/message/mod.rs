@@ -1,17 +1,649 @@
+use bytes::Bytes;
 use prost::{Enumeration, Message};
 
+/// `OnlinePush.PbPushGroupMsg`
+#[derive(Message)]
+pub struct PushMessagePacket {
+    #[prost(message, optional)]
+    pub message: Option<Msg>,
+}
+
+#[derive(Message)]
+pub struct Msg {
+    #[prost(message, optional)]
+    pub head: Option<MessageHead>,
+    #[prost(message, optional)]
+    pub content_head: Option<ContentHead>,
+    #[prost(message, optional)]
+    pub body: Option<MessageBody>,
+}
+
 #[derive(Message)]
 pub struct MessageHead {
     #[prost(uint64)]
-    from_uin: u64,
+    pub from_uin: u64,
     #[prost(uint64)]
-    to_uin: u64,
+    pub to_uin: u64,
     #[prost(enumeration = "MessageHeadType")]
-    msg_type: i32,
+    pub msg_type: i32,
+    #[prost(int32)]
+    pub c2c_cmd: i32,
+    #[prost(int32)]
+    pub msg_seq: i32,
+    #[prost(int32)]
+    pub msg_time: i32,
+    #[prost(int64)]
+    pub msg_uid: i64,
+    #[prost(message, optional)]
+    pub c2c_tmp_msg_head: Option<C2cTmpMsgHead>,
+    #[prost(message, optional)]
+    pub group_info: Option<GroupInfo>,
+    /// The member who joined, for [`MessageHeadType::MemberJoin`].
+    #[prost(uint64, tag = "15")]
+    pub auth_uin: u64,
 }
 
 #[derive(Debug, Enumeration)]
 #[repr(i32)]
 pub enum MessageHeadType {
+    MemberJoin = 33,
     Troop = 82,
+    /// Someone asked to join a group this account manages.
+    GroupRequest = 84,
+    /// A member invited someone to a group this account manages.
+    GroupInvite = 87,
+    Temp = 141,
+    Friend = 166,
+    FriendRequest = 187,
+    FriendVoice = 208,
+}
+
+#[derive(Message)]
+pub struct C2cTmpMsgHead {
+    #[prost(int32)]
+    pub c2c_type: i32,
+    #[prost(int32)]
+    pub service_type: i32,
+    #[prost(uint64)]
+    pub group_uin: u64,
+    #[prost(uint64)]
+    pub group_code: u64,
+}
+
+#[derive(Message)]
+pub struct GroupInfo {
+    #[prost(uint64)]
+    pub group_code: u64,
+    #[prost(int32)]
+    pub group_type: i32,
+    #[prost(int64)]
+    pub group_info_seq: i64,
+    #[prost(bytes = "bytes")]
+    pub group_card: Bytes,
+    #[prost(bytes = "bytes")]
+    pub group_rank: Bytes,
+    #[prost(int32)]
+    pub group_level: i32,
+    #[prost(int32)]
+    pub group_card_type: i32,
+    #[prost(bytes = "bytes")]
+    pub group_name: Bytes,
+}
+
+/// How a long message was split into packets.
+#[derive(Message)]
+pub struct ContentHead {
+    #[prost(int32)]
+    pub pkg_num: i32,
+    #[prost(int32)]
+    pub pkg_index: i32,
+    #[prost(int32)]
+    pub div_seq: i32,
+}
+
+#[derive(Message)]
+pub struct MessageBody {
+    #[prost(message, optional)]
+    pub rich_text: Option<RichText>,
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct RichText {
+    #[prost(message, optional)]
+    pub attr: Option<Attr>,
+    /// [`Elem`]s, left encoded so those not understood can be passed on as they are.
+    #[prost(bytes = "bytes", repeated)]
+    pub elems: Vec<Bytes>,
+    #[prost(message, optional, tag = "4")]
+    pub ptt: Option<Ptt>,
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct Attr {
+    #[prost(int32)]
+    pub code_page: i32,
+    #[prost(int32)]
+    pub time: i32,
+    #[prost(int32)]
+    pub random: i32,
+}
+
+#[derive(Message)]
+pub struct Elem {
+    #[prost(message, optional, tag = "1")]
+    pub text: Option<Text>,
+    #[prost(message, optional, tag = "2")]
+    pub face: Option<Face>,
+    #[prost(message, optional, tag = "4")]
+    pub not_online_image: Option<NotOnlineImage>,
+    #[prost(message, optional, tag = "6")]
+    pub market_face: Option<MarketFace>,
+    #[prost(message, optional, tag = "8")]
+    pub custom_face: Option<CustomFace>,
+    #[prost(message, optional, tag = "12")]
+    pub rich_msg: Option<RichMsg>,
+    #[prost(message, optional, tag = "13")]
+    pub group_file: Option<GroupFile>,
+    #[prost(message, optional, tag = "45")]
+    pub src_msg: Option<SourceMsg>,
+    #[prost(message, optional, tag = "51")]
+    pub light_app: Option<LightAppElem>,
+    #[prost(message, optional, tag = "53")]
+    pub common_elem: Option<CommonElem>,
+}
+
+#[derive(Message)]
+pub struct Text {
+    #[prost(string)]
+    pub str: String,
+    #[prost(string)]
+    pub link: String,
+    /// Marks the text as an at, see [`crate::message::At`].
+    #[prost(bytes = "bytes")]
+    pub attr6_buf: Bytes,
+    #[prost(bytes = "bytes")]
+    pub attr7_buf: Bytes,
+    #[prost(bytes = "bytes", tag = "11")]
+    pub buf: Bytes,
+    #[prost(bytes = "bytes")]
+    pub pb_reserve: Bytes,
+}
+
+#[derive(Message)]
+pub struct Face {
+    #[prost(int32)]
+    pub index: i32,
+    #[prost(bytes = "bytes")]
+    pub old: Bytes,
+    #[prost(bytes = "bytes", tag = "11")]
+    pub buf: Bytes,
+}
+
+/// A group image.
+#[derive(Clone, PartialEq, Message)]
+pub struct CustomFace {
+    #[prost(bytes = "bytes")]
+    pub guid: Bytes,
+    #[prost(string)]
+    pub file_path: String,
+    #[prost(string)]
+    pub shortcut: String,
+    #[prost(bytes = "bytes")]
+    pub buffer: Bytes,
+    #[prost(bytes = "bytes")]
+    pub flag: Bytes,
+    #[prost(bytes = "bytes")]
+    pub old_data: Bytes,
+    #[prost(uint32)]
+    pub file_id: u32,
+    #[prost(int32)]
+    pub server_ip: i32,
+    #[prost(int32)]
+    pub server_port: i32,
+    #[prost(int32)]
+    pub file_type: i32,
+    #[prost(bytes = "bytes")]
+    pub signature: Bytes,
+    #[prost(int32)]
+    pub useful: i32,
+    #[prost(bytes = "bytes")]
+    pub md5: Bytes,
+    #[prost(string)]
+    pub thumb_url: String,
+    #[prost(string)]
+    pub big_url: String,
+    #[prost(string)]
+    pub orig_url: String,
+    #[prost(int32)]
+    pub biz_type: i32,
+    #[prost(int32)]
+    pub repeat_index: i32,
+    #[prost(int32)]
+    pub repeat_image: i32,
+    #[prost(int32)]
+    pub image_type: i32,
+    #[prost(int32)]
+    pub index: i32,
+    #[prost(int32)]
+    pub width: i32,
+    #[prost(int32)]
+    pub height: i32,
+    #[prost(int32)]
+    pub source: i32,
+    #[prost(uint32)]
+    pub size: u32,
+    #[prost(int32)]
+    pub origin: i32,
+    #[prost(int32)]
+    pub thumb_width: i32,
+    #[prost(int32)]
+    pub thumb_height: i32,
+    #[prost(int32)]
+    pub show_len: i32,
+    #[prost(int32)]
+    pub download_len: i32,
+    #[prost(string)]
+    pub url_400: String,
+    #[prost(int32)]
+    pub width_400: i32,
+    #[prost(int32)]
+    pub height_400: i32,
+    #[prost(bytes = "bytes")]
+    pub pb_reserve: Bytes,
+}
+
+/// A friend image.
+#[derive(Clone, PartialEq, Message)]
+pub struct NotOnlineImage {
+    #[prost(string)]
+    pub file_path: String,
+    #[prost(uint32)]
+    pub file_len: u32,
+    #[prost(string)]
+    pub download_path: String,
+    #[prost(bytes = "bytes")]
+    pub old_ver_send_file: Bytes,
+    #[prost(int32)]
+    pub img_type: i32,
+    #[prost(bytes = "bytes")]
+    pub previews_image: Bytes,
+    #[prost(bytes = "bytes")]
+    pub pic_md5: Bytes,
+    #[prost(uint32)]
+    pub pic_height: u32,
+    #[prost(uint32)]
+    pub pic_width: u32,
+    #[prost(string)]
+    pub res_id: String,
+    #[prost(bytes = "bytes")]
+    pub flag: Bytes,
+    #[prost(string)]
+    pub thumb_url: String,
+    #[prost(int32)]
+    pub original: i32,
+    #[prost(string)]
+    pub big_url: String,
+    #[prost(string)]
+    pub orig_url: String,
+    #[prost(int32)]
+    pub biz_type: i32,
+    #[prost(int32)]
+    pub result: i32,
+    #[prost(int32)]
+    pub index: i32,
+    #[prost(bytes = "bytes")]
+    pub op_face_buf: Bytes,
+    #[prost(bool)]
+    pub old_pic_md5: bool,
+    #[prost(uint32)]
+    pub thumb_width: u32,
+    #[prost(uint32)]
+    pub thumb_height: u32,
+    #[prost(int32)]
+    pub file_id: i32,
+    #[prost(uint32)]
+    pub show_len: u32,
+    #[prost(uint32)]
+    pub download_len: u32,
+    #[prost(string)]
+    pub url_400: String,
+    #[prost(int32)]
+    pub width_400: i32,
+    #[prost(int32)]
+    pub height_400: i32,
+    #[prost(bytes = "bytes")]
+    pub pb_reserve: Bytes,
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct MarketFace {
+    #[prost(bytes = "bytes")]
+    pub face_name: Bytes,
+    #[prost(uint32)]
+    pub item_type: u32,
+    #[prost(uint32)]
+    pub face_info: u32,
+    #[prost(bytes = "bytes")]
+    pub face_id: Bytes,
+    #[prost(uint32)]
+    pub tab_id: u32,
+    #[prost(uint32)]
+    pub sub_type: u32,
+    #[prost(bytes = "bytes")]
+    pub key: Bytes,
+    #[prost(bytes = "bytes")]
+    pub param: Bytes,
+    #[prost(uint32)]
+    pub media_type: u32,
+    #[prost(uint32)]
+    pub image_width: u32,
+    #[prost(uint32)]
+    pub image_height: u32,
+    #[prost(bytes = "bytes")]
+    pub mobile_param: Bytes,
+    #[prost(bytes = "bytes")]
+    pub pb_reserve: Bytes,
+}
+
+/// An xml message, its template compressed behind a flag byte.
+#[derive(Message)]
+pub struct RichMsg {
+    #[prost(bytes = "bytes")]
+    pub template1: Bytes,
+    #[prost(int32)]
+    pub service_id: i32,
+    #[prost(bytes = "bytes")]
+    pub msg_resid: Bytes,
+    #[prost(int32)]
+    pub rand: i32,
+    #[prost(int32)]
+    pub seq: i32,
+    #[prost(int32)]
+    pub flags: i32,
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct GroupFile {
+    #[prost(bytes = "bytes")]
+    pub filename: Bytes,
+    #[prost(int64)]
+    pub file_size: i64,
+    #[prost(bytes = "bytes")]
+    pub file_id: Bytes,
+    #[prost(bytes = "bytes")]
+    pub batch_id: Bytes,
+    #[prost(bytes = "bytes")]
+    pub file_key: Bytes,
+    #[prost(bytes = "bytes")]
+    pub mark: Bytes,
+    #[prost(int64)]
+    pub sequence: i64,
+    #[prost(bytes = "bytes")]
+    pub batch_item_id: Bytes,
+    #[prost(int32)]
+    pub feed_msg_time: i32,
+    #[prost(bytes = "bytes")]
+    pub pb_reserve: Bytes,
+}
+
+/// The message a reply quotes.
+#[derive(Message)]
+pub struct SourceMsg {
+    #[prost(int32, repeated)]
+    pub orig_seqs: Vec<i32>,
+    #[prost(int64)]
+    pub sender_uin: i64,
+    #[prost(int32)]
+    pub time: i32,
+    #[prost(int32)]
+    pub flag: i32,
+    #[prost(bytes = "bytes", repeated)]
+    pub elems: Vec<Bytes>,
+    #[prost(int32)]
+    pub r#type: i32,
+    #[prost(bytes = "bytes")]
+    pub rich_msg: Bytes,
+    #[prost(bytes = "bytes")]
+    pub pb_reserve: Bytes,
+    #[prost(bytes = "bytes")]
+    pub src_msg: Bytes,
+    #[prost(int64)]
+    pub to_uin: i64,
+    #[prost(bytes = "bytes")]
+    pub troop_name: Bytes,
+}
+
+/// A json message, compressed behind a flag byte.
+#[derive(Message)]
+pub struct LightAppElem {
+    #[prost(bytes = "bytes")]
+    pub data: Bytes,
+    #[prost(bytes = "bytes")]
+    pub msg_resid: Bytes,
+}
+
+#[derive(Message)]
+pub struct CommonElem {
+    #[prost(int32)]
+    pub service_type: i32,
+    #[prost(bytes = "bytes")]
+    pub pb_elem: Bytes,
+    #[prost(int32)]
+    pub business_type: i32,
+}
+
+/// The `pb_elem` of a [`CommonElem`] of service type 3, a flash image.
+#[derive(Message)]
+pub struct MsgElemInfoServtype3 {
+    #[prost(message, optional)]
+    pub flash_troop_pic: Option<CustomFace>,
+    #[prost(message, optional)]
+    pub flash_c2c_pic: Option<NotOnlineImage>,
+}
+
+/// A voice message, carried next to the elems.
+#[derive(Clone, PartialEq, Message)]
+pub struct Ptt {
+    #[prost(int32)]
+    pub file_type: i32,
+    #[prost(int64)]
+    pub src_uin: i64,
+    #[prost(bytes = "bytes")]
+    pub file_uuid: Bytes,
+    #[prost(bytes = "bytes")]
+    pub file_md5: Bytes,
+    #[prost(bytes = "bytes")]
+    pub file_name: Bytes,
+    #[prost(int32)]
+    pub file_size: i32,
+    #[prost(bytes = "bytes")]
+    pub reserve: Bytes,
+    #[prost(int32)]
+    pub file_id: i32,
+    #[prost(int32)]
+    pub server_ip: i32,
+    #[prost(int32)]
+    pub server_port: i32,
+    #[prost(bool)]
+    pub valid: bool,
+    #[prost(bytes = "bytes")]
+    pub signature: Bytes,
+    #[prost(bytes = "bytes")]
+    pub shortcut: Bytes,
+    #[prost(bytes = "bytes")]
+    pub file_key: Bytes,
+    #[prost(int32)]
+    pub magic_ptt_index: i32,
+    #[prost(int32)]
+    pub voice_switch: i32,
+    #[prost(bytes = "bytes")]
+    pub ptt_url: Bytes,
+    #[prost(bytes = "bytes")]
+    pub group_file_key: Bytes,
+    #[prost(int32)]
+    pub time: i32,
+    #[prost(bytes = "bytes")]
+    pub down_para: Bytes,
+    #[prost(int32, tag = "29")]
+    pub format: i32,
+    #[prost(bytes = "bytes")]
+    pub pb_reserve: Bytes,
+    #[prost(bytes = "bytes", repeated)]
+    pub ptt_urls: Vec<Bytes>,
+    #[prost(int32)]
+    pub download_flag: i32,
+}
+
+/// `MessageSvc.PbSendMsg`
+#[derive(Message)]
+pub struct PbSendMsgReq {
+    #[prost(message, optional)]
+    pub routing_head: Option<RoutingHead>,
+    #[prost(message, optional)]
+    pub content_head: Option<ContentHead>,
+    #[prost(message, optional)]
+    pub body: Option<MessageBody>,
+    #[prost(int32)]
+    pub msg_seq: i32,
+    #[prost(int32)]
+    pub msg_rand: i32,
+    /// A [`SyncCookie`], for friend messages.
+    #[prost(bytes = "bytes")]
+    pub sync_cookie: Bytes,
+    #[prost(int32, tag = "8")]
+    pub msg_via: i32,
+}
+
+/// Where a message goes, one of the fields set.
+#[derive(Message)]
+pub struct RoutingHead {
+    #[prost(message, optional)]
+    pub c2c: Option<C2c>,
+    #[prost(message, optional)]
+    pub grp: Option<Grp>,
+}
+
+#[derive(Message)]
+pub struct C2c {
+    #[prost(uint64)]
+    pub to_uin: u64,
+}
+
+#[derive(Message)]
+pub struct Grp {
+    #[prost(uint64)]
+    pub group_code: u64,
+}
+
+#[derive(Message)]
+pub struct SyncCookie {
+    #[prost(int64)]
+    pub time1: i64,
+    #[prost(int64)]
+    pub time: i64,
+    #[prost(int64)]
+    pub ran1: i64,
+    #[prost(int64)]
+    pub ran2: i64,
+    #[prost(int64)]
+    pub const1: i64,
+    #[prost(int64, tag = "11")]
+    pub const2: i64,
+    #[prost(int64)]
+    pub const3: i64,
+    #[prost(int64)]
+    pub last_sync_time: i64,
+    #[prost(int64)]
+    pub const4: i64,
+}
+
+#[derive(Message)]
+pub struct PbSendMsgResp {
+    #[prost(int32)]
+    pub result: i32,
+    #[prost(string)]
+    pub errmsg: String,
+    #[prost(uint32)]
+    pub send_time: u32,
+}
+
+/// `MessageSvc.PbGetMsg`
+#[derive(Message)]
+pub struct GetMessageRequest {
+    #[prost(enumeration = "SyncFlag")]
+    pub sync_flag: i32,
+    #[prost(bytes = "bytes")]
+    pub sync_cookie: Bytes,
+    #[prost(int32)]
+    pub ramble_flag: i32,
+    #[prost(int32)]
+    pub latest_ramble_number: i32,
+    #[prost(int32)]
+    pub other_ramble_number: i32,
+    #[prost(int32)]
+    pub online_sync_flag: i32,
+    #[prost(int32)]
+    pub context_flag: i32,
+    #[prost(int32)]
+    pub whisper_session_id: i32,
+    #[prost(int32)]
+    pub msg_req_type: i32,
+}
+
+#[derive(Clone, Copy, Debug, Enumeration)]
+#[repr(i32)]
+pub enum SyncFlag {
+    Start = 0,
+    Continue = 1,
+    /// Nothing more to fetch.
+    Stop = 2,
+}
+
+#[derive(Message)]
+pub struct GetMessageResponse {
+    #[prost(int32)]
+    pub result: i32,
+    #[prost(string)]
+    pub error_message: String,
+    /// Where the next `MessageSvc.PbGetMsg` carries on.
+    #[prost(bytes = "bytes")]
+    pub sync_cookie: Bytes,
+    #[prost(enumeration = "SyncFlag")]
+    pub sync_flag: i32,
+    #[prost(message, repeated)]
+    pub uin_pair_msgs: Vec<UinPairMessage>,
+}
+
+/// The messages of one conversation.
+#[derive(Message)]
+pub struct UinPairMessage {
+    #[prost(int32)]
+    pub last_read_time: i32,
+    #[prost(uint64)]
+    pub peer_uin: u64,
+    #[prost(int32)]
+    pub msg_completed: i32,
+    #[prost(message, repeated)]
+    pub messages: Vec<Msg>,
+}
+
+/// `MessageSvc.PbDeleteMsg`
+#[derive(Message)]
+pub struct PbDeleteMsgReq {
+    #[prost(message, repeated)]
+    pub msg_items: Vec<MsgItem>,
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct MsgItem {
+    #[prost(uint64)]
+    pub from_uin: u64,
+    #[prost(uint64)]
+    pub to_uin: u64,
+    #[prost(int32)]
+    pub msg_type: i32,
+    #[prost(int32)]
+    pub msg_seq: i32,
+    #[prost(int64)]
+    pub msg_uid: i64,
 }
@@ -1 +1,4 @@
+pub mod device;
 pub mod message;
+pub mod notify;
+pub mod structmsg;
@@ -0,0 +1,182 @@
+use bytes::Bytes;
+use prost::Message;
+
+/// The protobuf part of the group notices `0x10`, `0x11`, `0x14` and `0x15` in
+/// `OnlinePush.ReqPush`.
+#[derive(Message)]
+pub struct NotifyMsgBody {
+    #[prost(message, optional, tag = "11")]
+    pub opt_msg_recall: Option<MessageRecallReminder>,
+    #[prost(message, optional, tag = "26")]
+    pub opt_general_gray_tip: Option<GeneralGrayTipInfo>,
+}
+
+#[derive(Message)]
+pub struct MessageRecallReminder {
+    /// Who recalled the messages.
+    #[prost(uint64)]
+    pub uin: u64,
+    #[prost(bytes = "bytes")]
+    pub nickname: Bytes,
+    #[prost(message, repeated)]
+    pub recalled_msg_list: Vec<RecalledMessageMeta>,
+}
+
+#[derive(Message)]
+pub struct RecalledMessageMeta {
+    #[prost(int32)]
+    pub seq: i32,
+    #[prost(int32)]
+    pub time: i32,
+    #[prost(int32)]
+    pub msg_random: i32,
+    #[prost(int32)]
+    pub msg_type: i32,
+    #[prost(int32)]
+    pub msg_flag: i32,
+    #[prost(uint64)]
+    pub author_uin: u64,
+}
+
+/// A grey line of text in a conversation, such as the one a poke leaves.
+#[derive(Message)]
+pub struct GeneralGrayTipInfo {
+    #[prost(int64)]
+    pub busi_type: i64,
+    #[prost(int64)]
+    pub busi_id: i64,
+    #[prost(int32)]
+    pub ctrl_flag: i32,
+    #[prost(int32)]
+    pub c2c_type: i32,
+    #[prost(int32)]
+    pub service_type: i32,
+    #[prost(int64)]
+    pub templ_id: i64,
+    /// The values filled into the template, such as `uin_str1`.
+    #[prost(message, repeated)]
+    pub msg_templ_param: Vec<TemplParam>,
+    #[prost(string)]
+    pub content: String,
+}
+
+#[derive(Message)]
+pub struct TemplParam {
+    #[prost(string)]
+    pub name: String,
+    #[prost(string)]
+    pub value: String,
+}
+
+/// Friend notice `0x8a` in `OnlinePush.ReqPush`, a recall.
+#[derive(Message)]
+pub struct Sub8a {
+    #[prost(message, repeated)]
+    pub msg_info: Vec<Sub8aMsgInfo>,
+}
+
+#[derive(Message)]
+pub struct Sub8aMsgInfo {
+    #[prost(uint64)]
+    pub from_uin: u64,
+    #[prost(uint64)]
+    pub to_uin: u64,
+    #[prost(int32)]
+    pub msg_seq: i32,
+    #[prost(int64)]
+    pub msg_uid: i64,
+    #[prost(int32)]
+    pub msg_time: i32,
+    #[prost(int32)]
+    pub msg_random: i32,
+}
+
+/// Friend notice `0xb3` in `OnlinePush.ReqPush`, a new friend.
+#[derive(Message)]
+pub struct SubB3 {
+    #[prost(int32)]
+    pub r#type: i32,
+    #[prost(message, optional)]
+    pub msg_add_frd_notify: Option<SubB3AddFrdNotify>,
+}
+
+#[derive(Message)]
+pub struct SubB3AddFrdNotify {
+    #[prost(uint64)]
+    pub fuin: u64,
+    #[prost(string, tag = "5")]
+    pub fuin_nick: String,
+}
+
+/// Friend notice `0x27` in `OnlinePush.ReqPush`, profile and friend list changes.
+#[derive(Message)]
+pub struct Sub27 {
+    #[prost(message, repeated)]
+    pub mod_infos: Vec<ForwardBody>,
+}
+
+/// One change, of which the field matching `op_type` is set.
+#[derive(Message)]
+pub struct ForwardBody {
+    #[prost(int32)]
+    pub notify_type: i32,
+    #[prost(int32)]
+    pub op_type: i32,
+    #[prost(message, optional, tag = "12")]
+    pub mod_group_profile: Option<ModGroupProfile>,
+    #[prost(message, optional, tag = "14")]
+    pub del_friend: Option<DelFriend>,
+}
+
+#[derive(Message)]
+pub struct ModGroupProfile {
+    #[prost(uint64)]
+    pub group_uin: u64,
+    #[prost(message, repeated)]
+    pub group_profile_infos: Vec<GroupProfileInfo>,
+    #[prost(uint64)]
+    pub group_code: u64,
+    /// Who made the change.
+    #[prost(uint64)]
+    pub cmd_uin: u64,
+}
+
+/// A changed field of a group, `1` being its name.
+#[derive(Message)]
+pub struct GroupProfileInfo {
+    #[prost(uint32)]
+    pub field: u32,
+    #[prost(bytes = "bytes")]
+    pub value: Bytes,
+}
+
+#[derive(Message)]
+pub struct DelFriend {
+    #[prost(uint64, repeated)]
+    pub uins: Vec<u64>,
+}
+
+/// `OnlinePush.PbPushTransMsg`
+#[derive(Message)]
+pub struct TransMsgInfo {
+    #[prost(uint64)]
+    pub from_uin: u64,
+    #[prost(uint64)]
+    pub to_uin: u64,
+    #[prost(int32)]
+    pub msg_type: i32,
+    #[prost(int32)]
+    pub msg_subtype: i32,
+    #[prost(int32)]
+    pub msg_seq: i32,
+    #[prost(int64)]
+    pub msg_uid: i64,
+    #[prost(int32)]
+    pub msg_time: i32,
+    #[prost(int32)]
+    pub real_msg_time: i32,
+    #[prost(string)]
+    pub nick_name: String,
+    #[prost(bytes = "bytes")]
+    pub msg_data: Bytes,
+}
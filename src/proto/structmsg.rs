@@ -0,0 +1,142 @@
+use prost::Message;
+
+/// `ProfileService.Pb.ReqSystemMsgNew.Friend` and `.Group`
+#[derive(Message)]
+pub struct ReqSystemMsgNew {
+    #[prost(int32)]
+    pub msg_num: i32,
+    #[prost(int64)]
+    pub latest_friend_seq: i64,
+    #[prost(int64)]
+    pub latest_group_seq: i64,
+    #[prost(int32)]
+    pub version: i32,
+    /// `2` for friend requests, `3` for group ones.
+    #[prost(int32)]
+    pub checktype: i32,
+    #[prost(message, optional)]
+    pub flag: Option<FlagInfo>,
+    #[prost(int32)]
+    pub language: i32,
+    #[prost(bool)]
+    pub is_get_frd_ribbon: bool,
+    #[prost(bool)]
+    pub is_get_grp_ribbon: bool,
+    #[prost(int64)]
+    pub friend_msg_type_flag: i64,
+}
+
+/// Which kinds of system messages the client understands.
+#[derive(Message)]
+pub struct FlagInfo {
+    #[prost(int32)]
+    pub grp_msg_kick_admin: i32,
+    #[prost(int32)]
+    pub grp_msg_hidden_grp: i32,
+    #[prost(int32)]
+    pub grp_msg_wording_down: i32,
+    #[prost(int32)]
+    pub frd_msg_get_busi_card: i32,
+    #[prost(int32)]
+    pub grp_msg_get_official_account: i32,
+    #[prost(int32)]
+    pub grp_msg_get_pay_in_group: i32,
+    #[prost(int32)]
+    pub frd_msg_discuss2_many_chat: i32,
+    #[prost(int32)]
+    pub grp_msg_not_allow_join_grp_invite_not_frd: i32,
+    #[prost(int32)]
+    pub frd_msg_need_waiting_msg: i32,
+    #[prost(int32)]
+    pub frd_msg_uint32_need_all_unread_msg: i32,
+    #[prost(int32)]
+    pub grp_msg_need_auto_admin_wording: i32,
+    #[prost(int32)]
+    pub grp_msg_get_transfer_group_msg_flag: i32,
+    #[prost(int32)]
+    pub grp_msg_get_quit_pay_group_msg_flag: i32,
+    #[prost(int32)]
+    pub grp_msg_support_invite_auto_join: i32,
+    #[prost(int32)]
+    pub grp_msg_mask_invite_auto_join: i32,
+    #[prost(int32)]
+    pub grp_msg_get_disbanded_by_admin: i32,
+    #[prost(int32)]
+    pub grp_msg_get_c2c_invite_join_group: i32,
+}
+
+#[derive(Message)]
+pub struct RspSystemMsgNew {
+    #[prost(message, optional)]
+    pub head: Option<RspHead>,
+    #[prost(int32)]
+    pub unread_friend_count: i32,
+    #[prost(int32)]
+    pub unread_group_count: i32,
+    #[prost(int64)]
+    pub latest_friend_seq: i64,
+    #[prost(int64)]
+    pub latest_group_seq: i64,
+    #[prost(message, repeated, tag = "9")]
+    pub friendmsgs: Vec<StructMsg>,
+    #[prost(message, repeated)]
+    pub groupmsgs: Vec<StructMsg>,
+}
+
+#[derive(Message)]
+pub struct RspHead {
+    #[prost(int32)]
+    pub result: i32,
+    #[prost(string)]
+    pub msg_fail: String,
+}
+
+/// A friend or group request, newest first.
+#[derive(Message)]
+pub struct StructMsg {
+    #[prost(int32)]
+    pub version: i32,
+    #[prost(int32)]
+    pub msg_type: i32,
+    /// Identifies the request when answering it.
+    #[prost(int64)]
+    pub msg_seq: i64,
+    #[prost(int64)]
+    pub msg_time: i64,
+    /// Who the request is about.
+    #[prost(uint64)]
+    pub req_uin: u64,
+    #[prost(int32)]
+    pub unread_flag: i32,
+    #[prost(message, optional, tag = "50")]
+    pub msg: Option<SystemMsg>,
+}
+
+#[derive(Message)]
+pub struct SystemMsg {
+    /// `1` while the request waits for an answer, `2` once it has one.
+    #[prost(int32)]
+    pub sub_type: i32,
+    #[prost(string)]
+    pub msg_title: String,
+    #[prost(string)]
+    pub msg_describe: String,
+    /// What the requester wrote along.
+    #[prost(string)]
+    pub msg_additional: String,
+    #[prost(uint64, tag = "10")]
+    pub group_code: u64,
+    /// The member who invited the requester, for invitations.
+    #[prost(uint64)]
+    pub action_uin: u64,
+    /// `1` for a join request, `2` for this account being invited, `22` for a member
+    /// inviting someone.
+    #[prost(int32)]
+    pub group_msg_type: i32,
+    #[prost(string, tag = "51")]
+    pub req_uin_nick: String,
+    #[prost(string)]
+    pub group_name: String,
+    #[prost(string)]
+    pub action_uin_nick: String,
+}
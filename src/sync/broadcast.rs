@@ -0,0 +1,205 @@
+//! A channel handing every value to each of its receivers, keeping only the latest few.
+
+use crate::sync::Mutex;
+use futures::Stream;
+use std::collections::{HashMap, VecDeque};
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll, Waker};
+
+struct State<T> {
+    /// The values still kept, the first of them at `head`.
+    buffer: VecDeque<T>,
+    head: u64,
+    capacity: usize,
+    closed: bool,
+    receivers: usize,
+    next_id: usize,
+    wakers: HashMap<usize, Waker>,
+}
+
+impl<T> State<T> {
+    /// The position of the next value sent.
+    fn tail(&self) -> u64 {
+        self.head + self.buffer.len() as u64
+    }
+}
+
+/// A channel keeping the last `capacity` values for receivers that fall behind.
+///
+/// Receivers are made by the [`Subscribers`], and only see what is sent after.
+pub(crate) fn channel<T: Clone>(capacity: usize) -> (Sender<T>, Subscribers<T>) {
+    assert!(capacity > 0, "a broadcast channel needs room for a value");
+
+    let state = Arc::new(Mutex::new(State {
+        buffer: VecDeque::with_capacity(capacity),
+        head: 0,
+        capacity,
+        closed: false,
+        receivers: 0,
+        next_id: 0,
+        wakers: HashMap::new(),
+    }));
+
+    (
+        Sender {
+            state: state.clone(),
+        },
+        Subscribers { state },
+    )
+}
+
+/// Closes the channel once dropped, receivers end after what is left.
+pub(crate) struct Sender<T> {
+    state: Arc<Mutex<State<T>>>,
+}
+
+impl<T> Sender<T> {
+    /// Sends `value` to every receiver, dropping the oldest value once full.
+    ///
+    /// Nothing is kept while nobody listens.
+    pub fn send(&self, value: T) {
+        let wakers = {
+            let mut state = self.state.lock();
+            if state.receivers == 0 {
+                return;
+            }
+
+            if state.buffer.len() == state.capacity {
+                state.buffer.pop_front();
+                state.head += 1;
+            }
+            state.buffer.push_back(value);
+            std::mem::take(&mut state.wakers)
+        };
+
+        wakers.into_values().for_each(Waker::wake);
+    }
+}
+
+impl<T> Drop for Sender<T> {
+    fn drop(&mut self) {
+        let wakers = {
+            let mut state = self.state.lock();
+            state.closed = true;
+            std::mem::take(&mut state.wakers)
+        };
+
+        wakers.into_values().for_each(Waker::wake);
+    }
+}
+
+/// Makes receivers of a channel, without keeping it open.
+pub(crate) struct Subscribers<T> {
+    state: Arc<Mutex<State<T>>>,
+}
+
+impl<T> Subscribers<T> {
+    pub fn subscribe(&self) -> Receiver<T> {
+        let mut state = self.state.lock();
+        state.receivers += 1;
+        state.next_id += 1;
+
+        Receiver {
+            state: self.state.clone(),
+            id: state.next_id,
+            next: state.tail(),
+        }
+    }
+}
+
+/// The values sent after it was made, or `Err` with how many it missed by lagging behind.
+pub(crate) struct Receiver<T> {
+    state: Arc<Mutex<State<T>>>,
+    id: usize,
+    /// The position of the next value to receive.
+    next: u64,
+}
+
+impl<T: Clone> Stream for Receiver<T> {
+    type Item = Result<T, u64>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = &mut *self;
+        let mut state = this.state.lock();
+
+        if this.next < state.head {
+            let missed = state.head - this.next;
+            this.next = state.head;
+            return Poll::Ready(Some(Err(missed)));
+        }
+
+        match state.buffer.get((this.next - state.head) as usize) {
+            Some(value) => {
+                this.next += 1;
+                Poll::Ready(Some(Ok(value.clone())))
+            }
+            None if state.closed => Poll::Ready(None),
+            None => {
+                state.wakers.insert(this.id, cx.waker().clone());
+                Poll::Pending
+            }
+        }
+    }
+}
+
+impl<T> Drop for Receiver<T> {
+    fn drop(&mut self) {
+        let mut state = self.state.lock();
+        state.receivers -= 1;
+        state.wakers.remove(&self.id);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::sync::broadcast::channel;
+    use futures::{FutureExt, StreamExt};
+
+    #[test]
+    fn broadcast() {
+        let (tx, subscribers) = channel(2);
+        // nobody listens yet
+        tx.send(0);
+
+        let mut a = subscribers.subscribe();
+        let mut b = subscribers.subscribe();
+        assert!(a.next().now_or_never().is_none());
+
+        tx.send(1);
+        tx.send(2);
+        assert_eq!(a.next().now_or_never(), Some(Some(Ok(1))));
+        assert_eq!(a.next().now_or_never(), Some(Some(Ok(2))));
+        assert_eq!(b.next().now_or_never(), Some(Some(Ok(1))));
+
+        // b falls behind and misses what no longer fits
+        tx.send(3);
+        tx.send(4);
+        assert_eq!(a.next().now_or_never(), Some(Some(Ok(3))));
+        assert_eq!(b.next().now_or_never(), Some(Some(Err(1))));
+        assert_eq!(b.next().now_or_never(), Some(Some(Ok(3))));
+
+        // late subscribers start at the tail
+        let mut c = subscribers.subscribe();
+        drop(tx);
+        assert_eq!(futures::executor::block_on(a.collect::<Vec<_>>()), [Ok(4)]);
+        assert_eq!(futures::executor::block_on(b.collect::<Vec<_>>()), [Ok(4)]);
+        assert_eq!(c.next().now_or_never(), Some(None));
+    }
+
+    #[test]
+    fn wake() {
+        let (tx, subscribers) = channel(4);
+        let rx = subscribers.subscribe();
+
+        let sender = std::thread::spawn(move || {
+            for i in 0..3 {
+                std::thread::sleep(std::time::Duration::from_millis(5));
+                tx.send(i);
+            }
+        });
+        let values: Vec<_> = futures::executor::block_on(rx.collect());
+        sender.join().unwrap();
+        assert_eq!(values, [Ok(0), Ok(1), Ok(2)]);
+    }
+}
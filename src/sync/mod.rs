@@ -1,3 +1,5 @@
+pub(crate) mod broadcast;
+
 #[cfg(feature = "parking_lot")]
 mod parking_lot {
     use parking_lot::lock_api::MutexGuard;
@@ -24,7 +26,7 @@ mod parking_lot {
         }
 
         #[inline]
-        pub fn lock(&self) -> MutexGuard<RawMutex, T> {
+        pub fn lock(&self) -> MutexGuard<'_, RawMutex, T> {
             self.inner.lock()
         }
     }
@@ -62,7 +64,7 @@ mod standard {
             }
         }
 
-        pub fn lock(&self) -> MutexGuard<T> {
+        pub fn lock(&self) -> MutexGuard<'_, T> {
             match self.inner.lock() {
                 Ok(g) => g,
                 Err(e) => e.into_inner(),
@@ -0,0 +1,389 @@
+#![allow(dead_code)]
+
+//! A minimal in-process sso server, enough to drive the client through wtlogin.
+
+use atri_core::crypto::ecdh::{Ecdh, ServerPublicKey};
+use atri_core::crypto::tea::Tea;
+use atri_core::crypto::Transport;
+use atri_core::data::tlv::{TlvMap, TlvWriter};
+use bytes::{Buf, BufMut, Bytes, BytesMut};
+use digest::Digest;
+use std::io::{Read, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{mpsc, Arc, Mutex};
+use std::thread;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+pub const SERVER_SECRET: [u8; 32] = [0x42; 32];
+pub const EMPTY_KEY: [u8; 16] = [0; 16];
+pub const D2_KEY: [u8; 16] = [0xd2; 16];
+
+pub fn server_key() -> ServerPublicKey {
+    let ecdh = Ecdh::from_secret(&SERVER_SECRET, ServerPublicKey::default()).unwrap();
+    ServerPublicKey::new(1, ecdh.public_key()).unwrap()
+}
+
+pub fn md5(data: &[u8]) -> [u8; 16] {
+    let mut arr = [0; 16];
+    arr.copy_from_slice(&md5::Md5::digest(data));
+    arr
+}
+
+pub fn tlv(tag: u16, value: &[u8]) -> Bytes {
+    let mut w = TlvWriter::new(tag);
+    w.put_slice(value);
+    w.into_bytes()
+}
+
+fn put_lv32(buf: &mut BytesMut, bytes: &[u8]) {
+    buf.put_u32(bytes.len() as u32 + 4);
+    buf.put_slice(bytes);
+}
+
+fn get_lv32(buf: &mut Bytes) -> Bytes {
+    let len = buf.get_u32() as usize - 4;
+    buf.split_to(len)
+}
+
+/// An sso packet sent by the client.
+pub struct Request {
+    pub seq: u32,
+    pub command: String,
+    pub uin: u64,
+    pub body: Bytes,
+}
+
+/// The decrypted oicq body of a `wtlogin.*` request.
+#[derive(Clone)]
+pub struct OicqRequest {
+    pub command: u16,
+    pub sub_command: u16,
+    pub encrypt_type: u8,
+    pub share_key: [u8; 16],
+    /// The whole decrypted body, for requests that don't carry plain tlvs.
+    pub body: Bytes,
+    pub tlvs: TlvMap,
+}
+
+impl Request {
+    pub fn oicq(&self, st_key: &[u8; 16]) -> OicqRequest {
+        let mut body = self.body.clone();
+        assert_eq!(body.get_u8(), 0x02);
+        body.advance(4);
+        let command = body.get_u16();
+        body.advance(6);
+        body.advance(1);
+        let encrypt_type = body.get_u8();
+        body.advance(13);
+        let body = body.slice(..body.len() - 1);
+
+        let (share_key, encrypted) = match encrypt_type {
+            0x87 => {
+                let mut body = body;
+                body.advance(2 + 16 + 2 + 2);
+                let len = body.get_u16() as usize;
+                let public_key = body.split_to(len);
+                let ecdh = Ecdh::from_secret(
+                    &SERVER_SECRET,
+                    ServerPublicKey::new(0, &public_key).unwrap(),
+                )
+                .unwrap();
+                (*ecdh.share_key(), body)
+            }
+            0x45 => {
+                let mut body = body;
+                let len = body.get_u16() as usize;
+                body.advance(len);
+                (*st_key, body)
+            }
+            t => panic!("unknown encrypt type {:#x}", t),
+        };
+
+        let body = Tea::from_bytes(&share_key).decrypt(&encrypted).unwrap();
+        let sub_command = u16::from_be_bytes([body[0], body[1]]);
+        let tlvs = TlvMap::decode(body.slice(4..)).unwrap_or_default();
+
+        OicqRequest {
+            command,
+            sub_command,
+            encrypt_type,
+            share_key,
+            body,
+            tlvs,
+        }
+    }
+}
+
+impl OicqRequest {
+    /// Builds the oicq reply frame, encrypted the way the request was.
+    pub fn reply(&self, uin: u64, status: u8, tlvs: &[Bytes]) -> Bytes {
+        let mut body = BytesMut::new();
+        body.put_u16(self.sub_command);
+        body.put_u8(status);
+        body.put_u16(tlvs.len() as u16);
+        for t in tlvs {
+            body.put_slice(t);
+        }
+        self.reply_raw(uin, &body)
+    }
+
+    /// Like [`OicqRequest::reply`] with a body of any shape.
+    pub fn reply_raw(&self, uin: u64, body: &[u8]) -> Bytes {
+        let body = Tea::from_bytes(&self.share_key).encrypt(body);
+
+        let mut buf = BytesMut::new();
+        buf.put_u8(0x02);
+        buf.put_u16((body.len() + 17) as u16);
+        buf.put_u16(8001);
+        buf.put_u16(self.command);
+        buf.put_u16(1);
+        buf.put_u32(uin as u32);
+        buf.put_u8(0);
+        buf.put_u8(if self.encrypt_type == 0x45 { 3 } else { 0 });
+        buf.put_u8(0);
+        buf.put_slice(&body);
+        buf.put_u8(0x03);
+        buf.freeze()
+    }
+
+    /// The tgtgt key sealed in `t106`.
+    pub fn tgtgt_key(&self, uin: u64, password: &str) -> [u8; 16] {
+        let mut key = [0; 24];
+        key[..16].copy_from_slice(&md5(password.as_bytes()));
+        key[20..].copy_from_slice(&(uin as u32).to_be_bytes());
+
+        let a1 = Tea::from_bytes(&md5(&key))
+            .decrypt(self.tlvs.require(0x106).unwrap())
+            .unwrap();
+        a1[51..67].try_into().unwrap()
+    }
+
+    /// Command of a `wtlogin.trans_emp` request, and its code2d body.
+    pub fn trans_emp(&self) -> (u16, Bytes) {
+        // trans header (14) | u32 time | 0x02 | u16 len | u16 cmd | 38 | body | 0x03
+        let command = u16::from_be_bytes([self.body[21], self.body[22]]);
+        (command, self.body.slice(61..self.body.len() - 1))
+    }
+
+    /// Replies to a `wtlogin.trans_emp` request with a code2d `body`.
+    pub fn trans_emp_reply(&self, body: &[u8]) -> Bytes {
+        let (command, _) = self.trans_emp();
+
+        let mut buf = BytesMut::new();
+        buf.put_bytes(0, 5);
+        buf.put_u8(0x02);
+        buf.put_u16((43 + body.len() + 1) as u16);
+        buf.put_u16(command);
+        buf.put_bytes(0, 38);
+        buf.put_slice(body);
+        buf.put_u8(0x03);
+        self.reply_raw(0, &buf)
+    }
+}
+
+pub fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_secs()
+}
+
+/// A session of `uin` saved after an earlier login, its d2 running out in `d2_expires_in`
+/// seconds. The tickets differ from the ones [`t119`] grants.
+pub fn saved_session(uin: u64, d2_expires_in: u64) -> Transport {
+    let mut session = Transport::new(uin);
+    session.ksid = "ksid".into();
+    session.st.a2 = "old-a2".into();
+    session.st.d2 = "old-d2".into();
+    session.st.d2_key = D2_KEY;
+    session.st.a2_expire_time = now() + 24 * 3600;
+    session.st.d2_expire_time = now() + d2_expires_in;
+    session
+}
+
+/// `t119` granting `d2` with [`D2_KEY`], encrypted with `key`.
+pub fn t119(key: &[u8; 16], a1: &[u8]) -> Bytes {
+    let tlvs = [
+        tlv(0x106, a1),
+        tlv(0x10a, b"a2"),
+        tlv(0x10d, &[0xa2; 16]),
+        tlv(0x143, b"d2"),
+        tlv(0x305, &D2_KEY),
+        tlv(0x108, b"ksid"),
+        tlv(0x133, b"st"),
+        tlv(0x134, &[0x5e; 16]),
+    ];
+
+    let mut body = BytesMut::new();
+    body.put_u16(tlvs.len() as u16);
+    for t in &tlvs {
+        body.put_slice(t);
+    }
+
+    tlv(0x119, &Tea::from_bytes(key).encrypt(&body))
+}
+
+pub struct Session {
+    stream: TcpStream,
+    pub d2_key: [u8; 16],
+}
+
+impl Session {
+    pub fn read(&mut self) -> Option<Request> {
+        let mut len = [0; 4];
+        self.stream.read_exact(&mut len).ok()?;
+        let mut frame = vec![0; u32::from_be_bytes(len) as usize - 4];
+        self.stream.read_exact(&mut frame).ok()?;
+        let mut frame = Bytes::from(frame);
+
+        let detail = frame.get_u32();
+        let flag = frame.get_u8();
+        let outer_seq = match detail {
+            0x0A => {
+                get_lv32(&mut frame);
+                None
+            }
+            _ => Some(frame.get_u32()),
+        };
+        frame.advance(1);
+        let uin = String::from_utf8(get_lv32(&mut frame).to_vec()).unwrap();
+
+        let mut inner = match flag {
+            0 => frame,
+            1 => Tea::from_bytes(&self.d2_key).decrypt(&frame).unwrap(),
+            _ => Tea::from_bytes(&EMPTY_KEY).decrypt(&frame).unwrap(),
+        };
+
+        let mut head = get_lv32(&mut inner);
+        let (seq, command) = match outer_seq {
+            None => {
+                let seq = head.get_u32();
+                head.advance(20);
+                get_lv32(&mut head);
+                (seq, get_lv32(&mut head))
+            }
+            Some(seq) => (seq, get_lv32(&mut head)),
+        };
+
+        Some(Request {
+            seq,
+            command: String::from_utf8(command.to_vec()).unwrap(),
+            uin: uin.parse().unwrap_or(0),
+            body: get_lv32(&mut inner),
+        })
+    }
+
+    pub fn write(&mut self, seq: u32, command: &str, uin: u64, key: &[u8; 16], body: &[u8]) {
+        let mut head = BytesMut::new();
+        head.put_u32(seq);
+        head.put_i32(0);
+        put_lv32(&mut head, b"");
+        put_lv32(&mut head, command.as_bytes());
+        put_lv32(&mut head, &[0; 4]);
+        head.put_u32(0);
+
+        let mut inner = BytesMut::new();
+        put_lv32(&mut inner, &head);
+        put_lv32(&mut inner, body);
+
+        let mut frame = BytesMut::new();
+        frame.put_u32(0);
+        frame.put_u32(0x0B);
+        frame.put_u8(if key == &EMPTY_KEY { 2 } else { 1 });
+        frame.put_u8(0);
+        put_lv32(&mut frame, uin.to_string().as_bytes());
+        frame.put_slice(&Tea::from_bytes(key).encrypt(&inner));
+        let len = frame.len() as u32;
+        frame[..4].copy_from_slice(&len.to_be_bytes());
+
+        let _ = self.stream.write_all(&frame);
+    }
+
+    /// Replies to `req` with the same seq and command.
+    pub fn reply(&mut self, req: &Request, body: &[u8]) {
+        let key = if req.command.starts_with("wtlogin.") {
+            EMPTY_KEY
+        } else {
+            self.d2_key
+        };
+        self.write(req.seq, &req.command, req.uin, &key, body);
+    }
+
+    /// Sends a server initiated packet.
+    pub fn push(&mut self, command: &str, uin: u64, body: &[u8]) {
+        let key = self.d2_key;
+        self.write(0, command, uin, &key, body);
+    }
+
+    pub fn shutdown(&mut self) {
+        let _ = self.stream.shutdown(std::net::Shutdown::Both);
+    }
+}
+
+pub struct MockServer {
+    pub addr: SocketAddr,
+    closed: mpsc::Receiver<()>,
+    killed: Arc<AtomicBool>,
+    current: Arc<Mutex<Option<TcpStream>>>,
+    thread: Mutex<Option<thread::JoinHandle<()>>>,
+}
+
+impl MockServer {
+    /// Serves connections one at a time, handing every request to `handler`.
+    pub fn spawn<H>(mut handler: H) -> Self
+    where
+        H: FnMut(&mut Session, Request) + Send + 'static,
+    {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let (closed_tx, closed) = mpsc::channel();
+        let killed = Arc::new(AtomicBool::new(false));
+        let current = Arc::new(Mutex::new(None));
+
+        let (kill, serving) = (killed.clone(), current.clone());
+        let thread = thread::spawn(move || {
+            for stream in listener.incoming() {
+                let Ok(stream) = stream else { break };
+                if kill.load(Ordering::Acquire) {
+                    break;
+                }
+                *serving.lock().unwrap() = stream.try_clone().ok();
+                let mut session = Session {
+                    stream,
+                    d2_key: D2_KEY,
+                };
+                while let Some(req) = session.read() {
+                    handler(&mut session, req);
+                }
+                let _ = closed_tx.send(());
+            }
+        });
+
+        Self {
+            addr,
+            closed,
+            killed,
+            current,
+            thread: Mutex::new(Some(thread)),
+        }
+    }
+
+    /// Drops the connection being served and stops listening, as a crashed server would.
+    pub fn kill(&self) {
+        self.killed.store(true, Ordering::Release);
+        if let Some(stream) = self.current.lock().unwrap().take() {
+            let _ = stream.shutdown(std::net::Shutdown::Both);
+        }
+        // wakes up the accept loop, which sees the flag and closes the listener
+        let _ = TcpStream::connect(self.addr);
+        if let Some(thread) = self.thread.lock().unwrap().take() {
+            let _ = thread.join();
+        }
+    }
+
+    /// Waits for the client to close a connection.
+    pub fn wait_closed(&self, timeout: Duration) -> bool {
+        self.closed.recv_timeout(timeout).is_ok()
+    }
+}
@@ -0,0 +1,401 @@
+mod common;
+
+use atri_core::client::{Client, LoginResponse, LoginVerifier};
+use atri_core::error::ClientError;
+use atri_core::executor::runtime::blocking::Runtime;
+use atri_core::executor::{Executor, JoinHandle, Timer};
+use common::{md5, now, saved_session, server_key, t119, tlv, MockServer, D2_KEY};
+use std::future::Future;
+use std::net::TcpStream;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+const UIN: u64 = 10001;
+const PASSWORD: &str = "password";
+
+fn login(server: &MockServer) -> (LoginResponse, atri_core::crypto::Transport) {
+    let mut builder = Client::builder()
+        .with_default_handler()
+        .with_executor(Runtime)
+        .with_server_key(server_key())
+        .with_connector(TcpStream::connect(server.addr).unwrap());
+
+    let rsp = futures::executor::block_on(builder.login(UIN, PASSWORD)).unwrap();
+    (rsp, builder.session())
+}
+
+#[test]
+fn password_login() {
+    let server = MockServer::spawn(|session, req| {
+        assert_eq!(req.command, "wtlogin.login");
+        assert_eq!(req.uin, UIN);
+
+        let oicq = req.oicq(&[0; 16]);
+        assert_eq!(oicq.command, 0x810);
+        assert_eq!(oicq.sub_command, 9);
+        assert_eq!(oicq.tlvs.len(), 23);
+        for tag in [
+            0x18, 0x1, 0x106, 0x116, 0x100, 0x144, 0x145, 0x154, 0x191, 0x525,
+        ] {
+            assert!(oicq.tlvs.contains(tag), "missing tlv {:#x}", tag);
+        }
+
+        let tgtgt_key = oicq.tgtgt_key(UIN, PASSWORD);
+        let a1 = oicq.tlvs.get(0x106).unwrap().clone();
+        let body = oicq.reply(UIN, 0, &[t119(&tgtgt_key, &a1)]);
+        session.reply(&req, &body);
+    });
+
+    let (rsp, session) = login(&server);
+    assert_eq!(rsp, LoginResponse::Success);
+    assert_eq!(session.uin, UIN);
+    assert_eq!(&session.st.d2[..], b"d2");
+    assert_eq!(session.st.d2_key, D2_KEY);
+    assert_eq!(&session.st.a2[..], b"a2");
+    assert_eq!(&session.ksid[..], b"ksid");
+    assert!(session.is_logged_in());
+}
+
+#[test]
+fn login_failures() {
+    let server = MockServer::spawn(|session, req| {
+        let oicq = req.oicq(&[0; 16]);
+        let body = match req.seq % 4 {
+            0 => oicq.reply(
+                UIN,
+                1,
+                &[tlv(0x146, b"\0\0\0\x01\0\x05error\0\x0ewrong password")],
+            ),
+            1 => oicq.reply(
+                UIN,
+                2,
+                &[
+                    tlv(0x104, b"t104"),
+                    tlv(0x192, b"https://captcha.example/slider"),
+                ],
+            ),
+            2 => oicq.reply(
+                UIN,
+                160,
+                &[
+                    tlv(0x104, b"t104"),
+                    tlv(0x174, b"t174"),
+                    tlv(0x178, b"\0\x0286\0\x0b138****0000"),
+                ],
+            ),
+            _ => oicq.reply(UIN, 40, &[tlv(0x146, b"\0\0\0\0\0\0\0\x06frozen")]),
+        };
+        session.reply(&req, &body);
+    });
+
+    let mut builder = Client::builder()
+        .with_default_handler()
+        .with_executor(Runtime)
+        .with_server_key(server_key())
+        .with_connector(TcpStream::connect(server.addr).unwrap());
+
+    futures::executor::block_on(async {
+        assert_eq!(
+            builder.login(UIN, PASSWORD).await.unwrap(),
+            LoginResponse::WrongPassword {
+                message: "error: wrong password".into()
+            }
+        );
+        assert_eq!(
+            builder.login(UIN, PASSWORD).await.unwrap(),
+            LoginResponse::NeedCaptcha {
+                url: "https://captcha.example/slider".into()
+            }
+        );
+        assert_eq!(
+            builder.login(UIN, PASSWORD).await.unwrap(),
+            LoginResponse::NeedSms {
+                phone: "138****0000".into()
+            }
+        );
+        assert_eq!(
+            builder.login(UIN, PASSWORD).await.unwrap(),
+            LoginResponse::AccountFrozen {
+                message: "frozen".into()
+            }
+        );
+    });
+
+    assert!(!builder.session().is_logged_in());
+}
+
+struct Script {
+    tickets: Vec<String>,
+    phones: Vec<String>,
+}
+
+impl LoginVerifier for Script {
+    async fn slider(&mut self, url: &str) -> Option<String> {
+        assert_eq!(url, "https://captcha.example/slider");
+        self.tickets.pop()
+    }
+
+    async fn sms(&mut self, phone: &str) -> Option<String> {
+        self.phones.push(phone.into());
+        Some("123456".into())
+    }
+}
+
+#[test]
+fn verifier() {
+    let mut a1 = None;
+    let mut sms_sent = false;
+    let server = MockServer::spawn(move |session, req| {
+        let oicq = req.oicq(&[0; 16]);
+        let tlvs = &oicq.tlvs;
+        if oicq.sub_command != 9 {
+            assert_eq!(&tlvs.get(0x104).unwrap()[..], b"t104");
+            assert!(tlvs.contains(0x8) && tlvs.contains(0x116));
+        }
+
+        let sms = [
+            tlv(0x104, b"t104"),
+            tlv(0x174, b"t174"),
+            tlv(0x178, b"\0\x0286\0\x0b138****0000"),
+            tlv(0x402, b"t402"),
+        ];
+
+        let body = match oicq.sub_command {
+            9 => {
+                a1 = Some(oicq.clone());
+                oicq.reply(
+                    UIN,
+                    2,
+                    &[
+                        tlv(0x104, b"t104"),
+                        tlv(0x192, b"https://captcha.example/slider"),
+                    ],
+                )
+            }
+            2 => {
+                assert_eq!(&tlvs.get(0x193).unwrap()[..], b"ticket");
+                oicq.reply(UIN, 160, &sms)
+            }
+            8 => {
+                assert_eq!(&tlvs.get(0x174).unwrap()[..], b"t174");
+                sms_sent = true;
+                oicq.reply(UIN, 160, &sms)
+            }
+            7 => {
+                assert!(sms_sent);
+                assert_eq!(&tlvs.get(0x17c).unwrap()[..], b"\0\x06123456");
+                assert_eq!(tlvs.get(0x401).unwrap().len(), 16);
+                oicq.reply(UIN, 204, &[tlv(0x104, b"t104")])
+            }
+            20 => {
+                let first = a1.as_ref().unwrap();
+                let tgtgt_key = first.tgtgt_key(UIN, PASSWORD);
+                let a1 = first.tlvs.get(0x106).unwrap().clone();
+                oicq.reply(UIN, 0, &[t119(&tgtgt_key, &a1)])
+            }
+            sub => panic!("unexpected sub command {}", sub),
+        };
+        session.reply(&req, &body);
+    });
+
+    let mut builder = Client::builder()
+        .with_default_handler()
+        .with_executor(Runtime)
+        .with_server_key(server_key())
+        .with_connector(TcpStream::connect(server.addr).unwrap());
+
+    let mut script = Script {
+        tickets: vec!["ticket".into()],
+        phones: vec![],
+    };
+    let rsp = futures::executor::block_on(builder.login_with(UIN, PASSWORD, &mut script));
+
+    assert_eq!(rsp.unwrap(), LoginResponse::Success);
+    assert_eq!(script.phones, ["138****0000"]);
+    assert!(builder.session().is_logged_in());
+}
+
+#[test]
+fn verifier_gives_up() {
+    let server = MockServer::spawn(|session, req| {
+        let oicq = req.oicq(&[0; 16]);
+        let body = oicq.reply(UIN, 2, &[tlv(0x192, b"https://captcha.example/slider")]);
+        session.reply(&req, &body);
+    });
+
+    let mut builder = Client::builder()
+        .with_default_handler()
+        .with_executor(Runtime)
+        .with_server_key(server_key())
+        .with_connector(TcpStream::connect(server.addr).unwrap());
+
+    let mut script = Script {
+        tickets: vec![],
+        phones: vec![],
+    };
+    let rsp = futures::executor::block_on(builder.login_with(UIN, PASSWORD, &mut script));
+    assert_eq!(
+        rsp.unwrap(),
+        LoginResponse::NeedCaptcha {
+            url: "https://captcha.example/slider".into()
+        }
+    );
+}
+
+#[test]
+fn verifier_repeats() {
+    for (status, expected) in [
+        (204, LoginResponse::DeviceLockLogin),
+        (
+            160,
+            LoginResponse::NeedSms {
+                phone: "138****0000".into(),
+            },
+        ),
+    ] {
+        let requests = Arc::new(AtomicUsize::new(0));
+        let counter = requests.clone();
+        let server = MockServer::spawn(move |session, req| {
+            counter.fetch_add(1, Ordering::SeqCst);
+            let oicq = req.oicq(&[0; 16]);
+            let body = oicq.reply(
+                UIN,
+                status,
+                &[
+                    tlv(0x104, b"t104"),
+                    tlv(0x174, b"t174"),
+                    tlv(0x178, b"\0\x0286\0\x0b138****0000"),
+                ],
+            );
+            session.reply(&req, &body);
+        });
+
+        let mut builder = Client::builder()
+            .with_default_handler()
+            .with_executor(Runtime)
+            .with_server_key(server_key())
+            .with_connector(TcpStream::connect(server.addr).unwrap());
+
+        let mut script = Script {
+            tickets: vec![],
+            phones: vec![],
+        };
+        let rsp = futures::executor::block_on(builder.login_with(UIN, PASSWORD, &mut script));
+        assert_eq!(rsp.unwrap(), expected);
+
+        // the login, then a single answer (sms also asks for the code to be sent)
+        let answers = if status == 160 { 2 } else { 1 };
+        assert_eq!(requests.load(Ordering::SeqCst), 1 + answers);
+    }
+}
+
+#[test]
+fn token_login() {
+    let server = MockServer::spawn(|session, req| {
+        assert_eq!(req.command, "wtlogin.exchange_emp");
+        assert_eq!(req.uin, UIN);
+
+        let oicq = req.oicq(&[0; 16]);
+        assert_eq!(oicq.sub_command, 11);
+        assert_eq!(&oicq.tlvs.get(0x10a).unwrap()[..], b"old-a2");
+        assert_eq!(&oicq.tlvs.get(0x143).unwrap()[..], b"old-d2");
+        assert_eq!(&oicq.tlvs.get(0x108).unwrap()[..], b"ksid");
+
+        let status = if req.seq == 0 { 0 } else { 1 };
+        let body = oicq.reply(UIN, status, &[t119(&md5(&D2_KEY), b"")]);
+        session.reply(&req, &body);
+    });
+
+    let mut builder = Client::builder()
+        .with_default_handler()
+        .with_executor(Runtime)
+        .with_server_key(server_key())
+        .with_connector(TcpStream::connect(server.addr).unwrap());
+
+    futures::executor::block_on(async {
+        // fresh d2 is used as is
+        builder
+            .token_login(saved_session(UIN, 24 * 3600))
+            .await
+            .unwrap();
+        assert_eq!(&builder.session().st.d2[..], b"old-d2");
+
+        builder.token_login(saved_session(UIN, 60)).await.unwrap();
+        let session = builder.session();
+        assert_eq!(&session.st.d2[..], b"d2");
+        assert_eq!(session.st.d2_key, D2_KEY);
+        assert!(session.st.d2_expire_time > now() + 3600);
+
+        // the server refusing the exchange means the tickets are gone
+        assert!(matches!(
+            builder.token_login(saved_session(UIN, 60)).await,
+            Err(ClientError::TokenExpired)
+        ));
+
+        let mut expired = saved_session(UIN, 60);
+        expired.st.a2_expire_time = now() - 1;
+        assert!(matches!(
+            builder.token_login(expired).await,
+            Err(ClientError::TokenExpired)
+        ));
+    });
+}
+
+/// Runs on [`Runtime`], but gives up on every wait after a fraction of a second.
+struct Impatient;
+
+impl Executor for Impatient {
+    fn spawn<F>(&self, fu: F) -> JoinHandle<F::Output>
+    where
+        F: Future + Send,
+        F: 'static,
+        F::Output: Send + 'static,
+    {
+        Runtime.spawn(fu)
+    }
+}
+
+impl Timer for Impatient {
+    type Sleep = <Runtime as Timer>::Sleep;
+
+    fn sleep(&self, _: Duration) -> Self::Sleep {
+        Runtime.sleep(Duration::from_millis(200))
+    }
+}
+
+#[test]
+fn unanswered() {
+    let server = MockServer::spawn(|session, req| {
+        if req.seq != 0 {
+            return;
+        }
+
+        // nothing the client can decode, skipped while it waits for the reply
+        session.write(7, "wtlogin.login", UIN, &[0x42; 16], b"stray");
+        let oicq = req.oicq(&[0; 16]);
+        let body = oicq.reply(UIN, 0, &[t119(&oicq.tgtgt_key(UIN, PASSWORD), b"")]);
+        session.reply(&req, &body);
+    });
+
+    let stream = TcpStream::connect(server.addr).unwrap();
+    stream
+        .set_read_timeout(Some(Duration::from_millis(20)))
+        .unwrap();
+    let mut builder = Client::builder()
+        .with_default_handler()
+        .with_executor(Impatient)
+        .with_server_key(server_key())
+        .with_connector(stream);
+
+    futures::executor::block_on(async {
+        let rsp = builder.login(UIN, PASSWORD).await.unwrap();
+        assert_eq!(rsp, LoginResponse::Success);
+
+        // the server takes the request, but never answers
+        assert!(matches!(
+            builder.login(UIN, PASSWORD).await,
+            Err(ClientError::Timeout)
+        ));
+    });
+}
@@ -0,0 +1,123 @@
+mod common;
+
+use atri_core::client::{Client, LoginResponse, QrCodeState};
+use atri_core::executor::runtime::blocking::Runtime;
+use bytes::{Buf, BufMut, BytesMut};
+use common::{server_key, t119, tlv, MockServer};
+use futures::StreamExt;
+use std::net::TcpStream;
+
+const UIN: u64 = 10002;
+const TGTGT_KEY: [u8; 16] = [0x1e; 16];
+
+fn state(code: u8) -> BytesMut {
+    let mut body = BytesMut::new();
+    body.put_u16(0);
+    body.put_u32(16);
+    body.put_u8(code);
+    body
+}
+
+#[test]
+fn qrcode_login() {
+    let mut queries = 0;
+    let server = MockServer::spawn(move |session, req| {
+        let oicq = req.oicq(&[0; 16]);
+
+        let body = match &*req.command {
+            "wtlogin.trans_emp" => {
+                assert_eq!(req.uin, 0);
+                assert_eq!(oicq.command, 0x812);
+
+                let (command, mut body) = oicq.trans_emp();
+                match command {
+                    0x31 => {
+                        let mut rsp = BytesMut::new();
+                        rsp.put_u16(0);
+                        rsp.put_u32(0);
+                        rsp.put_u8(0);
+                        rsp.put_u16(6);
+                        rsp.put_slice(b"qr-sig");
+                        rsp.put_u16(1);
+                        rsp.put_slice(&tlv(0x17, b"\x89PNG"));
+                        oicq.trans_emp_reply(&rsp)
+                    }
+                    0x12 => {
+                        body.advance(2 + 1 + 4 + 4);
+                        let len = body.get_u16() as usize;
+                        assert_eq!(&body[..len], b"qr-sig");
+
+                        queries += 1;
+                        let rsp = match queries {
+                            1 | 2 => state(0x30),
+                            3 => state(0x35),
+                            _ => {
+                                let mut rsp = state(0);
+                                rsp.put_u64(UIN);
+                                rsp.put_u32(0);
+                                rsp.put_u16(4);
+                                rsp.put_slice(&tlv(0x18, b"tmp-pwd"));
+                                rsp.put_slice(&tlv(0x19, b"no-pic-sig"));
+                                rsp.put_slice(&tlv(0x1e, &TGTGT_KEY));
+                                rsp.put_slice(&tlv(0x65, b"tgt-qr"));
+                                rsp
+                            }
+                        };
+                        oicq.trans_emp_reply(&rsp)
+                    }
+                    c => panic!("unexpected code2d command {:#x}", c),
+                }
+            }
+            "wtlogin.login" => {
+                assert_eq!(req.uin, UIN);
+                assert_eq!(oicq.sub_command, 9);
+                assert_eq!(oicq.tlvs.len(), 24);
+                assert_eq!(&oicq.tlvs.get(0x106).unwrap()[..], b"tmp-pwd");
+                assert_eq!(&oicq.tlvs.get(0x16a).unwrap()[..], b"no-pic-sig");
+                assert_eq!(&oicq.tlvs.get(0x318).unwrap()[..], b"tgt-qr");
+
+                oicq.reply(UIN, 0, &[t119(&TGTGT_KEY, b"a1")])
+            }
+            c => panic!("unexpected command {}", c),
+        };
+        session.reply(&req, &body);
+    });
+
+    let mut builder = Client::builder()
+        .with_default_handler()
+        .with_executor(Runtime)
+        .with_server_key(server_key())
+        .with_connector(TcpStream::connect(server.addr).unwrap());
+
+    futures::executor::block_on(async {
+        let qrcode = builder.fetch_qrcode().await.unwrap();
+        assert_eq!(&qrcode.image[..], b"\x89PNG");
+        assert_eq!(&qrcode.sig[..], b"qr-sig");
+
+        let mut delays = 0;
+        let states: Vec<_> = builder
+            .watch_qrcode(qrcode.sig.clone(), || {
+                delays += 1;
+                async {}
+            })
+            .collect()
+            .await;
+
+        assert_eq!(delays, 3);
+        assert_eq!(states.len(), 3);
+        assert!(matches!(states[0], Ok(QrCodeState::WaitingForScan)));
+        assert!(matches!(states[1], Ok(QrCodeState::WaitingForConfirm)));
+        let Ok(QrCodeState::Confirmed(info)) = &states[2] else {
+            panic!("not confirmed");
+        };
+        assert_eq!(info.uin, UIN);
+
+        let rsp = builder.qrcode_login(info).await.unwrap();
+        assert_eq!(rsp, LoginResponse::Success);
+    });
+
+    let session = builder.session();
+    assert_eq!(session.uin, UIN);
+    assert_eq!(&session.st.a1[..], b"a1");
+    assert!(session.is_logged_in());
+}
@@ -1,19 +1,25 @@
 use atri_core::executor::runtime::smol::Runtime;
-use atri_core::executor::Executor;
+use atri_core::executor::runtime::{blocking, smol as smol_rt};
+use atri_core::executor::{Executor, Timer};
 use atri_core::net::connector::send_all;
+use futures::StreamExt;
 use std::io::Read;
 use std::net::TcpListener;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
 
 #[test]
 fn smol() {
     let listener = TcpListener::bind("127.0.0.1:8889").unwrap();
 
-    Runtime.spawn(async {
-        let mut c = smol::net::TcpStream::connect("127.0.0.1:8889")
-            .await
-            .unwrap();
-        send_all(&mut c, "123".as_bytes()).await.unwrap();
-    });
+    Runtime
+        .spawn(async {
+            let mut c = smol::net::TcpStream::connect("127.0.0.1:8889")
+                .await
+                .unwrap();
+            send_all(&mut c, "123".as_bytes()).await.unwrap();
+        })
+        .detach();
 
     let mut s = String::new();
     listener.accept().unwrap().0.read_to_string(&mut s).unwrap();
@@ -33,7 +39,8 @@ fn tokio() {
             .await
             .unwrap();
         send_all(&mut c, "123".as_bytes()).await.unwrap();
-    });
+    })
+    .detach();
 
     let mut s = String::new();
     listener.accept().unwrap().0.read_to_string(&mut s).unwrap();
@@ -44,12 +51,69 @@ fn tokio() {
 fn blocking() {
     let listener = TcpListener::bind("127.0.0.1:8800").unwrap();
 
-    Runtime.spawn(async {
-        let mut c = std::net::TcpStream::connect("127.0.0.1:8800").unwrap();
-        send_all(&mut c, "123".as_bytes()).await.unwrap();
-    });
+    Runtime
+        .spawn(async {
+            let mut c = std::net::TcpStream::connect("127.0.0.1:8800").unwrap();
+            send_all(&mut c, "123".as_bytes()).await.unwrap();
+        })
+        .detach();
 
     let mut s = String::new();
     listener.accept().unwrap().0.read_to_string(&mut s).unwrap();
     assert_eq!(s, "123");
 }
+
+fn check_timer<T: Timer>(timer: T) {
+    futures::executor::block_on(async {
+        let start = Instant::now();
+        timer.sleep(Duration::from_millis(20)).await;
+        assert!(start.elapsed() >= Duration::from_millis(20));
+
+        let rsp = timer.timeout(Duration::from_secs(5), async { 1 }).await;
+        assert_eq!(rsp, Some(1));
+        let pending = futures::future::pending::<()>();
+        let rsp = timer.timeout(Duration::from_millis(10), pending).await;
+        assert_eq!(rsp, None);
+
+        let start = Instant::now();
+        let ticks: Vec<_> = timer
+            .interval(Duration::from_millis(10))
+            .take(3)
+            .collect()
+            .await;
+        assert!(start.elapsed() >= Duration::from_millis(30));
+        assert!(ticks[2] - ticks[0] >= Duration::from_millis(20));
+    });
+}
+
+#[test]
+fn timer() {
+    check_timer(blocking::Runtime);
+    check_timer(Arc::new(smol_rt::Runtime));
+    check_timer(smol::Executor::new());
+
+    let rt = tokio::runtime::Builder::new_multi_thread()
+        .enable_time()
+        .build()
+        .unwrap();
+    check_timer(&rt);
+}
+
+fn check_join<E: Executor>(executor: E) {
+    futures::executor::block_on(async {
+        assert_eq!(executor.spawn(async { 1 }).await, Ok(1));
+
+        let pending = executor.spawn(futures::future::pending::<()>());
+        pending.abort();
+        assert!(pending.await.is_err());
+    });
+}
+
+#[test]
+fn join_handle() {
+    check_join(blocking::Runtime);
+    check_join(smol_rt::Runtime);
+
+    let rt = tokio::runtime::Builder::new_multi_thread().build().unwrap();
+    check_join(&rt);
+}
@@ -0,0 +1,901 @@
+mod common;
+
+use atri_core::client::{Backoff, Client, ClientBuilder, Request, RequestClient};
+use atri_core::crypto::Transport;
+use atri_core::error::ClientError;
+use atri_core::event::{ClientEvent, EventKind, Lagged};
+use atri_core::executor::runtime::blocking;
+use atri_core::executor::{Executor, Timer};
+use atri_core::jce::{JceReader, JceWriter, UniPacket};
+use atri_core::message::{MessageChain, MessageElement};
+use atri_core::net::connector::{Connector, ConnectorFactory};
+use atri_core::net::server::ServerList;
+use bytes::Bytes;
+use common::{md5, now, saved_session, server_key, t119, MockServer, D2_KEY, EMPTY_KEY};
+use futures::{future, StreamExt};
+use std::net::{SocketAddr, TcpStream};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{mpsc, Arc};
+use std::time::{Duration, Instant};
+
+const UIN: u64 = 10003;
+
+/// How close to expiring d2 gets exchanged, in seconds.
+const REFRESH_MARGIN: u64 = 3600;
+
+struct Echo(&'static str);
+
+impl Request for Echo {
+    type Response = Bytes;
+
+    const COMMAND: &'static str = "Test.Echo";
+
+    fn encode(&self, _: &RequestClient) -> Bytes {
+        Bytes::from_static(self.0.as_bytes())
+    }
+
+    fn decode(&self, body: Bytes) -> Result<Bytes, ClientError> {
+        Ok(body)
+    }
+}
+
+/// Tells the mock server what to do instead of replying.
+struct Unanswered(&'static str);
+
+impl Request for Unanswered {
+    type Response = ();
+
+    const COMMAND: &'static str = "Test.Unanswered";
+
+    fn encode(&self, _: &RequestClient) -> Bytes {
+        Bytes::from_static(self.0.as_bytes())
+    }
+
+    fn decode(&self, _: Bytes) -> Result<(), ClientError> {
+        Ok(())
+    }
+}
+
+fn server() -> MockServer {
+    MockServer::spawn(|session, req| match req.command.as_str() {
+        "Test.Echo" => session.reply(&req, &req.body.clone()),
+        "Test.Unanswered" => match &req.body[..] {
+            b"push" => session.push("OnlinePush.ReqPush", req.uin, b"pushed"),
+            b"hangup" => session.shutdown(),
+            _ => {}
+        },
+        c => panic!("unexpected command {}", c),
+    })
+}
+
+type Events = mpsc::Receiver<ClientEvent>;
+
+fn start<E, C>(builder: ClientBuilder<(), (), ()>, executor: E, connector: C) -> (Client, Events)
+where
+    E: Executor + Timer + Send + Sync + 'static,
+    C: Connector + Send + 'static,
+{
+    let session = saved_session(UIN, 24 * 3600);
+    start_with(builder, executor, connector, session)
+}
+
+/// Like [`start`], resuming `session` instead of one good for another day.
+fn start_with<E, C>(
+    builder: ClientBuilder<(), (), ()>,
+    executor: E,
+    connector: C,
+    session: Transport,
+) -> (Client, Events)
+where
+    E: Executor + Timer + Send + Sync + 'static,
+    C: Connector + Send + 'static,
+{
+    let (tx, events) = mpsc::channel();
+    let mut builder = builder
+        .with_handler(move |event| {
+            let tx = tx.clone();
+            async move {
+                let _ = tx.send(event);
+            }
+        })
+        .with_executor(executor)
+        .with_connector(connector);
+
+    futures::executor::block_on(builder.token_login(session)).unwrap();
+    (builder.run(), events)
+}
+
+fn exercise(server: &MockServer, client: Client, events: Events) {
+    let mut subscribed = client.subscribe();
+    futures::executor::block_on(async {
+        assert_eq!(&client.call(Echo("ping")).await.unwrap()[..], b"ping");
+
+        let (a, b) = futures::join!(client.call(Echo("a")), client.call(Echo("b")));
+        assert_eq!(&a.unwrap()[..], b"a");
+        assert_eq!(&b.unwrap()[..], b"b");
+
+        let rsp = client
+            .call_timeout(Unanswered("silent"), Duration::from_millis(50))
+            .await;
+        assert!(matches!(rsp, Err(ClientError::Timeout)));
+
+        let _ = client
+            .call_timeout(Unanswered("push"), Duration::from_millis(1))
+            .await;
+    });
+
+    let event = events.recv_timeout(Duration::from_secs(5)).unwrap();
+    match event.kind {
+        EventKind::OnlinePush { command, body } => {
+            assert_eq!(command, "OnlinePush.ReqPush");
+            assert_eq!(&body[..], b"pushed");
+        }
+        e => panic!("unexpected event {:?}", e),
+    }
+    // the event carries a handle to the same session
+    let rsp = futures::executor::block_on(event.client.call(Echo("handled")));
+    assert_eq!(&rsp.unwrap()[..], b"handled");
+    drop(event.client);
+
+    // subscribers see the same events
+    let event = futures::executor::block_on(subscribed.next())
+        .unwrap()
+        .unwrap();
+    assert!(matches!(event.kind, EventKind::OnlinePush { .. }));
+    assert_eq!(event.client.uin(), UIN);
+    drop(event);
+
+    // stopping must not wait for the next packet
+    let start = Instant::now();
+    client.stop();
+    assert!(server.wait_closed(Duration::from_secs(5)));
+    assert!(start.elapsed() < Duration::from_secs(2));
+    // and ends the subscriptions
+    assert!(futures::executor::block_on(subscribed.next()).is_none());
+}
+
+fn hangup(client: Client) {
+    // a broken connection fails pending requests right away
+    let start = Instant::now();
+    let rsp = futures::executor::block_on(
+        client.call_timeout(Unanswered("hangup"), Duration::from_secs(10)),
+    );
+    assert!(matches!(rsp, Err(ClientError::IO(_))), "{:?}", rsp.err());
+    assert!(start.elapsed() < Duration::from_secs(5));
+
+    let rsp = futures::executor::block_on(client.call(Echo("ping")));
+    assert!(matches!(rsp, Err(ClientError::IO(_))));
+
+    // every task of the session is gone
+    futures::executor::block_on(client.closed());
+}
+
+fn std_stream(server: &MockServer) -> TcpStream {
+    let stream = TcpStream::connect(server.addr).unwrap();
+    stream
+        .set_read_timeout(Some(Duration::from_millis(20)))
+        .unwrap();
+    stream
+}
+
+#[test]
+fn blocking() {
+    let server = server();
+
+    let (client, events) = start(Client::builder(), blocking::Runtime, std_stream(&server));
+    exercise(&server, client, events);
+
+    let (client, _) = start(Client::builder(), blocking::Runtime, std_stream(&server));
+    hangup(client);
+}
+
+#[test]
+fn tokio() {
+    let server = server();
+    let rt = Arc::new(
+        tokio::runtime::Builder::new_multi_thread()
+            .enable_all()
+            .build()
+            .unwrap(),
+    );
+    let connect = || {
+        rt.block_on(tokio::net::TcpStream::connect(server.addr))
+            .unwrap()
+    };
+
+    let (client, events) = start(Client::builder(), rt.clone(), connect());
+    exercise(&server, client, events);
+
+    let (client, _) = start(Client::builder(), rt.clone(), connect());
+    hangup(client);
+}
+
+#[test]
+fn subscribe() {
+    let server = server();
+    let builder = Client::builder().with_event_capacity(2);
+    let (client, events) = start(builder, blocking::Runtime, std_stream(&server));
+    let (mut first, mut second) = (client.subscribe(), client.subscribe());
+
+    let push = || {
+        let rsp = client.call_timeout(Unanswered("push"), Duration::from_millis(1));
+        let _ = futures::executor::block_on(rsp);
+        let event = events.recv_timeout(Duration::from_secs(5)).unwrap();
+        assert!(matches!(event.kind, EventKind::OnlinePush { .. }));
+    };
+    push();
+    let event = futures::executor::block_on(first.next()).unwrap().unwrap();
+    assert!(matches!(event.kind, EventKind::OnlinePush { .. }));
+
+    // the second falls behind by one of the three it was sent
+    push();
+    push();
+    let received: Vec<_> = futures::executor::block_on(async {
+        let mut received = vec![];
+        for _ in 0..3 {
+            received.push(second.next().await.unwrap().map(|e| e.kind));
+        }
+        received
+    });
+    assert!(matches!(received[0], Err(Lagged(1))));
+    assert!(received[1..]
+        .iter()
+        .all(|e| matches!(e, Ok(EventKind::OnlinePush { .. }))));
+    let first: Vec<_> = futures::executor::block_on(async {
+        let a = first.next().await.unwrap();
+        let b = first.next().await.unwrap();
+        [a.is_ok(), b.is_ok()]
+    })
+    .to_vec();
+    assert_eq!(first, [true, true]);
+
+    client.stop();
+    assert!(futures::executor::block_on(second.next()).is_none());
+}
+
+/// The parts of `msg.proto` the mock server reads and writes.
+mod msg {
+    use bytes::Bytes;
+    use prost::Message;
+
+    #[derive(Message)]
+    pub struct PbSendMsgReq {
+        #[prost(message, optional, tag = "1")]
+        pub routing_head: Option<RoutingHead>,
+        #[prost(message, optional, tag = "3")]
+        pub body: Option<MessageBody>,
+        #[prost(int32, tag = "4")]
+        pub msg_seq: i32,
+        #[prost(int32, tag = "5")]
+        pub msg_rand: i32,
+    }
+
+    #[derive(Message)]
+    pub struct RoutingHead {
+        #[prost(message, optional, tag = "1")]
+        pub c2c: Option<Uin>,
+        #[prost(message, optional, tag = "2")]
+        pub grp: Option<Uin>,
+    }
+
+    #[derive(Message)]
+    pub struct Uin {
+        #[prost(uint64, tag = "1")]
+        pub uin: u64,
+    }
+
+    #[derive(Message)]
+    pub struct PbSendMsgResp {
+        #[prost(int32, tag = "1")]
+        pub result: i32,
+        #[prost(string, tag = "2")]
+        pub errmsg: String,
+        #[prost(uint32, tag = "3")]
+        pub send_time: u32,
+    }
+
+    #[derive(Message)]
+    pub struct PushMessagePacket {
+        #[prost(message, optional, tag = "1")]
+        pub message: Option<Msg>,
+    }
+
+    #[derive(Message)]
+    pub struct Msg {
+        #[prost(message, optional, tag = "1")]
+        pub head: Option<MessageHead>,
+        #[prost(message, optional, tag = "3")]
+        pub body: Option<MessageBody>,
+    }
+
+    #[derive(Message)]
+    pub struct MessageHead {
+        #[prost(uint64, tag = "1")]
+        pub from_uin: u64,
+        #[prost(uint64, tag = "2")]
+        pub to_uin: u64,
+        #[prost(int32, tag = "3")]
+        pub msg_type: i32,
+        #[prost(int32, tag = "5")]
+        pub msg_seq: i32,
+        #[prost(int32, tag = "6")]
+        pub msg_time: i32,
+        #[prost(message, optional, tag = "9")]
+        pub group_info: Option<Uin>,
+    }
+
+    #[derive(Message)]
+    pub struct MessageBody {
+        #[prost(message, optional, tag = "1")]
+        pub rich_text: Option<RichText>,
+    }
+
+    #[derive(Message)]
+    pub struct RichText {
+        #[prost(message, optional, tag = "1")]
+        pub attr: Option<Attr>,
+        #[prost(bytes = "bytes", repeated, tag = "2")]
+        pub elems: Vec<Bytes>,
+    }
+
+    #[derive(Message)]
+    pub struct Attr {
+        #[prost(int32, tag = "3")]
+        pub random: i32,
+    }
+
+    #[derive(Message)]
+    pub struct GetMessageRequest {
+        #[prost(int32, tag = "1")]
+        pub sync_flag: i32,
+        #[prost(bytes = "bytes", tag = "2")]
+        pub sync_cookie: Bytes,
+    }
+
+    #[derive(Message)]
+    pub struct GetMessageResponse {
+        #[prost(int32, tag = "1")]
+        pub result: i32,
+        #[prost(bytes = "bytes", tag = "3")]
+        pub sync_cookie: Bytes,
+        #[prost(int32, tag = "4")]
+        pub sync_flag: i32,
+        #[prost(message, repeated, tag = "5")]
+        pub uin_pair_msgs: Vec<UinPairMessage>,
+    }
+
+    #[derive(Message)]
+    pub struct UinPairMessage {
+        #[prost(message, repeated, tag = "4")]
+        pub messages: Vec<Msg>,
+    }
+
+    #[derive(Message)]
+    pub struct PbDeleteMsgReq {
+        #[prost(message, repeated, tag = "1")]
+        pub msg_items: Vec<MsgItem>,
+    }
+
+    #[derive(Message)]
+    pub struct MsgItem {
+        #[prost(uint64, tag = "1")]
+        pub from_uin: u64,
+        #[prost(int32, tag = "4")]
+        pub msg_seq: i32,
+    }
+
+    #[derive(Message)]
+    pub struct RspSystemMsgNew {
+        #[prost(message, repeated, tag = "9")]
+        pub friendmsgs: Vec<StructMsg>,
+    }
+
+    #[derive(Message)]
+    pub struct StructMsg {
+        #[prost(int64, tag = "3")]
+        pub msg_seq: i64,
+        #[prost(uint64, tag = "5")]
+        pub req_uin: u64,
+        #[prost(message, optional, tag = "50")]
+        pub msg: Option<SystemMsg>,
+    }
+
+    #[derive(Message)]
+    pub struct SystemMsg {
+        #[prost(int32, tag = "1")]
+        pub sub_type: i32,
+        #[prost(string, tag = "4")]
+        pub msg_additional: String,
+        #[prost(string, tag = "51")]
+        pub req_uin_nick: String,
+    }
+}
+
+const MUTED_GROUP: u64 = 20002;
+/// Takes messages without echoing them back.
+const SILENT_GROUP: u64 = 20003;
+
+/// Answers `MessageSvc.PbSendMsg`, echoing group messages back as the server does.
+fn message_server() -> MockServer {
+    use prost::Message;
+
+    MockServer::spawn(|session, req| {
+        assert_eq!(req.command, "MessageSvc.PbSendMsg");
+        let sent = msg::PbSendMsgReq::decode(req.body.clone()).unwrap();
+        let routing = sent.routing_head.unwrap();
+
+        let group = routing.grp.map(|g| g.uin);
+        let rsp = match group {
+            Some(MUTED_GROUP) => msg::PbSendMsgResp {
+                result: 120,
+                errmsg: "muted".into(),
+                send_time: 0,
+            },
+            _ => msg::PbSendMsgResp {
+                result: 0,
+                errmsg: String::new(),
+                send_time: 1_600_000_000,
+            },
+        };
+        session.reply(&req, &rsp.encode_to_vec());
+
+        if let Some(group) = group.filter(|g| ![MUTED_GROUP, SILENT_GROUP].contains(g)) {
+            let mut rich_text = sent.body.unwrap().rich_text.unwrap();
+            rich_text.attr = Some(msg::Attr {
+                random: sent.msg_rand,
+            });
+            let echo = msg::PushMessagePacket {
+                message: Some(msg::Msg {
+                    head: Some(msg::MessageHead {
+                        from_uin: req.uin,
+                        msg_seq: 9000,
+                        msg_time: 1_600_000_001,
+                        group_info: Some(msg::Uin { uin: group }),
+                        ..Default::default()
+                    }),
+                    body: Some(msg::MessageBody {
+                        rich_text: Some(rich_text),
+                    }),
+                }),
+            };
+            session.push("OnlinePush.PbPushGroupMsg", req.uin, &echo.encode_to_vec());
+        }
+    })
+}
+
+#[test]
+fn send_message() {
+    let server = message_server();
+    let (client, events) = start(Client::builder(), blocking::Runtime, std_stream(&server));
+    let chain = || {
+        MessageChain::new()
+            .with("hello ")
+            .with(MessageElement::AtAll)
+    };
+
+    // the seq of a group message comes with its echo
+    let receipt = futures::executor::block_on(client.send_group_message(20001, chain())).unwrap();
+    assert_eq!(receipt.seqs, [9000]);
+    assert_eq!(receipt.time, 1_600_000_001);
+    let event = events.recv_timeout(Duration::from_secs(5)).unwrap();
+    match event.kind {
+        EventKind::GroupMessage(msg) => {
+            assert_eq!(msg.group, 20001);
+            assert_eq!(msg.sender, UIN);
+            assert_eq!(receipt.rands, [msg.id.rand]);
+            assert_eq!(msg.chain, chain());
+        }
+        e => panic!("unexpected event {:?}", e),
+    }
+
+    let receipt = futures::executor::block_on(client.send_friend_message(10002, chain())).unwrap();
+    assert_eq!(receipt.seqs.len(), 1);
+    assert_eq!(receipt.rands.len(), 1);
+    assert_eq!(receipt.time, 1_600_000_000);
+
+    let rsp = futures::executor::block_on(client.send_group_message(MUTED_GROUP, chain()));
+    assert!(matches!(
+        rsp,
+        Err(ClientError::Refused { code: 120, ref message }) if message == "muted"
+    ));
+
+    // delivered, but no seq to report without the echo
+    let receipt =
+        futures::executor::block_on(client.send_group_message(SILENT_GROUP, chain())).unwrap();
+    assert!(receipt.seqs.is_empty());
+    assert_eq!(receipt.rands.len(), 1);
+    assert_eq!(receipt.time, 1_600_000_000);
+}
+
+/// A message waiting on the mock server, of `msg_type`.
+fn waiting(msg_type: i32, from_uin: u64, msg_seq: i32) -> msg::Msg {
+    msg::Msg {
+        head: Some(msg::MessageHead {
+            from_uin,
+            to_uin: UIN,
+            msg_type,
+            msg_seq,
+            msg_time: 1_600_000_000,
+            group_info: None,
+        }),
+        body: Some(msg::MessageBody {
+            rich_text: Some(msg::RichText {
+                attr: None,
+                elems: vec![Bytes::from_static(b"\x0a\x04\x0a\x02hi")],
+            }),
+        }),
+    }
+}
+
+fn friend_request(msg_seq: i64, req_uin: u64) -> msg::StructMsg {
+    msg::StructMsg {
+        msg_seq,
+        req_uin,
+        msg: Some(msg::SystemMsg {
+            sub_type: 1,
+            msg_additional: "hello".into(),
+            req_uin_nick: "nick".into(),
+        }),
+    }
+}
+
+#[test]
+fn sync_messages() {
+    use prost::Message;
+
+    const FRIEND: i32 = 166;
+    const FRIEND_REQUEST: i32 = 187;
+
+    let (deleted_tx, deleted) = mpsc::channel();
+    let mut round = 0;
+    let server = MockServer::spawn(move |session, req| match req.command.as_str() {
+        "Test.Unanswered" => session.push("MessageSvc.PushNotify", req.uin, b""),
+        "MessageSvc.PbGetMsg" => {
+            let get = msg::GetMessageRequest::decode(req.body.clone()).unwrap();
+            round += 1;
+            // the cookie handed out comes back
+            let messages = match round {
+                1 => vec![waiting(FRIEND, 10002, 1), waiting(FRIEND_REQUEST, 10004, 2)],
+                _ => {
+                    assert_eq!(&get.sync_cookie[..], b"cookie-1");
+                    vec![waiting(FRIEND_REQUEST, 10005, 3)]
+                }
+            };
+            let rsp = msg::GetMessageResponse {
+                result: 0,
+                sync_cookie: Bytes::from(format!("cookie-{}", round)),
+                sync_flag: 2,
+                uin_pair_msgs: vec![msg::UinPairMessage { messages }],
+            };
+            session.reply(&req, &rsp.encode_to_vec());
+        }
+        "MessageSvc.PbDeleteMsg" => {
+            let del = msg::PbDeleteMsgReq::decode(req.body.clone()).unwrap();
+            let seqs: Vec<_> = del.msg_items.iter().map(|i| i.msg_seq).collect();
+            let _ = deleted_tx.send(seqs);
+            session.reply(&req, b"");
+        }
+        "ProfileService.Pb.ReqSystemMsgNew.Friend" => {
+            // still listing the request reported before, newest first
+            let friendmsgs = match round {
+                1 => vec![friend_request(100, 10004)],
+                _ => vec![friend_request(200, 10005), friend_request(100, 10004)],
+            };
+            let rsp = msg::RspSystemMsgNew { friendmsgs };
+            session.reply(&req, &rsp.encode_to_vec());
+        }
+        c => panic!("unexpected command {}", c),
+    });
+    let (client, events) = start(Client::builder(), blocking::Runtime, std_stream(&server));
+
+    let notify = || {
+        let rsp = client.call_timeout(Unanswered("notify"), Duration::from_millis(1));
+        let _ = futures::executor::block_on(rsp);
+    };
+    let next = || events.recv_timeout(Duration::from_secs(5)).unwrap().kind;
+
+    notify();
+    assert!(matches!(next(), EventKind::MessageNotify { .. }));
+    match next() {
+        EventKind::FriendMessage(msg) => {
+            assert_eq!((msg.sender, msg.target), (10002, UIN));
+            assert_eq!(msg.chain.text(), "hi");
+        }
+        e => panic!("unexpected event {:?}", e),
+    }
+    match next() {
+        EventKind::FriendRequest(req) => {
+            assert_eq!((req.seq, req.uin), (100, 10004));
+            assert_eq!(
+                (req.nickname.as_str(), req.message.as_str()),
+                ("nick", "hello")
+            );
+        }
+        e => panic!("unexpected event {:?}", e),
+    }
+    assert_eq!(
+        deleted.recv_timeout(Duration::from_secs(5)).unwrap(),
+        [1, 2]
+    );
+
+    // only the request not reported yet
+    notify();
+    assert!(matches!(next(), EventKind::MessageNotify { .. }));
+    assert!(matches!(next(), EventKind::FriendRequest(req) if req.uin == 10005));
+    assert_eq!(deleted.recv_timeout(Duration::from_secs(5)).unwrap(), [3]);
+    assert!(events.recv_timeout(Duration::from_millis(100)).is_err());
+
+    client.stop();
+}
+
+fn register_response(code: i64) -> Bytes {
+    let mut w = JceWriter::new();
+    w.put_int(0, UIN as i64);
+    w.put_int(2, code);
+
+    let mut pkt = UniPacket::new("PushService", "SvcRespRegister");
+    pkt.put("SvcRespRegister", &JceReader::new(w.into_bytes()));
+    pkt.encode()
+}
+
+fn heartbeat<S>(start: S)
+where
+    S: FnOnce(&MockServer, ClientBuilder<(), (), ()>) -> (Client, Events),
+{
+    let answering = Arc::new(AtomicBool::new(true));
+    let (seen_tx, seen) = mpsc::channel();
+
+    let answer = answering.clone();
+    let server = MockServer::spawn(move |session, req| {
+        let _ = seen_tx.send(req.command.clone());
+        match req.command.as_str() {
+            "StatSvc.register" => {
+                let code = if req.uin == UIN { 0 } else { -1 };
+                session.reply(&req, &register_response(code));
+            }
+            "Heartbeat.Alive" | "StatSvc.SimpleGet" if answer.load(Ordering::Relaxed) => {
+                session.reply(&req, b"");
+            }
+            _ => {}
+        }
+    });
+
+    let builder = Client::builder().with_heartbeat_interval(Duration::from_millis(100));
+    let (client, events) = start(&server, builder);
+    futures::executor::block_on(client.register()).unwrap();
+
+    let mut alive = 0;
+    let mut simple_get = 0;
+    while alive < 2 || simple_get < 2 {
+        match seen.recv_timeout(Duration::from_secs(5)).unwrap().as_str() {
+            "StatSvc.register" => {}
+            "Heartbeat.Alive" => alive += 1,
+            "StatSvc.SimpleGet" => simple_get += 1,
+            c => panic!("unexpected command {}", c),
+        }
+    }
+    assert!(matches!(
+        events.recv_timeout(Duration::from_secs(5)).unwrap().kind,
+        EventKind::Online
+    ));
+    assert!(events.try_recv().is_err());
+
+    // three unanswered heartbeats take the session down
+    answering.store(false, Ordering::Relaxed);
+    let start = Instant::now();
+    match events.recv_timeout(Duration::from_secs(5)).unwrap().kind {
+        EventKind::Disconnected => {}
+        e => panic!("unexpected event {:?}", e),
+    }
+    assert!(start.elapsed() >= Duration::from_millis(3 * 100));
+    assert!(server.wait_closed(Duration::from_secs(5)));
+
+    let rsp = futures::executor::block_on(client.register());
+    assert!(matches!(rsp, Err(ClientError::IO(_))));
+}
+
+#[test]
+fn heartbeat_blocking() {
+    heartbeat(|server, builder| start(builder, blocking::Runtime, std_stream(server)));
+}
+
+#[test]
+fn heartbeat_tokio() {
+    let rt = Arc::new(
+        tokio::runtime::Builder::new_multi_thread()
+            .enable_all()
+            .build()
+            .unwrap(),
+    );
+
+    heartbeat(|server, builder| {
+        let stream = rt
+            .block_on(tokio::net::TcpStream::connect(server.addr))
+            .unwrap();
+        start(builder, rt.clone(), stream)
+    });
+}
+
+#[test]
+fn register_refused() {
+    let server = MockServer::spawn(|session, req| {
+        if req.command == "StatSvc.register" {
+            session.reply(&req, &register_response(-1));
+        }
+    });
+
+    let (client, _) = start(Client::builder(), blocking::Runtime, std_stream(&server));
+    let rsp = futures::executor::block_on(client.register());
+    assert!(matches!(rsp, Err(ClientError::Refused { code: -1, .. })));
+    client.stop();
+}
+
+/// A session whose d2 comes due for an exchange a second after it starts, against a
+/// server answering the exchange with `status`.
+fn refreshing(status: u8) -> (MockServer, Client, Events, mpsc::Receiver<()>) {
+    let (exchanged_tx, exchanged) = mpsc::channel();
+    let server = MockServer::spawn(move |session, req| {
+        if req.command != "wtlogin.exchange_emp" {
+            // heartbeats
+            return session.reply(&req, b"");
+        }
+
+        let oicq = req.oicq(&EMPTY_KEY);
+        assert_eq!(&oicq.tlvs.get(0x10a).unwrap()[..], b"old-a2");
+        let body = oicq.reply(UIN, status, &[t119(&md5(&D2_KEY), b"")]);
+        session.reply(&req, &body);
+        let _ = exchanged_tx.send(());
+    });
+
+    let builder = Client::builder()
+        .with_server_key(server_key())
+        .with_heartbeat_interval(Duration::from_millis(100));
+    let session = saved_session(UIN, REFRESH_MARGIN + 1);
+    let (client, events) = start_with(builder, blocking::Runtime, std_stream(&server), session);
+    (server, client, events, exchanged)
+}
+
+#[test]
+fn token_refresh() {
+    let (_accepting, accepted, accepted_events, exchanged) = refreshing(0);
+    let (_refusing, refused, refused_events, _) = refreshing(1);
+
+    exchanged.recv_timeout(Duration::from_secs(5)).unwrap();
+    // exchanged once, d2 is good for another day
+    assert!(exchanged.recv_timeout(Duration::from_millis(300)).is_err());
+    let session = accepted.session();
+    assert_eq!(&session.st.d2[..], b"d2");
+    assert!(session.st.d2_expire_time > now() + REFRESH_MARGIN);
+    assert!(accepted_events.try_recv().is_err());
+    accepted.stop();
+
+    // without tickets the session has nothing left to do
+    match refused_events
+        .recv_timeout(Duration::from_secs(5))
+        .unwrap()
+        .kind
+    {
+        EventKind::TokenExpired => {}
+        e => panic!("unexpected event {:?}", e),
+    }
+    match refused_events
+        .recv_timeout(Duration::from_secs(5))
+        .unwrap()
+        .kind
+    {
+        EventKind::Disconnected => {}
+        e => panic!("unexpected event {:?}", e),
+    }
+    let rsp = futures::executor::block_on(refused.call(Echo("ping")));
+    assert!(matches!(rsp, Err(ClientError::IO(_))));
+}
+
+/// Answers everything a session sends, reporting each command on `seen`.
+fn online_server(seen: mpsc::Sender<(SocketAddr, String)>) -> MockServer {
+    let addr = Arc::new(std::sync::OnceLock::new());
+    let this = addr.clone();
+    let server = MockServer::spawn(move |session, req| {
+        let _ = seen.send((*this.get().unwrap(), req.command.clone()));
+        match req.command.as_str() {
+            "StatSvc.register" => session.reply(&req, &register_response(0)),
+            _ => session.reply(&req, &req.body.clone()),
+        }
+    });
+    addr.set(server.addr).unwrap();
+    server
+}
+
+fn reconnect<S, R>(start: S, factory: R)
+where
+    S: FnOnce(&MockServer, ClientBuilder<(), (), ()>) -> (Client, Events),
+    R: ConnectorFactory + Send + Sync + 'static,
+    R::Connector: Send + 'static,
+    R::Future: Send + 'static,
+{
+    let (seen_tx, seen) = mpsc::channel();
+    let first = online_server(seen_tx.clone());
+    let second = online_server(seen_tx);
+
+    let builder = Client::builder()
+        .with_reconnect(factory, ServerList::fixed([first.addr, second.addr]))
+        .with_backoff(Backoff {
+            initial: Duration::from_millis(50),
+            max: Duration::from_millis(100),
+            max_attempts: Some(4),
+        });
+    let (client, events) = start(&first, builder);
+    futures::executor::block_on(client.register()).unwrap();
+    assert_eq!(
+        seen.recv_timeout(Duration::from_secs(5)).unwrap(),
+        (first.addr, "StatSvc.register".to_string())
+    );
+    assert!(matches!(
+        events.recv_timeout(Duration::from_secs(5)).unwrap().kind,
+        EventKind::Online
+    ));
+
+    first.kill();
+    match events.recv_timeout(Duration::from_secs(5)).unwrap().kind {
+        EventKind::Reconnecting => {}
+        e => panic!("unexpected event {:?}", e),
+    }
+    // sent once the session is back
+    let rsp = futures::executor::block_on(client.call(Echo("ping")));
+    assert_eq!(&rsp.unwrap()[..], b"ping");
+    match events.recv_timeout(Duration::from_secs(5)).unwrap().kind {
+        EventKind::Reconnected { server } => assert_eq!(server, second.addr),
+        e => panic!("unexpected event {:?}", e),
+    }
+    assert_eq!(client.servers().addrs(), [second.addr, first.addr]);
+
+    // registered again before anything else goes out
+    let commands: Vec<_> = seen.try_iter().collect();
+    assert_eq!(commands[0], (second.addr, "StatSvc.register".to_string()));
+    assert!(commands.contains(&(second.addr, "Test.Echo".to_string())));
+
+    // nowhere left to go
+    second.kill();
+    match events.recv_timeout(Duration::from_secs(5)).unwrap().kind {
+        EventKind::Reconnecting => {}
+        e => panic!("unexpected event {:?}", e),
+    }
+    match events.recv_timeout(Duration::from_secs(5)).unwrap().kind {
+        EventKind::Disconnected => {}
+        e => panic!("unexpected event {:?}", e),
+    }
+    let rsp = futures::executor::block_on(client.call(Echo("ping")));
+    assert!(matches!(rsp, Err(ClientError::IO(_))));
+}
+
+#[test]
+fn reconnect_blocking() {
+    reconnect(
+        |server, builder| start(builder, blocking::Runtime, std_stream(server)),
+        |addr| {
+            future::ready(TcpStream::connect(addr).and_then(|stream| {
+                stream.set_read_timeout(Some(Duration::from_millis(20)))?;
+                Ok(stream)
+            }))
+        },
+    );
+}
+
+#[test]
+fn reconnect_tokio() {
+    let rt = Arc::new(
+        tokio::runtime::Builder::new_multi_thread()
+            .enable_all()
+            .build()
+            .unwrap(),
+    );
+
+    reconnect(
+        |server, builder| {
+            let stream = rt
+                .block_on(tokio::net::TcpStream::connect(server.addr))
+                .unwrap();
+            start(builder, rt.clone(), stream)
+        },
+        tokio::net::TcpStream::connect::<SocketAddr>,
+    );
+}